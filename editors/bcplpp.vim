@@ -21,13 +21,13 @@ hi def link bcplppLiteralKeyword Boolean
 syn keyword bcplppConditionalKeyword resultis return if unless switchon match every
 hi def link bcplppConditionalKeyword Conditional
 
-syn keyword bcplppLoopKeyword repeat while until for
+syn keyword bcplppLoopKeyword repeat repeatwhile repeatuntil loop while until for
 hi def link bcplppLoopKeyword Repeat
 
-syn keyword bcplppLabelKeyword case default break finish skip
+syn keyword bcplppLabelKeyword case default break finish skip goto
 hi def link bcplppLabelKeyword Label
 
-syn keyword bcplppOperatorKeyword valof do mod abs be of by to
+syn keyword bcplppOperatorKeyword valof do mod abs be fn of by to vec table
 hi def link bcplppOperatorKeyword Operator
 
 syn keyword bcplppPreProcessorKeyword section require
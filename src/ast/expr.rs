@@ -1,6 +1,6 @@
 use crate::source_file::{Location, Located};
 
-use super::{types::TypeIndex, stmt::Stmt, pattern::Pattern};
+use super::{types::TypeIndex, stmt::Stmt, pattern::Pattern, Param, BasicFunctionBody};
 
 pub type AtomIndex = u32;
 
@@ -50,6 +50,37 @@ impl Expr {
         }
     }
 
+    // operands of binary arithmetic, bitwise and comparison operators
+    pub fn binary_operands(&self) -> Option<(&Expr, &Expr)> {
+        match &self.kind {
+            ExprKind::Add(lhs, rhs) | ExprKind::Sub(lhs, rhs) | ExprKind::Mul(lhs, rhs)
+                | ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs)
+                | ExprKind::And(lhs, rhs) | ExprKind::Or(lhs, rhs) | ExprKind::XOr(lhs, rhs)
+                | ExprKind::Eq(lhs, rhs) | ExprKind::Ne(lhs, rhs)
+                | ExprKind::Gt(lhs, rhs) | ExprKind::Ge(lhs, rhs)
+                | ExprKind::Lt(lhs, rhs) | ExprKind::Le(lhs, rhs)
+                | ExprKind::LShift(lhs, rhs) | ExprKind::RShift(lhs, rhs) => Some((lhs, rhs)),
+            _ => None
+        }
+    }
+
+    pub fn binary_operands_mut(&mut self) -> Option<(&mut Expr, &mut Expr)> {
+        match &mut self.kind {
+            ExprKind::Add(lhs, rhs) | ExprKind::Sub(lhs, rhs) | ExprKind::Mul(lhs, rhs)
+                | ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs)
+                | ExprKind::And(lhs, rhs) | ExprKind::Or(lhs, rhs) | ExprKind::XOr(lhs, rhs)
+                | ExprKind::Eq(lhs, rhs) | ExprKind::Ne(lhs, rhs)
+                | ExprKind::Gt(lhs, rhs) | ExprKind::Ge(lhs, rhs)
+                | ExprKind::Lt(lhs, rhs) | ExprKind::Le(lhs, rhs)
+                | ExprKind::LShift(lhs, rhs) | ExprKind::RShift(lhs, rhs) => Some((lhs, rhs)),
+            _ => None
+        }
+    }
+
+    pub fn kind(&self) -> &ExprKind {
+        &self.kind
+    }
+
     pub fn kind_mut(&mut self) -> &mut ExprKind {
         &mut self.kind
     }
@@ -75,6 +106,7 @@ pub enum ExprKind {
 
     Mod(Box<Expr>, Box<Expr>),
     Abs(Box<Expr>),
+    Neg(Box<Expr>),
 
     Not(Box<Expr>),
     And(Box<Expr>, Box<Expr>),
@@ -95,14 +127,74 @@ pub enum ExprKind {
     Deref(Box<Expr>),
     Index(Box<Expr>, Box<Expr>),
     Slice(Box<Expr>, Box<Expr>, Box<Expr>),
+    Vector(Box<Expr>), // `vec n`, stack-allocated
+    Array(Vec<Expr>), // `[a, b]` or `table a, b`, statically allocated
+    Record(Vec<(String, Expr)>), // `{ x = a, y = b }`, fields sorted by name
+    Field(Box<Expr>, String), // `p.x`
 
     Cast(Box<Expr>),
     ImplicitCast(Box<Expr>),
     ValOf(Box<Stmt>), 
     FuncCall(Box<Expr>, Vec<Expr>),
+    Lambda(Box<Lambda>), // `fn (x :: Int32) = x + 1`
+    Instance(String, Vec<TypeIndex>), // generic function with its inferred type arguments, replaced by the function instantiated for them
 
     Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
 
     Match(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Expr>)>),
     Every(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Expr>)>),
 }
+
+// anonymous function, capturing the local variables it uses by value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lambda {
+    params: Vec<Param>,
+    return_type: Option<TypeIndex>,
+    body: BasicFunctionBody,
+
+    // local variables of the enclosing functions used in the body, filled in by the typechecker
+    captures: Vec<String>
+}
+
+impl Lambda {
+    pub fn new(params: Vec<Param>, return_type: Option<TypeIndex>, body: BasicFunctionBody) -> Self {
+        Self {
+            params,
+            return_type,
+            body,
+            captures: vec![]
+        }
+    }
+
+    pub fn params(&self) -> &Vec<Param> {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Vec<Param> {
+        &mut self.params
+    }
+
+    pub fn return_type(&self) -> &Option<TypeIndex> {
+        &self.return_type
+    }
+
+    pub fn set_return_type(&mut self, typ: TypeIndex) {
+        self.return_type = Some(typ)
+    }
+
+    pub fn body(&self) -> &BasicFunctionBody {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut BasicFunctionBody {
+        &mut self.body
+    }
+
+    pub fn captures(&self) -> &Vec<String> {
+        &self.captures
+    }
+
+    pub fn set_captures(&mut self, captures: Vec<String>) {
+        self.captures = captures
+    }
+}
@@ -2,7 +2,7 @@ use std::{collections::{HashMap, HashSet}, fmt::Debug, any::Any};
 
 use crate::source_file::{Location, Located};
 
-use self::{types::{TypeList, TypeIndex}, expr::{Expr, AtomIndex}, stmt::Stmt, pattern::Pattern, visitor::Traversable};
+use self::{types::{TypeList, TypeIndex}, expr::{Expr, AtomIndex}, stmt::Stmt, pattern::Pattern};
 
 pub(crate) mod types;
 pub(crate) mod expr;
@@ -13,6 +13,8 @@ pub(crate) mod visitor;
 #[derive(Default, Debug)]
 pub struct Program {
     sections: HashMap<String, Section>,
+    // sections ordered after the sections they require, set once requires are resolved
+    section_order: Vec<String>,
     types: TypeList,
 
     next_atom_index: AtomIndex,
@@ -20,13 +22,14 @@ pub struct Program {
 }
 
 impl Program {
-    pub fn add_section(&mut self, section: Section) {
-        if !self.sections.contains_key(section.ident()) {
-            self.sections.insert(section.ident().clone(), section);
-        }
-        else {
-            todo!()
+    // returns the location of the previous section, if a section with the same name was already added
+    pub fn add_section(&mut self, section: Section) -> Result<(), Location> {
+        if let Some(prev) = self.sections.get(section.ident()) {
+            return Err(prev.location().clone())
         }
+
+        self.sections.insert(section.ident().clone(), section);
+        Ok(())
     }
 
     pub fn add_atom(&mut self, atom: String) -> AtomIndex {
@@ -47,6 +50,40 @@ impl Program {
     pub fn types_mut(&mut self) -> &mut TypeList {
         &mut self.types
     }
+
+    pub fn sections(&self) -> &HashMap<String, Section> {
+        &self.sections
+    }
+
+    pub fn sections_mut(&mut self) -> &mut HashMap<String, Section> {
+        &mut self.sections
+    }
+
+    pub fn set_section_order(&mut self, order: Vec<String>) {
+        self.section_order = order;
+    }
+
+    // section names in dependency order, or sorted by name if requires were not resolved yet
+    pub fn section_order(&self) -> Vec<String> {
+        if self.section_order.len() == self.sections.len() {
+            return self.section_order.clone()
+        }
+
+        let mut idents = self.sections.keys().cloned().collect::<Vec<_>>();
+        idents.sort();
+        idents
+    }
+
+    pub fn find_function(&self, ident: &str) -> Option<&Function> {
+        self.find_decl::<Function>(ident)
+    }
+
+    // finds the declaration of `ident`, if it is of type `T`
+    pub fn find_decl<T: Decl + 'static>(&self, ident: &str) -> Option<&T> {
+        self.sections.values()
+            .find_map(|section| section.defines(&ident.to_string()))
+            .and_then(|decl| decl.as_any().downcast_ref::<T>())
+    }
 }
 
 #[derive(Debug)]
@@ -69,12 +106,20 @@ impl Section {
         }
     }
 
+    pub fn location(&self) -> &Location {
+        &self.loc
+    }
+
     pub fn ident(&self) -> &String {
         &self.ident
     }
 
-    pub fn defines(&self, ident: &String) -> Option<&Box<dyn Decl>> {
-        self.declarations.get(ident)
+    pub fn defines(&self, ident: &String) -> Option<&dyn Decl> {
+        self.declarations.get(ident).map(|decl| decl.as_ref())
+    }
+
+    pub fn required(&self) -> &HashSet<Located<String>> {
+        &self.required
     }
 
     pub fn add_require(&mut self, require: Located<String>) {
@@ -84,12 +129,22 @@ impl Section {
     pub fn declare(&mut self, decl: Box<dyn Decl>) {
         self.declarations.insert(decl.ident().clone(), decl);
     }
+
+    pub fn declarations(&self) -> &HashMap<String, Box<dyn Decl>> {
+        &self.declarations
+    }
+
+    pub fn declarations_mut(&mut self) -> &mut HashMap<String, Box<dyn Decl>> {
+        &mut self.declarations
+    }
 }
 
-pub trait Decl: Debug {
+pub trait Decl: Debug + Send + Sync {
     fn location(&self) -> &Location;
     fn ident(&self) -> &String;
     fn is_public(&self) -> bool;
+    // declarations of the same identifier in several sections are renamed to keep them apart
+    fn rename(&mut self, ident: String);
 
     fn as_any(&self) -> &dyn Any;
     fn as_mut_any(&mut self) -> &mut dyn Any;
@@ -150,6 +205,31 @@ pub struct ManifestDecl {
     value: Expr
 }
 
+impl ManifestDecl {
+    pub fn new(loc: Location, ident: String, value: Expr) -> Self {
+        Self {
+            loc,
+            is_public: true,
+            ident,
+            value
+        }
+    }
+
+    pub fn value(&self) -> &Expr {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut Expr {
+        &mut self.value
+    }
+}
+
+impl IntoDecl for ManifestDecl {
+    fn into_decl(self) -> Box<dyn Decl> {
+        Box::new(self)
+    }
+}
+
 impl Decl for ManifestDecl {
     fn ident(&self) -> &String {
         &self.ident
@@ -163,6 +243,85 @@ impl Decl for ManifestDecl {
         self.is_public
     }
 
+    fn rename(&mut self, ident: String) {
+        self.ident = ident;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// number of slots in the global vector of the runtime library
+pub const GLOBAL_VECTOR_SIZE: i64 = 1024;
+
+// variable stored in a slot of the global vector, shared by all sections
+#[derive(Debug)]
+pub struct GlobalDecl {
+    loc: Location,
+    is_public: bool,
+
+    ident: String,
+    typ: Option<TypeIndex>,
+
+    index: Expr // position in the global vector
+}
+
+impl GlobalDecl {
+    pub fn new(loc: Location, ident: String, typ: Option<TypeIndex>, index: Expr) -> Self {
+        Self {
+            loc,
+            is_public: true,
+            ident,
+            typ,
+            index
+        }
+    }
+
+    pub fn typ(&self) -> &Option<TypeIndex> {
+        &self.typ
+    }
+
+    pub fn set_typ(&mut self, typ: TypeIndex) {
+        self.typ = Some(typ)
+    }
+
+    pub fn index(&self) -> &Expr {
+        &self.index
+    }
+
+    pub fn index_mut(&mut self) -> &mut Expr {
+        &mut self.index
+    }
+}
+
+impl IntoDecl for GlobalDecl {
+    fn into_decl(self) -> Box<dyn Decl> {
+        Box::new(self)
+    }
+}
+
+impl Decl for GlobalDecl {
+    fn ident(&self) -> &String {
+        &self.ident
+    }
+
+    fn location(&self) -> &Location {
+        &self.loc
+    }
+
+    fn is_public(&self) -> bool {
+        self.is_public
+    }
+
+    fn rename(&mut self, ident: String) {
+        self.ident = ident;
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -172,28 +331,103 @@ impl Decl for ManifestDecl {
     }
 }
 
+// variable with static storage, private to the compiled program
 #[derive(Debug)]
+pub struct StaticDecl {
+    loc: Location,
+    is_public: bool,
+
+    ident: String,
+    typ: Option<TypeIndex>,
+
+    value: Expr // initial value
+}
+
+impl StaticDecl {
+    pub fn new(loc: Location, ident: String, typ: Option<TypeIndex>, value: Expr) -> Self {
+        Self {
+            loc,
+            is_public: false,
+            ident,
+            typ,
+            value
+        }
+    }
+
+    pub fn typ(&self) -> &Option<TypeIndex> {
+        &self.typ
+    }
+
+    pub fn set_typ(&mut self, typ: TypeIndex) {
+        self.typ = Some(typ)
+    }
+
+    pub fn value(&self) -> &Expr {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut Expr {
+        &mut self.value
+    }
+}
+
+impl IntoDecl for StaticDecl {
+    fn into_decl(self) -> Box<dyn Decl> {
+        Box::new(self)
+    }
+}
+
+impl Decl for StaticDecl {
+    fn ident(&self) -> &String {
+        &self.ident
+    }
+
+    fn location(&self) -> &Location {
+        &self.loc
+    }
+
+    fn is_public(&self) -> bool {
+        self.is_public
+    }
+
+    fn rename(&mut self, ident: String) {
+        self.ident = ident;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Function {
     loc: Location,
     is_public: bool,
 
     ident: String,
+    type_params: Vec<String>, // generic functions are instantiated for each combination of type arguments
 
     params: Vec<Param>,
     required_params: u32,
 
     return_type: Option<TypeIndex>,
+    #[allow(dead_code)]
     tailcall_recursive: bool, // recursiveness indicated by the `and` declaration
 
     body: FunctionBody
 }
 
 impl Function {
-    pub fn new(loc: Location, ident: String, params: Vec<Param>, return_type: Option<TypeIndex>, tailcall_recursive: bool, body: FunctionBody) -> Self {
+    pub fn new(loc: Location, ident: String, type_params: Vec<String>, params: Vec<Param>, return_type: Option<TypeIndex>, tailcall_recursive: bool, body: FunctionBody) -> Self {
         Self {
             loc,
             is_public: true,
             ident,
+            type_params,
             required_params: required_params_of(&params),
             params,
             return_type,
@@ -201,11 +435,56 @@ impl Function {
             body
         }
     }
+
+    pub fn type_params(&self) -> &Vec<String> {
+        &self.type_params
+    }
+
+    pub fn is_generic(&self) -> bool {
+        !self.type_params.is_empty()
+    }
+
+    // copy of a generic function named `ident`, whose types get substituted
+    pub fn instance(&self, ident: String) -> Self {
+        Self {
+            ident,
+            type_params: vec![],
+            ..self.clone()
+        }
+    }
+
+    pub fn params(&self) -> &Vec<Param> {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Vec<Param> {
+        &mut self.params
+    }
+
+    pub fn required_params(&self) -> u32 {
+        self.required_params
+    }
+
+    pub fn return_type(&self) -> &Option<TypeIndex> {
+        &self.return_type
+    }
+
+    pub fn set_return_type(&mut self, typ: TypeIndex) {
+        self.return_type = Some(typ)
+    }
+
+    pub fn body(&self) -> &FunctionBody {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut FunctionBody {
+        &mut self.body
+    }
 }
 
 fn required_params_of(params: &[Param]) -> u32 {
     if let Some((i, _)) = params.iter().enumerate().find(|(_, param)| param.default_value.is_some()) {
-        i as u32 - 1
+        i as u32
     }
     else {
         params.len() as u32
@@ -231,6 +510,10 @@ impl Decl for Function {
         self.is_public
     }
 
+    fn rename(&mut self, ident: String) {
+        self.ident = ident;
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -240,7 +523,7 @@ impl Decl for Function {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum FunctionBody {
     Expr(Expr),
     Stmt(Stmt),
@@ -257,13 +540,13 @@ impl From<BasicFunctionBody> for FunctionBody {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum BasicFunctionBody {
     Expr(Expr),
     Stmt(Stmt)
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Param {
     loc: Location,
     ident: Located<Pattern>,
@@ -280,5 +563,33 @@ impl Param {
             default_value
         }
     }
+
+    pub fn pattern(&self) -> &Located<Pattern> {
+        &self.ident
+    }
+
+    pub fn location(&self) -> &Location {
+        &self.loc
+    }
+
+    pub fn pattern_mut(&mut self) -> &mut Located<Pattern> {
+        &mut self.ident
+    }
+
+    pub fn typ(&self) -> &Option<TypeIndex> {
+        &self.typ
+    }
+
+    pub fn set_typ(&mut self, typ: TypeIndex) {
+        self.typ = Some(typ)
+    }
+
+    pub fn default_value(&self) -> &Option<Expr> {
+        &self.default_value
+    }
+
+    pub fn default_value_mut(&mut self) -> &mut Option<Expr> {
+        &mut self.default_value
+    }
 }
 
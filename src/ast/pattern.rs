@@ -13,6 +13,7 @@ pub enum Pattern {
     
     Variant(String, Vec<Located<Pattern>>), // `Foo(Bar, Baz, ...)`
     List(Vec<Located<Pattern>>), // `[A, B, C, ...]`
+    Record(Vec<(String, Located<Pattern>)>), // `{ x = A, y }`, fields sorted by name
     Remaining, // `..`
 }
 
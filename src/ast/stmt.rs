@@ -17,9 +17,25 @@ impl Stmt {
         }
     }
 
+    pub fn location(&self) -> &Location {
+        &self.loc
+    }
+
+    pub fn kind(&self) -> &StmtKind {
+        &self.kind
+    }
+
     pub fn kind_mut(&mut self) -> &mut StmtKind {
         &mut self.kind
     }
+
+    // whether `label` labels this statement, possibly among other labels
+    pub fn is_labelled(&self, label: &str) -> bool {
+        match &self.kind {
+            StmtKind::Label(ident, body) => ident == label || body.is_labelled(label),
+            _ => false
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -37,6 +53,9 @@ pub enum StmtKind {
     
     While(Box<Expr>, Box<Stmt>),
     Until(Box<Expr>, Box<Stmt>),
+    Loop(Box<Stmt>), // `loop C` or `C repeat`
+    RepeatWhile(Box<Stmt>, Box<Expr>), // `C repeatwhile E`, the body runs before the condition is checked
+    RepeatUntil(Box<Stmt>, Box<Expr>),
 
     //  iterator   init val   target val         stepsize           body
     For(Located<Pattern>, Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>, Box<Stmt>),
@@ -47,9 +66,18 @@ pub enum StmtKind {
     
     Break,
     Next,
+    Finish, // terminates the program
+
+    Label(String, Box<Stmt>), // `L: C`, labels are local to their function, lambda or `valof` body
+    Goto(String),
 
     Match(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Stmt>)>),
     Every(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Stmt>)>),
 
-    Binding(Vec<(Located<Pattern>, Expr)>)
+    Binding(Vec<(Located<Pattern>, Expr)>),
+
+    //     targets    values
+    Assign(Vec<Expr>, Vec<Expr>),
+    // binary operation with the assigned target as its left operand, e.g. `Add(x, 1)` for `x +:= 1`
+    CompoundAssign(Box<Expr>)
 }
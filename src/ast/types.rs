@@ -11,6 +11,7 @@ pub struct Type {
     loc: Option<Location>,
     size: u32,
 
+    #[allow(dead_code)]
     is_builtin: bool,
     kind: TypeKind
 }
@@ -28,7 +29,7 @@ impl Type {
     fn new_builtin(kind: TypeKind) -> Self {
         Self {
             loc: None,
-            size: kind.try_get_size().expect(&format!("internal error when initializing builtin type {kind:?}")),
+            size: kind.try_get_size().unwrap_or_else(|| panic!("internal error when initializing builtin type {kind:?}")),
             is_builtin: true,
             kind
         }
@@ -49,6 +50,10 @@ impl Type {
     pub fn kind(&self) -> &TypeKind {
         &self.kind
     }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -78,11 +83,17 @@ pub enum TypeKind {
 
     Alias(String, Option<TypeIndex>),
     Sum(Vec<SumVariant>),
+    Record(Vec<RecordField>), // fields are sorted by name
+    Function(Vec<TypeIndex>, TypeIndex), // parameter types, return type
+
+    // type variable of the type inference, bound to the inferred type once it is known
+    Var(Option<TypeIndex>),
+
+    Param(String), // type parameter of a generic type or function
+    Generic(Vec<String>, TypeIndex), // type parameters, body; generic type aliases are bound to it
+    Instance(String, Vec<TypeIndex>, Option<TypeIndex>), // generic type alias, type arguments, body with the arguments substituted
 
     // Table
-    // Function
-    // Generic
-    // Record
     // ...
 }
 
@@ -94,10 +105,35 @@ impl TypeKind {
             TypeKind::UInt16 | TypeKind::Int16 => Some(2),
             TypeKind::UInt32 | TypeKind::Int32 | TypeKind::Float32 | TypeKind::Atom => Some(4),
             TypeKind::UInt64 | TypeKind::Int64 | TypeKind::Float64 => Some(8),
-            TypeKind::Pointer(_) => Some(std::mem::size_of::<*const ()>() as u32), // TODO: handle crosscompilation
+            TypeKind::Pointer(_) | TypeKind::Function(..) => Some(std::mem::size_of::<*const ()>() as u32), // TODO: handle crosscompilation
             _ => None,
         } 
     }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, TypeKind::Float32 | TypeKind::Float64)
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, TypeKind::UInt8 | TypeKind::UInt16 | TypeKind::UInt32 | TypeKind::UInt64)
+    }
+
+    pub fn is_byte_sized(&self) -> bool {
+        matches!(self, TypeKind::UInt8 | TypeKind::Int8 | TypeKind::Char)
+    }
+
+    // number of bits and signedness of integer types narrower than a machine word
+    pub fn narrow_width(&self) -> Option<(u8, bool)> {
+        match self {
+            TypeKind::Int8 => Some((8, true)),
+            TypeKind::Int16 => Some((16, true)),
+            TypeKind::Int32 => Some((32, true)),
+            TypeKind::UInt8 | TypeKind::Char => Some((8, false)),
+            TypeKind::UInt16 => Some((16, false)),
+            TypeKind::UInt32 => Some((32, false)),
+            _ => None
+        }
+    }
 }
 
 impl TryFrom<&str> for TypeKind {
@@ -130,6 +166,40 @@ pub enum SumVariant {
     // Add stuff like tagged variants, etc.
 }
 
+impl SumVariant {
+    pub fn ident(&self) -> &String {
+        match self {
+            Self::Basic(ident, _) => ident
+        }
+    }
+
+    pub fn fields(&self) -> &Vec<TypeIndex> {
+        match self {
+            Self::Basic(_, fields) => fields
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordField {
+    ident: String,
+    typ: TypeIndex
+}
+
+impl RecordField {
+    pub fn new(ident: String, typ: TypeIndex) -> Self {
+        Self { ident, typ }
+    }
+
+    pub fn ident(&self) -> &String {
+        &self.ident
+    }
+
+    pub fn typ(&self) -> TypeIndex {
+        self.typ
+    }
+}
+
 const BUILTIN_TYPE_KINDS: [TypeKind; 14] = [
     TypeKind::UInt8,
     TypeKind::UInt16,
@@ -147,6 +217,9 @@ const BUILTIN_TYPE_KINDS: [TypeKind; 14] = [
     TypeKind::Atom
 ];
 
+// records are stored in words, except for byte-sized fields
+const RECORD_WORD_SIZE: u32 = 8;
+
 #[derive(Debug)]
 pub struct TypeList {
     types: Vec<Type>
@@ -178,6 +251,260 @@ impl TypeList {
         self.types.len() as u32 - 1
     }
 
+    pub fn get(&self, index: TypeIndex) -> Option<&Type> {
+        self.types.get(index as usize)
+    }
+
+    // returns the type of kind `kind`, defining it if it doesn't exist yet
+    pub fn get_or_define(&mut self, kind: TypeKind) -> TypeIndex {
+        match self.by_kind(&kind) {
+            Some(typ) => typ,
+            None => self.define(Type::new(None, kind))
+        }
+    }
+
+    pub fn fresh_var(&mut self) -> TypeIndex {
+        self.define(Type::new(None, TypeKind::Var(None)))
+    }
+
+    // follows type aliases and bound type variables until a concrete type kind is found
+    pub fn resolve(&self, index: TypeIndex) -> Option<&TypeKind> {
+        match self.get(index)?.kind() {
+            TypeKind::Alias(_, Some(inner)) | TypeKind::Var(Some(inner)) | TypeKind::Instance(_, _, Some(inner)) => self.resolve(*inner),
+            TypeKind::Alias(_, None) | TypeKind::Var(None) | TypeKind::Instance(_, _, None) => None,
+            kind => Some(kind)
+        }
+    }
+
+    // human-readable name of a type, used in diagnostics
+    pub fn name_of(&self, index: TypeIndex) -> String {
+        let Some(typ) = self.get(index) else {
+            return "<invalid>".into()
+        };
+
+        match typ.kind() {
+            TypeKind::Pointer(inner) => format!("&{}", self.name_of(*inner)),
+            TypeKind::Slice(inner) => format!("[{}]", self.name_of(*inner)),
+            TypeKind::Array(inner, _) => format!("[{}; ..]", self.name_of(*inner)),
+            TypeKind::Alias(ident, _) => ident.clone(),
+            TypeKind::Sum(variants) => variants.iter()
+                .map(|variant| variant.ident().clone())
+                .collect::<Vec<_>>()
+                .join(" : "),
+            TypeKind::Record(fields) => format!("{{ {} }}", fields.iter()
+                .map(|field| format!("{} :: {}", field.ident(), self.name_of(field.typ())))
+                .collect::<Vec<_>>()
+                .join(", ")
+            ),
+            TypeKind::Function(params, return_type) => format!(
+                "({}) -> {}",
+                params.iter().map(|param| self.name_of(*param)).collect::<Vec<_>>().join(", "),
+                self.name_of(*return_type)
+            ),
+            TypeKind::Var(Some(inner)) => self.name_of(*inner),
+            TypeKind::Var(None) => "?".into(),
+            TypeKind::Param(ident) => ident.clone(),
+            TypeKind::Instance(ident, args, _) => format!(
+                "{ident}({})",
+                args.iter().map(|arg| self.name_of(*arg)).collect::<Vec<_>>().join(", ")
+            ),
+            kind => format!("{kind:?}")
+        }
+    }
+
+    // returns the sum type, the tag and the variant declaring a variant named `ident`
+    pub fn find_variant(&self, ident: &str) -> Option<(TypeIndex, u32, &SumVariant)> {
+        self.types.iter()
+            .enumerate()
+            .find_map(|(i, typ)| match &typ.kind {
+                TypeKind::Sum(variants) => variants.iter()
+                    .enumerate()
+                    .find(|(_, variant)| variant.ident() == ident)
+                    .map(|(tag, variant)| (i as u32, tag as u32, variant)),
+                _ => None
+            })
+    }
+
+    // variant `ident` of the sum type `typ` if it has one, like `find_variant` otherwise;
+    // the variants of instances of generic sum types have the type arguments substituted in their fields
+    pub fn find_variant_of(&self, typ: Option<TypeIndex>, ident: &str) -> Option<(TypeIndex, u32, &SumVariant)> {
+        if let Some(typ) = typ && let Some(TypeKind::Sum(variants)) = self.resolve(typ)
+            && let Some(tag) = variants.iter().position(|variant| variant.ident() == ident) {
+            return Some((typ, tag as u32, &variants[tag]))
+        }
+        self.find_variant(ident)
+    }
+
+    // generic type alias whose body is the sum type `sum` and its type parameters
+    pub fn generic_of(&self, sum: TypeIndex) -> Option<(String, Vec<String>)> {
+        self.types.iter().find_map(|typ| match &typ.kind {
+            TypeKind::Alias(ident, Some(generic)) => match self.get(*generic)?.kind() {
+                TypeKind::Generic(params, body) if *body == sum => Some((ident.clone(), params.clone())),
+                _ => None
+            },
+            _ => None
+        })
+    }
+
+    pub fn is_generic(&self, ident: &str) -> bool {
+        matches!(
+            self.find_alias(ident).and_then(|alias| self.resolve(alias)),
+            Some(TypeKind::Generic(..))
+        )
+    }
+
+    // instance of the generic type alias `ident` with the type arguments `args`;
+    // instances are interned, so equal type arguments give the same type
+    pub fn instantiate(&mut self, ident: &str, args: Vec<TypeIndex>) -> TypeIndex {
+        let existing = self.types.iter().position(|typ| matches!(
+            &typ.kind,
+            TypeKind::Instance(other, other_args, _) if other == ident && *other_args == args
+        ));
+        if let Some(index) = existing {
+            return index as TypeIndex
+        }
+
+        let index = self.define(Type::new(None, TypeKind::Instance(ident.to_string(), args, None)));
+        self.instantiate_body(index);
+        index
+    }
+
+    // substitutes the type arguments of the instance `index` into the body of its generic type alias;
+    // does nothing if the alias is not generic or the number of arguments is wrong
+    pub fn instantiate_body(&mut self, index: TypeIndex) {
+        let Some(TypeKind::Instance(ident, args, None)) = self.get(index).map(|typ| typ.kind().clone())
+        else {
+            return
+        };
+        let Some(TypeKind::Generic(params, body)) = self.find_alias(&ident).and_then(|alias| self.resolve(alias)).cloned()
+        else {
+            return
+        };
+        if params.len() != args.len() {
+            return
+        }
+
+        let substitution = params.into_iter().zip(args.iter().copied()).collect();
+        let body = self.substitute(body, &substitution);
+        self.types[index as usize].kind = TypeKind::Instance(ident, args, Some(body));
+    }
+
+    // replaces the type parameters in `typ` by the types they are mapped to
+    pub fn substitute(&mut self, typ: TypeIndex, substitution: &HashMap<String, TypeIndex>) -> TypeIndex {
+        if !self.has_params(typ) {
+            return typ
+        }
+        let kind = self.types[typ as usize].kind.clone();
+
+        match kind {
+            TypeKind::Param(ident) => substitution.get(&ident).copied().unwrap_or(typ),
+            TypeKind::Var(Some(inner)) => self.substitute(inner, substitution),
+            TypeKind::Pointer(inner) => {
+                let inner = self.substitute(inner, substitution);
+                self.get_or_define(TypeKind::Pointer(inner))
+            }
+            TypeKind::Slice(inner) => {
+                let inner = self.substitute(inner, substitution);
+                self.get_or_define(TypeKind::Slice(inner))
+            }
+            TypeKind::Array(inner, len) => {
+                let inner = self.substitute(inner, substitution);
+                self.get_or_define(TypeKind::Array(inner, len))
+            }
+            TypeKind::Record(fields) => {
+                let fields = fields.into_iter()
+                    .map(|field| RecordField::new(field.ident, self.substitute(field.typ, substitution)))
+                    .collect();
+                self.get_or_define(TypeKind::Record(fields))
+            }
+            TypeKind::Function(params, return_type) => {
+                let params = params.into_iter()
+                    .map(|param| self.substitute(param, substitution))
+                    .collect();
+                let return_type = self.substitute(return_type, substitution);
+                self.get_or_define(TypeKind::Function(params, return_type))
+            }
+            // sum types are nominal, so every instance gets its own
+            TypeKind::Sum(variants) => {
+                let variants = variants.into_iter()
+                    .map(|SumVariant::Basic(ident, fields)| SumVariant::Basic(
+                        ident,
+                        fields.into_iter().map(|field| self.substitute(field, substitution)).collect()
+                    ))
+                    .collect();
+                let loc = self.types[typ as usize].loc.clone();
+                self.define(Type::new(loc, TypeKind::Sum(variants)))
+            }
+            TypeKind::Instance(ident, args, _) => {
+                let args = args.into_iter()
+                    .map(|arg| self.substitute(arg, substitution))
+                    .collect();
+                self.instantiate(&ident, args)
+            }
+            _ => typ
+        }
+    }
+
+    // whether `typ` refers to type parameters; aliases are never generic themselves
+    fn has_params(&self, typ: TypeIndex) -> bool {
+        match self.get(typ).map(|typ| typ.kind()) {
+            Some(TypeKind::Param(_)) => true,
+            Some(TypeKind::Var(Some(inner)) | TypeKind::Pointer(inner) | TypeKind::Slice(inner) | TypeKind::Array(inner, _)) => self.has_params(*inner),
+            Some(TypeKind::Record(fields)) => fields.iter().any(|field| self.has_params(field.typ())),
+            Some(TypeKind::Function(params, return_type)) => params.iter().chain([return_type]).any(|typ| self.has_params(*typ)),
+            Some(TypeKind::Sum(variants)) => variants.iter().flat_map(|variant| variant.fields()).any(|typ| self.has_params(*typ)),
+            Some(TypeKind::Instance(_, args, _)) => args.iter().any(|arg| self.has_params(*arg)),
+            _ => false
+        }
+    }
+
+    // byte offsets of the fields of a record and its size in bytes;
+    // byte-sized fields are stored after all word-sized ones, so no padding is needed between fields
+    pub fn record_layout(&self, fields: &[RecordField]) -> (Vec<u32>, u32) {
+        let is_byte = |field: &RecordField| self.resolve(field.typ()).is_some_and(TypeKind::is_byte_sized);
+
+        let mut offsets = vec![0; fields.len()];
+        let mut offset = 0;
+        for bytes in [false, true] {
+            for (i, _) in fields.iter().enumerate().filter(|(_, field)| is_byte(field) == bytes) {
+                offsets[i] = offset;
+                offset += if bytes { 1 } else { RECORD_WORD_SIZE };
+            }
+        }
+
+        (offsets, offset.next_multiple_of(RECORD_WORD_SIZE))
+    }
+
+    // sets the sizes of all record types, once the types of their fields are known
+    pub fn compute_layouts(&mut self) {
+        for i in 0..self.types.len() {
+            if let TypeKind::Record(fields) = &self.types[i].kind {
+                let (_, size) = self.record_layout(fields);
+                self.types[i].size = size;
+            }
+        }
+    }
+
+    // size in bytes of the type behind aliases and type variables
+    pub fn size_of(&self, index: TypeIndex) -> u32 {
+        match self.get(index).map(|typ| (typ.kind(), typ.size())) {
+            Some((TypeKind::Alias(_, Some(inner)) | TypeKind::Var(Some(inner)) | TypeKind::Instance(_, _, Some(inner)), _)) => self.size_of(*inner),
+            Some((_, size)) => size,
+            None => 0
+        }
+    }
+
+    // returns the type of the field `ident` of the record `index` and its byte offset
+    pub fn find_field(&self, index: TypeIndex, ident: &str) -> Option<(TypeIndex, u32)> {
+        let TypeKind::Record(fields) = self.resolve(index)? else {
+            return None
+        };
+
+        let pos = fields.iter().position(|field| field.ident() == ident)?;
+        let (offsets, _) = self.record_layout(fields);
+        Some((fields[pos].typ(), offsets[pos]))
+    }
+
     pub fn get_mut(&mut self, index: TypeIndex) -> Option<&mut Type> {
         self.types.get_mut(index as usize)
     }
@@ -1,6 +1,6 @@
-use crate::{match_decl, ast::{ManifestDecl, FunctionBody, pattern::PatternTerm, stmt::StmtKind, expr::ExprKind}};
+use crate::{match_decl, ast::{ManifestDecl, GlobalDecl, StaticDecl, FunctionBody, BasicFunctionBody, pattern::PatternTerm, stmt::StmtKind, expr::ExprKind}, source_file::Located};
 
-use super::{Program, Function, Section, Decl, Param, stmt::Stmt, expr::Expr, pattern::Pattern};
+use super::{Program, Function, Section, Param, stmt::Stmt, expr::Expr, pattern::Pattern};
 
 pub enum Action {
     Continue,
@@ -20,13 +20,20 @@ pub trait Visitor<T, E> where T: Traversable {
     fn visit_before(&mut self, _node: &mut T) -> Result<Action, E> { Ok(Action::Continue) }
 }
 
-pub trait ASTVisitor<E> = Visitor<Program, E> 
+// notified when bindings go in and out of scope: around functions, lambdas, blocks, `for` loops and match branches
+pub trait ScopeVisitor {
+    fn enter_scope(&mut self) {}
+    fn leave_scope(&mut self) {}
+}
+
+pub trait ASTVisitor<E> = ScopeVisitor
+    + Visitor<Program, E> 
     + Visitor<Section, E>
     + Visitor<Function, E>
     + Visitor<Param, E>
     + Visitor<Stmt, E>
     + Visitor<Expr, E>
-    + Visitor<Pattern, E>;
+    + Visitor<Located<Pattern>, E>;
 
 pub trait Traversable {
     fn traverse<E>(&mut self, visitor: &mut impl ASTVisitor<E>) -> Result<Action, E>;
@@ -36,7 +43,8 @@ impl Traversable for Program {
     fn traverse<E>(&mut self, visitor: &mut impl ASTVisitor<E>) -> Result<Action, E> {
         act!(visitor.visit_before(self)?);
         
-        for (_, section) in &mut self.sections {
+        for ident in self.section_order() {
+            let section = self.sections.get_mut(&ident).expect("ordered section not in program");
             act!(section.traverse(visitor)?)
         }
 
@@ -48,14 +56,20 @@ impl Traversable for Section {
     fn traverse<E>(&mut self, visitor: &mut impl ASTVisitor<E>) -> Result<Action, E> {
         act!(visitor.visit_before(self)?);
 
-        for (_, decl) in &mut self.declarations {
+        for decl in self.declarations.values_mut() {
             match_decl!{
                 mut decl;
                 func as Function => {
                     act!(func.traverse(visitor)?)
                 },
                 manifest as ManifestDecl => {
-                    println!("manifest: {}", manifest.ident());
+                    act!(manifest.value_mut().traverse(visitor)?)
+                },
+                global as GlobalDecl => {
+                    act!(global.index_mut().traverse(visitor)?)
+                },
+                static_decl as StaticDecl => {
+                    act!(static_decl.value_mut().traverse(visitor)?)
                 },
                 _ => ()
             }
@@ -68,6 +82,7 @@ impl Traversable for Section {
 impl Traversable for Function {
     fn traverse<E>(&mut self, visitor: &mut impl ASTVisitor<E>) -> Result<Action, E> {
         act!(visitor.visit_before(self)?);
+        visitor.enter_scope();
 
         for param in &mut self.params {
             act!(param.traverse(visitor)?)
@@ -78,22 +93,27 @@ impl Traversable for Function {
             FunctionBody::Stmt(stmt) => act!(stmt.traverse(visitor)?),
             FunctionBody::PatternMatchedExpr(branches) => {
                 for (patterns, expr) in branches {
+                    visitor.enter_scope();
                     for pattern in patterns {
                         act!(pattern.traverse(visitor)?)
                     }
-                    act!(expr.traverse(visitor)?)
+                    act!(expr.traverse(visitor)?);
+                    visitor.leave_scope();
                 }
             }
             FunctionBody::PatternMatchedStmt(branches) => {
                 for (patterns, stmt) in branches {
+                    visitor.enter_scope();
                     for pattern in patterns {
                         act!(pattern.traverse(visitor)?)
                     }
-                    act!(stmt.traverse(visitor)?)
+                    act!(stmt.traverse(visitor)?);
+                    visitor.leave_scope();
                 } 
             }
         }
 
+        visitor.leave_scope();
         visitor.visit(self)
     }
 }
@@ -117,11 +137,15 @@ impl Traversable for Stmt {
 
         match self.kind_mut() {
             StmtKind::Nop | StmtKind::Return | StmtKind::DefaultCase 
-                | StmtKind::Break | StmtKind::Next => (),
+                | StmtKind::Break | StmtKind::Next | StmtKind::Finish | StmtKind::Goto(_) => (),
             StmtKind::Expr(expr) | StmtKind::ResultIs(expr) 
                 | StmtKind::Case(expr) => act!(expr.traverse(visitor)?),
-            StmtKind::Block(stmts) => for stmt in stmts {
-                act!(stmt.traverse(visitor)?);
+            StmtKind::Block(stmts) => {
+                visitor.enter_scope();
+                for stmt in stmts {
+                    act!(stmt.traverse(visitor)?);
+                }
+                visitor.leave_scope();
             }
             StmtKind::If(cond, if_branch, else_branch) => {
                 act!(cond.traverse(visitor)?);
@@ -135,8 +159,12 @@ impl Traversable for Stmt {
                 act!(cond.traverse(visitor)?);
                 act!(body.traverse(visitor)?);
             }
+            StmtKind::Loop(body) | StmtKind::Label(_, body) => act!(body.traverse(visitor)?),
+            StmtKind::RepeatWhile(body, cond) | StmtKind::RepeatUntil(body, cond) => {
+                act!(body.traverse(visitor)?);
+                act!(cond.traverse(visitor)?);
+            }
             StmtKind::For(iter, init, bound, step, body) => {
-                act!(iter.traverse(visitor)?);
                 act!(init.traverse(visitor)?);
                 if let Some(bound) = bound {
                     act!(bound.traverse(visitor)?);
@@ -144,25 +172,39 @@ impl Traversable for Stmt {
                 if let Some(step) = step {
                     act!(step.traverse(visitor)?);
                 }
+                visitor.enter_scope();
+                act!(iter.traverse(visitor)?);
                 act!(body.traverse(visitor)?);
+                visitor.leave_scope();
             }
             StmtKind::Match(cond, branches) | StmtKind::Every(cond, branches) => {
                 for c in cond {
                     act!(c.traverse(visitor)?);
                 }                
                 for (patterns, body) in branches {
+                    visitor.enter_scope();
                     for pattern in patterns {
                         act!(pattern.traverse(visitor)?);
                     }
                     act!(body.traverse(visitor)?);
+                    visitor.leave_scope();
                 }
             }
             StmtKind::Binding(pairs) => {
-                for (pattern, expr) in pairs {
+                // the bound values can't refer to the bindings themselves
+                for (_, expr) in pairs.iter_mut() {
+                    act!(expr.traverse(visitor)?);
+                }
+                for (pattern, _) in pairs {
                     act!(pattern.traverse(visitor)?);
+                }
+            }
+            StmtKind::Assign(targets, values) => {
+                for expr in targets.iter_mut().chain(values) {
                     act!(expr.traverse(visitor)?);
                 }
             }
+            StmtKind::CompoundAssign(operation) => act!(operation.traverse(visitor)?),
         }
 
         visitor.visit(self)
@@ -174,13 +216,14 @@ impl Traversable for Expr {
         act!(visitor.visit_before(self)?);
 
         match self.kind_mut() {
-            ExprKind::Ident(_) | ExprKind::Atom(_)
+            ExprKind::Ident(_) | ExprKind::Instance(..) | ExprKind::Atom(_)
                 | ExprKind::IntLit(_) | ExprKind::FloatLit(_)
                 | ExprKind::CharLit(_) | ExprKind::StringLit(_)
                 | ExprKind::True | ExprKind::False => (),
-            ExprKind::Abs(expr) | ExprKind::Not(expr)
-                | ExprKind::Ref(expr) | ExprKind::Deref(expr)
-                | ExprKind::Cast(expr) | ExprKind::ImplicitCast(expr) => act!(expr.traverse(visitor)?),
+            ExprKind::Abs(expr) | ExprKind::Neg(expr) | ExprKind::Not(expr)
+                | ExprKind::Ref(expr) | ExprKind::Deref(expr) | ExprKind::Vector(expr)
+                | ExprKind::Cast(expr) | ExprKind::ImplicitCast(expr)
+                | ExprKind::Field(expr, _) => act!(expr.traverse(visitor)?),
             ExprKind::Add(lhs, rhs) | ExprKind::Sub(lhs, rhs) 
                 | ExprKind::Mul(lhs, rhs) | ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs)
                 | ExprKind::And(lhs, rhs) | ExprKind::Or(lhs, rhs) | ExprKind::XOr(lhs, rhs)
@@ -203,15 +246,38 @@ impl Traversable for Expr {
                     act!(arg.traverse(visitor)?);
                 }
             }
+            ExprKind::Lambda(lambda) => {
+                visitor.enter_scope();
+                for param in lambda.params_mut() {
+                    act!(param.traverse(visitor)?);
+                }
+                match lambda.body_mut() {
+                    BasicFunctionBody::Expr(expr) => act!(expr.traverse(visitor)?),
+                    BasicFunctionBody::Stmt(stmt) => act!(stmt.traverse(visitor)?)
+                }
+                visitor.leave_scope();
+            }
+            ExprKind::Array(elems) => {
+                for elem in elems {
+                    act!(elem.traverse(visitor)?);
+                }
+            }
+            ExprKind::Record(fields) => {
+                for (_, value) in fields {
+                    act!(value.traverse(visitor)?);
+                }
+            }
             ExprKind::Match(cond, branches) | ExprKind::Every(cond, branches) => {
                 for c in cond {
                     act!(c.traverse(visitor)?);
                 }
                 for (patterns, expr) in branches {
+                    visitor.enter_scope();
                     for pattern in patterns {
                         act!(pattern.traverse(visitor)?);
                     }
                     act!(expr.traverse(visitor)?);
+                    visitor.leave_scope();
                 }
             }
         }
@@ -220,29 +286,35 @@ impl Traversable for Expr {
     }
 }
 
-impl Traversable for Pattern {
+// patterns are visited with their location, since `Pattern` itself has none
+impl Traversable for Located<Pattern> {
     fn traverse<E>(&mut self, visitor: &mut impl ASTVisitor<E>) -> Result<Action, E> {
         act!(visitor.visit_before(self)?);
 
-        match self {
-            Self::Any | Self::Remaining | Self::Query(_) => (),
-            Self::Term(PatternTerm::Range(lhs, rhs)) => {
+        match &mut **self {
+            Pattern::Any | Pattern::Remaining | Pattern::Query(_) => (),
+            Pattern::Term(PatternTerm::Range(lhs, rhs)) => {
                 act!(lhs.traverse(visitor)?);
                 act!(rhs.traverse(visitor)?);
             }
-            Self::Term(PatternTerm::Lt(e) | PatternTerm::Le(e)
+            Pattern::Term(PatternTerm::Lt(e) | PatternTerm::Le(e)
                 | PatternTerm::Gt(e) | PatternTerm::Ge(e)
                 | PatternTerm::Ne(e) | PatternTerm::Eq(e) 
                 | PatternTerm::Basic(e)) => act!(e.traverse(visitor)?),
-            Self::Or(lhs, rhs) | Self::And(lhs, rhs) => {
+            Pattern::Or(lhs, rhs) | Pattern::And(lhs, rhs) => {
                 act!(lhs.traverse(visitor)?);
                 act!(rhs.traverse(visitor)?);
             }
-            Self::List(args) | Self::Variant(_, args) => {
+            Pattern::List(args) | Pattern::Variant(_, args) => {
                 for arg in args {
                     act!(arg.traverse(visitor)?);
                 }
             }
+            Pattern::Record(fields) => {
+                for (_, field) in fields {
+                    act!(field.traverse(visitor)?);
+                }
+            }
         }
 
         visitor.visit(self)
@@ -0,0 +1,286 @@
+use crate::{
+    context::BuildKind,
+    ir::{BinaryOp, Callee, Condition, Function, Instr, Module, Operand, Terminator, UnaryOp, Width}
+};
+
+use super::{CodegenError, CodegenResult, RUNTIME_HEADER_NAME};
+
+const WORD: &str = "bcpl_word";
+
+// natives of the runtime taking a variable number of arguments, which must be called through a prototype
+const VARIADIC_EXTERNS: &[&str] = &["writef"];
+
+pub struct CGenerator<'a> {
+    module: &'a Module,
+    output: String
+}
+
+// lambdas are named like `lambda.0`, which isn't a valid C identifier
+fn mangle(ident: &str) -> String {
+    format!("bcpl_{}", ident.replace('.', "__"))
+}
+
+fn c_string_literal(value: &str) -> String {
+    let mut lit = String::with_capacity(value.len() + 2);
+    lit.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => lit.push_str("\\\""),
+            '\\' => lit.push_str("\\\\"),
+            '\n' => lit.push_str("\\n"),
+            '\t' => lit.push_str("\\t"),
+            '\r' => lit.push_str("\\r"),
+            _ if ch.is_ascii_graphic() || ch == ' ' => lit.push(ch),
+            _ => {
+                let mut buf = [0u8; 4];
+                for byte in ch.encode_utf8(&mut buf).bytes() {
+                    // use a separate string literal so following hex digits are not consumed
+                    lit.push_str(&format!("\\x{byte:02x}\"\""));
+                }
+            }
+        }
+    }
+    lit.push('"');
+    lit
+}
+
+fn var(var: &u32) -> String {
+    format!("v{var}")
+}
+
+fn vector(index: &usize) -> String {
+    format!("vec{index}")
+}
+
+fn table(index: &usize) -> String {
+    format!("table{index}")
+}
+
+fn memory_type(width: Width) -> &'static str {
+    match width {
+        Width::Word => WORD,
+        Width::Byte => "uint8_t",
+        Width::SignedByte => "int8_t"
+    }
+}
+
+fn comparison_operator(condition: Condition) -> &'static str {
+    match condition {
+        Condition::Eq | Condition::FEq => "==",
+        Condition::Ne | Condition::FNe => "!=",
+        Condition::Lt | Condition::ULt | Condition::FLt => "<",
+        Condition::Le | Condition::ULe | Condition::FLe => "<=",
+        Condition::Gt | Condition::UGt | Condition::FGt => ">",
+        Condition::Ge | Condition::UGe | Condition::FGe => ">=",
+    }
+}
+
+impl<'a> CGenerator<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        Self {
+            module,
+            output: String::new()
+        }
+    }
+
+    pub fn generate(mut self, build_kind: &BuildKind) -> CodegenResult<String> {
+        let mut generated = format!("/* generated by bcplpp */\n#include \"{RUNTIME_HEADER_NAME}\"\n\n");
+        for ext in self.module.externs() {
+            if VARIADIC_EXTERNS.contains(&ext.as_str()) {
+                generated.push_str(&format!("extern {WORD} {}({WORD}, ...);\n", mangle(ext)));
+            }
+            else {
+                generated.push_str(&format!("extern {WORD} {}();\n", mangle(ext)));
+            }
+        }
+        for func in self.module.functions() {
+            generated.push_str(&Self::function_header(func));
+            generated.push_str(";\n");
+        }
+        // tables may refer to the tables before them
+        for (index, (width, elems)) in self.module.tables().iter().enumerate() {
+            let len = elems.len();
+            let elems = elems.iter().map(|elem| self.operand(elem)).collect::<Vec<_>>().join(", ");
+            generated.push_str(&format!(
+                "static struct {{ {WORD} len; {} elems[{}]; }} {} = {{ INT64_C({len}), {{ {elems} }} }};\n",
+                memory_type(*width), len.max(1), table(&index)
+            ));
+        }
+        for (ident, value) in self.module.statics() {
+            generated.push_str(&format!("static {WORD} {} = {};\n", mangle(ident), self.operand(value)));
+        }
+        generated.push('\n');
+
+        for func in self.module.functions() {
+            self.function(func);
+        }
+
+        match self.module.find_function("main") {
+            Some(main) => {
+                let args = ["argc", "argv"].iter()
+                    .take(main.num_params() as usize)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.output.push_str(&format!("{WORD} bcplrt_start({WORD} argc, {WORD} argv) {{\n    return {}({args});\n}}\n", mangle("main")));
+            }
+            None if matches!(build_kind, BuildKind::Executable) => return Err(CodegenError::NoMainFunction),
+            None => ()
+        }
+
+        generated.push_str(&self.output);
+        Ok(generated)
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        self.output.push_str("    ");
+        self.output.push_str(line.as_ref());
+        self.output.push('\n');
+    }
+
+    fn function_header(func: &Function) -> String {
+        let params = if func.num_params() == 0 {
+            "void".to_string()
+        }
+        else {
+            (0..func.num_params())
+                .map(|i| format!("{WORD} {}", var(&i)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let storage = if func.is_local() { "static " } else { "" };
+        format!("{storage}{WORD} {}({params})", mangle(func.ident()))
+    }
+
+    fn function(&mut self, func: &Function) {
+        self.output.push_str(&format!("{} {{\n", Self::function_header(func)));
+        for local in func.num_params()..func.num_vars() {
+            self.line(format!("{WORD} {} = 0;", var(&local)));
+        }
+        for (index, size) in func.vectors().iter().enumerate() {
+            self.line(format!("{WORD} {}[{size}] = {{0}};", vector(&index)));
+        }
+
+        for (id, block) in func.blocks().iter().enumerate() {
+            self.output.push_str(&format!("B{id}: ;\n"));
+            for instr in block.instrs() {
+                self.instr(instr);
+            }
+            self.terminator(block.terminator());
+        }
+
+        self.output.push_str("}\n\n");
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Var(v) => var(v),
+            Operand::Const(value) if *value == i64::MIN => "INT64_MIN".into(),
+            Operand::Const(value) => format!("INT64_C({value})"),
+            Operand::String(index) => format!("(({WORD}) {})", c_string_literal(&self.module.strings()[*index])),
+            Operand::Function(ident) => format!("(({WORD}) &{})", mangle(ident)),
+            Operand::Static(ident) => format!("(({WORD}) &{})", mangle(ident)),
+            Operand::Global(index) => format!("(({WORD}) &bcplrt_globals[{index}])"),
+            Operand::Vector(index) => format!("(({WORD}) {})", vector(index)),
+            Operand::Table(index) => format!("(({WORD}) {}.elems)", table(index))
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        let line = match instr {
+            Instr::Copy(dest, value) => format!("{} = {};", var(dest), self.operand(value)),
+            Instr::Unary(dest, op, value) => format!("{} = {};", var(dest), self.unary(*op, &self.operand(value))),
+            Instr::Binary(dest, op, lhs, rhs) => format!("{} = {};", var(dest), self.binary(*op, &self.operand(lhs), &self.operand(rhs))),
+            Instr::AddrOf(dest, v) => format!("{} = ({WORD}) &{};", var(dest), var(v)),
+            Instr::Load(dest, base, index, width) => format!(
+                "{} = ({WORD}) (({}*) {})[{}];",
+                var(dest), memory_type(*width), self.operand(base), self.operand(index)
+            ),
+            Instr::Store(base, index, value, width) => format!(
+                "(({}*) {})[{}] = {};",
+                memory_type(*width), self.operand(base), self.operand(index), self.operand(value)
+            ),
+            Instr::Call(dest, callee, args) => {
+                let function = match callee {
+                    Callee::Function(ident) => mangle(ident),
+                    Callee::Runtime(ident) => ident.to_string(),
+                    Callee::Indirect(pointer) => format!("(({WORD} (*)()) {})", self.operand(pointer))
+                };
+                let args = args.iter().map(|arg| self.operand(arg)).collect::<Vec<_>>().join(", ");
+                match callee {
+                    // runtime functions are declared with their C signatures
+                    Callee::Runtime("bcplrt_match_failure") => format!("bcplrt_match_failure((const char*) {args});"),
+                    Callee::Runtime("bcplrt_finish") => "bcplrt_finish();".into(),
+                    _ => format!("{} = ({WORD}) {function}({args});", var(dest))
+                }
+            }
+        };
+        self.line(line);
+    }
+
+    fn unary(&self, op: UnaryOp, value: &str) -> String {
+        match op {
+            UnaryOp::Not => format!("~{value}"),
+            UnaryOp::Abs => format!("({value} < 0 ? ({WORD}) -(uint64_t) {value} : {value})"),
+            UnaryOp::FAbs => format!("(bcplrt_wtof({value}) < 0 ? bcplrt_ftow(-bcplrt_wtof({value})) : {value})"),
+            UnaryOp::Neg => format!("({WORD}) -(uint64_t) {value}"),
+            UnaryOp::FNeg => format!("bcplrt_ftow(-bcplrt_wtof({value}))"),
+            UnaryOp::IntToFloat => format!("bcplrt_ftow((double) {value})"),
+            UnaryOp::UIntToFloat => format!("bcplrt_ftow((double) (uint64_t) {value})"),
+            UnaryOp::FloatToInt => format!("({WORD}) bcplrt_wtof({value})"),
+            UnaryOp::Truncate { bits, signed } => format!("({WORD}) ({}int{bits}_t) {value}", if signed { "" } else { "u" })
+        }
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: &str, rhs: &str) -> String {
+        // unsigned arithmetic wraps around instead of overflowing
+        let wrapping = |op| format!("({WORD}) ((uint64_t) {lhs} {op} (uint64_t) {rhs})");
+        let float = |op| format!("bcplrt_ftow(bcplrt_wtof({lhs}) {op} bcplrt_wtof({rhs}))");
+
+        match op {
+            BinaryOp::Add => wrapping("+"),
+            BinaryOp::Sub => wrapping("-"),
+            BinaryOp::Mul => wrapping("*"),
+            BinaryOp::Div => format!("{lhs} / {rhs}"),
+            BinaryOp::Rem => format!("{lhs} % {rhs}"),
+            BinaryOp::UDiv => wrapping("/"),
+            BinaryOp::URem => wrapping("%"),
+            BinaryOp::FAdd => float("+"),
+            BinaryOp::FSub => float("-"),
+            BinaryOp::FMul => float("*"),
+            BinaryOp::FDiv => float("/"),
+            BinaryOp::FRem => format!("bcplrt_ftow(bcplrt_wtof({lhs}) - (double) ({WORD}) (bcplrt_wtof({lhs}) / bcplrt_wtof({rhs})) * bcplrt_wtof({rhs}))"),
+            BinaryOp::And => format!("{lhs} & {rhs}"),
+            BinaryOp::Or => format!("{lhs} | {rhs}"),
+            BinaryOp::Xor => format!("{lhs} ^ {rhs}"),
+            BinaryOp::Shl => wrapping("<<"),
+            BinaryOp::Shr => wrapping(">>"),
+            BinaryOp::Cmp(condition) => {
+                let op = comparison_operator(condition);
+                match condition {
+                    _ if condition.is_float() => format!("bcplrt_wtof({lhs}) {op} bcplrt_wtof({rhs})"),
+                    Condition::ULt | Condition::ULe | Condition::UGt | Condition::UGe => format!("(uint64_t) {lhs} {op} (uint64_t) {rhs}"),
+                    _ => format!("{lhs} {op} {rhs}")
+                }
+            }
+        }
+    }
+
+    fn terminator(&mut self, terminator: &Terminator) {
+        let line = match terminator {
+            Terminator::Jump(block) => format!("goto B{block};"),
+            Terminator::Branch(condition, on_true, on_false) => format!("if ({}) goto B{on_true}; else goto B{on_false};", self.operand(condition)),
+            Terminator::Switch(value, cases, default) => {
+                let cases = cases.iter()
+                    .map(|(case, block)| format!("case {}: goto B{block}; ", self.operand(&Operand::Const(*case))))
+                    .collect::<String>();
+                format!("switch ({}) {{ {cases}default: goto B{default}; }}", self.operand(value))
+            }
+            Terminator::Return(value) => format!("return {};", self.operand(value)),
+            // only reached after `bcplrt_match_failure` or `bcplrt_finish`, which do not return
+            Terminator::Unreachable => "return 0;".into()
+        };
+        self.line(line);
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt::Display, path::{Path, PathBuf}, process::Command};
+
+use crate::{
+    context::BuildKind,
+    ir::Module
+};
+
+mod c;
+mod x86_64;
+
+const RUNTIME_HEADER: &str = include_str!("runtime.h");
+const RUNTIME_SOURCE: &str = include_str!("runtime.c");
+
+const RUNTIME_HEADER_NAME: &str = "bcplpp_runtime.h";
+const RUNTIME_SOURCE_NAME: &str = "bcplpp_runtime.c";
+
+pub enum CodegenError {
+    Io(std::io::Error),
+    ToolFailed(String, Option<i32>),
+    NoMainFunction
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message: String = match self {
+            Self::Io(err) => format!("I/O error during code generation: {err}"),
+            Self::ToolFailed(tool, Some(code)) => format!("`{tool}` exited with code {code}."),
+            Self::ToolFailed(tool, None) => format!("`{tool}` was terminated by a signal."),
+            Self::NoMainFunction => "no `main` function defined.".into()
+        };
+        f.write_str(&message)
+    }
+}
+
+impl From<std::io::Error> for CodegenError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+pub type CodegenResult<T> = Result<T, CodegenError>;
+
+// temporary directory holding intermediate files, removed when dropped
+struct WorkDir {
+    path: PathBuf
+}
+
+impl WorkDir {
+    fn create() -> CodegenResult<Self> {
+        let path = std::env::temp_dir().join(format!("bcplpp-{}", std::process::id()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    fn write(&self, filename: &str, contents: &str) -> CodegenResult<PathBuf> {
+        let path = self.path.join(filename);
+        std::fs::write(&path, contents)?;
+        Ok(path)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+fn run_tool(mut command: Command) -> CodegenResult<()> {
+    let status = command.status()?;
+
+    if status.success() {
+        Ok(())
+    }
+    else {
+        Err(CodegenError::ToolFailed(command.get_program().to_string_lossy().into(), status.code()))
+    }
+}
+
+fn c_compiler() -> String {
+    std::env::var("CC").unwrap_or_else(|_| "cc".into())
+}
+
+fn assembler() -> String {
+    std::env::var("AS").unwrap_or_else(|_| "as".into())
+}
+
+fn assemble(source: &Path, output: &Path) -> CodegenResult<()> {
+    let mut command = Command::new(assembler());
+    command.arg("-o").arg(output).arg(source);
+    run_tool(command)
+}
+
+fn compile_c_files(sources: &[&Path], output: &str, build_kind: &BuildKind) -> CodegenResult<()> {
+    let mut command = Command::new(c_compiler());
+    command.args(["-std=c99", "-w", "-o", output]);
+
+    match build_kind {
+        BuildKind::Executable => (),
+        BuildKind::Object => { command.arg("-c"); }
+        BuildKind::SharedObject => { command.args(["-shared", "-fPIC", "-DBCPLPP_NO_MAIN"]); }
+    }
+
+    command.args(sources);
+    run_tool(command)
+}
+
+pub fn compile_with_c_backend(module: &Module, output: &str, build_kind: &BuildKind) -> CodegenResult<()> {
+    let generated = c::CGenerator::new(module).generate(build_kind)?;
+
+    let workdir = WorkDir::create()?;
+    workdir.write(RUNTIME_HEADER_NAME, RUNTIME_HEADER)?;
+    let generated = workdir.write("out.c", &generated)?;
+
+    if let BuildKind::Object = build_kind {
+        compile_c_files(&[&generated], output, build_kind)
+    }
+    else {
+        let runtime = workdir.write(RUNTIME_SOURCE_NAME, RUNTIME_SOURCE)?;
+        compile_c_files(&[&generated, &runtime], output, build_kind)
+    }
+}
+
+pub fn compile_with_x86_64_backend(module: &Module, output: &str, build_kind: &BuildKind) -> CodegenResult<()> {
+    let generated = x86_64::AsmGenerator::new(module).generate(build_kind)?;
+
+    let workdir = WorkDir::create()?;
+    let generated = workdir.write("out.s", &generated)?;
+
+    if let BuildKind::Object = build_kind {
+        return assemble(&generated, Path::new(output))
+    }
+
+    // the runtime is still written in C, so linking is done through the C compiler driver
+    let object = workdir.path.join("out.o");
+    assemble(&generated, &object)?;
+
+    workdir.write(RUNTIME_HEADER_NAME, RUNTIME_HEADER)?;
+    let runtime = workdir.write(RUNTIME_SOURCE_NAME, RUNTIME_SOURCE)?;
+    compile_c_files(&[&object, &runtime], output, build_kind)
+}
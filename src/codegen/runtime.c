@@ -0,0 +1,178 @@
+/* BCPL++ runtime library, linked into every executable and shared object */
+#include <ctype.h>
+#include <stdarg.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "bcplpp_runtime.h"
+
+bcpl_word bcplrt_globals[BCPLRT_GLOBAL_VECTOR_SIZE];
+
+bcpl_word bcplrt_alloc(bcpl_word words) {
+    bcpl_word *block = calloc(words > 0 ? words : 1, sizeof(bcpl_word));
+    if(!block) {
+        fputs("bcplpp: out of memory\n", stderr);
+        exit(1);
+    }
+    return (bcpl_word) block;
+}
+
+bcpl_word bcplrt_alloc_vec(bcpl_word len) {
+    bcpl_word *block = (bcpl_word*) bcplrt_alloc(len + 1);
+    block[0] = len;
+    return (bcpl_word) (block + 1);
+}
+
+bcpl_word bcplrt_slice(bcpl_word vec, bcpl_word lo, bcpl_word hi) {
+    bcpl_word len = hi > lo ? hi - lo : 0;
+    bcpl_word slice = bcplrt_alloc_vec(len);
+    memcpy((bcpl_word*) slice, (bcpl_word*) vec + lo, len * sizeof(bcpl_word));
+    return slice;
+}
+
+void bcplrt_match_failure(const char *loc) {
+    fprintf(stderr, "bcplpp: no pattern matched at %s\n", loc);
+    exit(1);
+}
+
+void bcplrt_finish(void) {
+    fflush(stdout);
+    exit(0);
+}
+
+/*
+ * functions of the `Std` section provided by the runtime, called like external functions;
+ * they are not declared in the header, but by the generated code calling them
+ */
+
+/* programs not requiring `Std` may define functions of the same names */
+#define BCPLRT_STD __attribute__((weak)) bcpl_word
+
+/* returned by `rdch` at the end of the input */
+#define BCPLRT_ENDSTREAMCH ((bcpl_word) -1)
+
+/* digits after the decimal point printed by `%f` without a precision */
+#define BCPLRT_DEFAULT_PRECISION 6
+
+static int bcplrt_digits(const char **c) {
+    int value = 0;
+    while(isdigit((unsigned char) **c))
+        value = value * 10 + *(*c)++ - '0';
+    return value;
+}
+
+static void bcplrt_write_binary(unsigned long long value, int width) {
+    char digits[64];
+    int len = 0;
+    do {
+        digits[len++] = '0' + (value & 1);
+        value >>= 1;
+    } while(value);
+
+    for(int i = len; i < width; i++)
+        putchar('0');
+    while(len)
+        putchar(digits[--len]);
+}
+
+/*
+ * directives are `%n` and `%d` (natural width), `%i`, `%x`, `%o`, `%b`, `%s`, `%c` and `%f`, all but `%n` and `%d`
+ * followed by an optional width, `%f` also by an optional `.precision`; escapes like `*n` are decoded by the compiler;
+ * each directive consumes the next of the variadic arguments
+ */
+BCPLRT_STD bcpl_writef(bcpl_word format, ...) {
+    va_list args;
+    va_start(args, format);
+
+    for(const char *c = (const char*) format; *c; c++) {
+        if(*c != '%') {
+            putchar(*c);
+            continue;
+        }
+
+        char directive = (char) tolower((unsigned char) *++c);
+        if(!directive || !strchr("ndixobscf", directive)) {
+            putchar('%');
+            if(!directive)
+                c--;
+            else if(directive != '%')
+                putchar(*c);
+            continue;
+        }
+
+        const char *rest = c + 1;
+        int width = 0, precision = BCPLRT_DEFAULT_PRECISION;
+        if(directive != 'n' && directive != 'd') {
+            width = bcplrt_digits(&rest);
+            if(directive == 'f' && *rest == '.') {
+                rest++;
+                precision = bcplrt_digits(&rest);
+            }
+        }
+        c = rest - 1;
+
+        bcpl_word arg = va_arg(args, bcpl_word);
+        switch(directive) {
+        case 'n': case 'd': case 'i': printf("%*lld", width, (long long) arg); break;
+        case 'x': printf("%0*llX", width, (unsigned long long) arg); break;
+        case 'o': printf("%0*llo", width, (unsigned long long) arg); break;
+        case 'b': bcplrt_write_binary((unsigned long long) arg, width); break;
+        case 's': printf("%*s", width, (const char*) arg); break;
+        case 'c': printf("%*c", width, (int) arg); break;
+        case 'f': printf("%*.*f", width, precision, bcplrt_wtof(arg)); break;
+        }
+    }
+    va_end(args);
+
+    fflush(stdout);
+    return 0;
+}
+
+BCPLRT_STD bcpl_writes(bcpl_word string) {
+    fputs((const char*) string, stdout);
+    fflush(stdout);
+    return 0;
+}
+
+BCPLRT_STD bcpl_writen(bcpl_word n) {
+    printf("%lld", (long long) n);
+    fflush(stdout);
+    return 0;
+}
+
+BCPLRT_STD bcpl_wrch(bcpl_word ch) {
+    putchar((int) ch);
+    fflush(stdout);
+    return 0;
+}
+
+BCPLRT_STD bcpl_newline(void) {
+    return bcpl_wrch('\n');
+}
+
+BCPLRT_STD bcpl_rdch(void) {
+    int ch = getchar();
+    return ch == EOF ? BCPLRT_ENDSTREAMCH : (bcpl_word) ch;
+}
+
+/* `newvec(upb)` has the elements `0` to `upb`, like `vec upb` */
+BCPLRT_STD bcpl_newvec(bcpl_word upb) {
+    return bcplrt_alloc_vec(upb >= 0 ? upb + 1 : 0);
+}
+
+BCPLRT_STD bcpl_freevec(bcpl_word vec) {
+    if(vec)
+        free((bcpl_word*) vec - 1);
+    return 0;
+}
+
+BCPLRT_STD bcpl_stop(bcpl_word code) {
+    fflush(stdout);
+    exit((int) code);
+}
+
+#ifndef BCPLPP_NO_MAIN
+int main(int argc, char **argv) {
+    return (int) bcplrt_start((bcpl_word) argc, (bcpl_word) argv);
+}
+#endif
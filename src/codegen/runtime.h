@@ -0,0 +1,46 @@
+/* BCPL++ runtime interface, shared by the generated code and the runtime library */
+#ifndef BCPLPP_RUNTIME_H
+#define BCPLPP_RUNTIME_H
+
+#include <stdint.h>
+#include <string.h>
+
+typedef int64_t bcpl_word;
+
+/* floating point values are stored bit-cast into a word */
+static inline double bcplrt_wtof(bcpl_word w) {
+    double f;
+    memcpy(&f, &w, sizeof f);
+    return f;
+}
+
+static inline bcpl_word bcplrt_ftow(double f) {
+    bcpl_word w;
+    memcpy(&w, &f, sizeof w);
+    return w;
+}
+
+/* must match `GLOBAL_VECTOR_SIZE` of the compiler */
+#define BCPLRT_GLOBAL_VECTOR_SIZE 1024
+
+/* storage of all `global` variables, indexed by their global number */
+extern bcpl_word bcplrt_globals[BCPLRT_GLOBAL_VECTOR_SIZE];
+
+/* allocates a zeroed block of `words` words */
+bcpl_word bcplrt_alloc(bcpl_word words);
+
+/* allocates a vector of `len` elements, its length is stored in the word before the first element */
+bcpl_word bcplrt_alloc_vec(bcpl_word len);
+
+/* copies the elements `lo` to `hi` (exclusive) of a vector into a new one */
+bcpl_word bcplrt_slice(bcpl_word vec, bcpl_word lo, bcpl_word hi);
+
+void bcplrt_match_failure(const char *loc);
+
+/* `finish`, terminates the program successfully */
+void bcplrt_finish(void);
+
+/* entry point generated by the compiler, calls `main` */
+bcpl_word bcplrt_start(bcpl_word argc, bcpl_word argv);
+
+#endif /* BCPLPP_RUNTIME_H */
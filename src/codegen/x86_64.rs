@@ -0,0 +1,510 @@
+use crate::{
+    context::BuildKind,
+    ir::{BinaryOp, BlockId, Callee, Condition, Function, Instr, Module, Operand, Terminator, UnaryOp, Var, Width}
+};
+
+use super::{CodegenError, CodegenResult};
+
+const ARG_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+const WORD_SIZE: i64 = 8;
+// switches with fewer cases are lowered to a chain of comparisons
+const MIN_JUMP_TABLE_CASES: usize = 4;
+
+fn condition_code(condition: Condition) -> &'static str {
+    // `ucomisd` sets the flags like an unsigned comparison, and additionally the parity flag if either operand is NaN;
+    // `<` and `<=` compare the swapped operands, since only `a` and `ae` are false for NaN
+    match condition {
+        Condition::Eq | Condition::FEq => "e",
+        Condition::Ne | Condition::FNe => "ne",
+        Condition::Lt => "l",
+        Condition::Le => "le",
+        Condition::Gt => "g",
+        Condition::Ge => "ge",
+        Condition::ULt => "b",
+        Condition::ULe => "be",
+        Condition::UGt | Condition::FGt | Condition::FLt => "a",
+        Condition::UGe | Condition::FGe | Condition::FLe => "ae",
+    }
+}
+
+fn mangle(ident: &str) -> String {
+    format!("bcpl_{ident}")
+}
+
+fn asm_string_literal(value: &str) -> String {
+    let mut lit = String::with_capacity(value.len() + 2);
+    lit.push('"');
+    for byte in value.bytes() {
+        match byte {
+            b'"' => lit.push_str("\\\""),
+            b'\\' => lit.push_str("\\\\"),
+            b' '..=b'~' => lit.push(byte as char),
+            _ => lit.push_str(&format!("\\{byte:03o}"))
+        }
+    }
+    lit.push('"');
+    lit
+}
+
+// value of an operand in the data section, which can only be a constant or an address
+fn data_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Const(value) => value.to_string(),
+        Operand::String(index) => format!(".LS{index}"),
+        Operand::Table(index) => format!(".LT{index}+{WORD_SIZE}"),
+        Operand::Function(ident) => mangle(ident),
+        _ => unreachable!("operand {operand:?} is not known at link time")
+    }
+}
+
+// stack slot of a variable, relative to `%rbp`
+fn slot(var: Var) -> String {
+    format!("{}(%rbp)", -(var as i64 + 1) * WORD_SIZE)
+}
+
+pub struct AsmGenerator<'a> {
+    module: &'a Module,
+    text: String,
+    // offsets of the stack-allocated vectors of the current function, relative to `%rbp`
+    vector_offsets: Vec<i64>
+}
+
+impl<'a> AsmGenerator<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        Self {
+            module,
+            text: String::new(),
+            vector_offsets: vec![]
+        }
+    }
+
+    pub fn generate(mut self, build_kind: &BuildKind) -> CodegenResult<String> {
+        for func in self.module.functions() {
+            self.function(func);
+        }
+
+        match self.module.find_function("main") {
+            Some(_) => {
+                // `argc` and `argv` are already in place, so `main` can be tail-called
+                self.text.push_str("\n\t.globl bcplrt_start\n\t.type bcplrt_start, @function\nbcplrt_start:\n");
+                self.text.push_str(&format!("\tjmp {}@PLT\n", mangle("main")));
+            }
+            None if matches!(build_kind, BuildKind::Executable) => return Err(CodegenError::NoMainFunction),
+            None => ()
+        }
+
+        let mut generated = String::from("# generated by bcplpp\n\t.text\n");
+        generated.push_str(&self.text);
+
+        if !self.module.statics().is_empty() || !self.module.tables().is_empty() {
+            generated.push_str("\n\t.data\n");
+            for (index, (width, elems)) in self.module.tables().iter().enumerate() {
+                let directive = if *width == Width::Word { ".quad" } else { ".byte" };
+                generated.push_str(&format!("\t.p2align 3\n.LT{index}:\n\t.quad {}\n", elems.len()));
+                for elem in elems {
+                    generated.push_str(&format!("\t{directive} {}\n", data_operand(elem)));
+                }
+            }
+
+            generated.push_str("\t.p2align 3\n");
+            for (ident, value) in self.module.statics() {
+                generated.push_str(&format!("{}:\n\t.quad {}\n", mangle(ident), data_operand(value)));
+            }
+        }
+
+        if !self.module.strings().is_empty() {
+            generated.push_str("\n\t.section .rodata\n");
+            for (i, value) in self.module.strings().iter().enumerate() {
+                generated.push_str(&format!(".LS{i}:\n\t.string {}\n", asm_string_literal(value)));
+            }
+        }
+
+        generated.push_str("\n\t.section .note.GNU-stack,\"\",@progbits\n");
+        Ok(generated)
+    }
+
+    fn emit(&mut self, instruction: impl AsRef<str>) {
+        self.text.push('\t');
+        self.text.push_str(instruction.as_ref());
+        self.text.push('\n');
+    }
+
+    fn block_label(func: &Function, block: BlockId) -> String {
+        format!(".L{}_{block}", func.ident())
+    }
+
+    fn function(&mut self, func: &Function) {
+        let name = mangle(func.ident());
+        // vectors are placed below the variable slots, with their elements in ascending order
+        let mut frame_words = func.num_vars() as i64;
+        self.vector_offsets = func.vectors().iter()
+            .map(|size| {
+                frame_words += size;
+                -frame_words * WORD_SIZE
+            })
+            .collect();
+
+        let frame_size = (frame_words * WORD_SIZE + 15) & !15;
+        if !func.is_local() {
+            self.text.push_str(&format!("\n\t.globl {name}"));
+        }
+        self.text.push_str(&format!("\n\t.type {name}, @function\n{name}:\n"));
+        self.emit("pushq %rbp");
+        self.emit("movq %rsp, %rbp");
+        if frame_size > 0 {
+            self.emit(format!("subq ${frame_size}, %rsp"));
+        }
+
+        // spill parameters onto the stack frame
+        for param in 0..func.num_params() {
+            match ARG_REGISTERS.get(param as usize) {
+                Some(register) => self.emit(format!("movq {register}, {}", slot(param))),
+                None => {
+                    self.emit(format!("movq {}(%rbp), %rax", 16 + (param as i64 - ARG_REGISTERS.len() as i64) * WORD_SIZE));
+                    self.emit(format!("movq %rax, {}", slot(param)));
+                }
+            }
+        }
+
+        // the remaining variables and all vectors start out as zero
+        for var in func.num_params()..func.num_vars() {
+            self.emit(format!("movq $0, {}", slot(var)));
+        }
+        if frame_words > func.num_vars() as i64 {
+            self.emit(format!("leaq {}(%rbp), %rdi", -frame_words * WORD_SIZE));
+            self.emit(format!("movq ${}, %rcx", frame_words - func.num_vars() as i64));
+            self.emit("xorl %eax, %eax");
+            self.emit("rep stosq");
+        }
+
+        for (id, block) in func.blocks().iter().enumerate() {
+            self.text.push_str(&format!("{}:\n", Self::block_label(func, id)));
+            for instr in block.instrs() {
+                self.instr(instr);
+            }
+            self.terminator(func, id, block.terminator());
+        }
+    }
+
+    // loads `operand` into `register`
+    fn load(&mut self, operand: &Operand, register: &str) {
+        match operand {
+            Operand::Var(var) => self.emit(format!("movq {}, {register}", slot(*var))),
+            Operand::Const(value) if i32::try_from(*value).is_ok() => self.emit(format!("movq ${value}, {register}")),
+            Operand::Const(value) => self.emit(format!("movabsq ${value}, {register}")),
+            Operand::String(index) => self.emit(format!("leaq .LS{index}(%rip), {register}")),
+            Operand::Function(ident) => self.emit(format!("movq {}@GOTPCREL(%rip), {register}", mangle(ident))),
+            Operand::Static(ident) => self.emit(format!("leaq {}(%rip), {register}", mangle(ident))),
+            Operand::Global(index) => {
+                self.emit(format!("movq bcplrt_globals@GOTPCREL(%rip), {register}"));
+                if *index != 0 {
+                    self.emit(format!("addq ${}, {register}", index * WORD_SIZE));
+                }
+            }
+            Operand::Vector(index) => self.emit(format!("leaq {}(%rbp), {register}", self.vector_offsets[*index])),
+            Operand::Table(index) => self.emit(format!("leaq .LT{index}+{WORD_SIZE}(%rip), {register}"))
+        }
+    }
+
+    fn store(&mut self, var: Var) {
+        self.emit(format!("movq %rax, {}", slot(var)));
+    }
+
+    // address of the element `index` of width `width` relative to `%rax`, using `%rcx` for dynamic indices
+    fn element_address(&mut self, index: &Operand, width: Width) -> String {
+        match index {
+            Operand::Const(index) => format!("{}(%rax)", index * width.size()),
+            _ => {
+                self.load(index, "%rcx");
+                format!("(%rax,%rcx,{})", width.size())
+            }
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Copy(dest, value) => {
+                self.load(value, "%rax");
+                self.store(*dest);
+            }
+            Instr::Unary(dest, op, value) => {
+                self.load(value, "%rax");
+                self.unary(*op);
+                self.store(*dest);
+            }
+            Instr::Binary(dest, op, lhs, rhs) => {
+                self.load(lhs, "%rax");
+                self.load(rhs, "%rcx");
+                self.binary(*op);
+                self.store(*dest);
+            }
+            Instr::AddrOf(dest, var) => {
+                self.emit(format!("leaq {}, %rax", slot(*var)));
+                self.store(*dest);
+            }
+            Instr::Load(dest, base, index, width) => {
+                self.load(base, "%rax");
+                let address = self.element_address(index, *width);
+                match width {
+                    Width::Word => self.emit(format!("movq {address}, %rax")),
+                    Width::Byte => self.emit(format!("movzbq {address}, %rax")),
+                    Width::SignedByte => self.emit(format!("movsbq {address}, %rax"))
+                }
+                self.store(*dest);
+            }
+            Instr::Store(base, index, value, width) => {
+                self.load(value, "%rdx");
+                self.load(base, "%rax");
+                let address = self.element_address(index, *width);
+                match width {
+                    Width::Word => self.emit(format!("movq %rdx, {address}")),
+                    Width::Byte | Width::SignedByte => self.emit(format!("movb %dl, {address}"))
+                }
+            }
+            Instr::Call(dest, callee, args) => {
+                self.call(callee, args);
+                self.store(*dest);
+            }
+        }
+    }
+
+    fn unary(&mut self, op: UnaryOp) {
+        match op {
+            UnaryOp::Not => self.emit("notq %rax"),
+            UnaryOp::Abs => {
+                self.emit("movq %rax, %rcx");
+                self.emit("negq %rax");
+                self.emit("cmovlq %rcx, %rax");
+            }
+            UnaryOp::FAbs => self.emit("btrq $63, %rax"),
+            UnaryOp::Neg => self.emit("negq %rax"),
+            UnaryOp::FNeg => self.emit("btcq $63, %rax"),
+            UnaryOp::IntToFloat => {
+                self.emit("cvtsi2sdq %rax, %xmm0");
+                self.emit("movq %xmm0, %rax");
+            }
+            UnaryOp::UIntToFloat => {
+                // values above `i64::MAX` are halved, converted and doubled again
+                self.emit("testq %rax, %rax");
+                self.emit("js 1f");
+                self.emit("cvtsi2sdq %rax, %xmm0");
+                self.emit("jmp 2f");
+                self.text.push_str("1:\n");
+                self.emit("movq %rax, %rcx");
+                self.emit("shrq %rcx");
+                self.emit("andl $1, %eax");
+                self.emit("orq %rax, %rcx");
+                self.emit("cvtsi2sdq %rcx, %xmm0");
+                self.emit("addsd %xmm0, %xmm0");
+                self.text.push_str("2:\n");
+                self.emit("movq %xmm0, %rax");
+            }
+            UnaryOp::FloatToInt => {
+                self.emit("movq %rax, %xmm0");
+                self.emit("cvttsd2si %xmm0, %rax");
+            }
+            UnaryOp::Truncate { bits: 8, signed: true } => self.emit("movsbq %al, %rax"),
+            UnaryOp::Truncate { bits: 16, signed: true } => self.emit("movswq %ax, %rax"),
+            UnaryOp::Truncate { bits: 32, signed: true } => self.emit("movslq %eax, %rax"),
+            UnaryOp::Truncate { bits: 8, signed: false } => self.emit("movzbq %al, %rax"),
+            UnaryOp::Truncate { bits: 16, signed: false } => self.emit("movzwq %ax, %rax"),
+            UnaryOp::Truncate { bits: 32, signed: false } => self.emit("movl %eax, %eax"),
+            UnaryOp::Truncate { .. } => ()
+        }
+    }
+
+    // applies `op` to `%rax` and `%rcx`, leaving the result in `%rax`
+    fn binary(&mut self, op: BinaryOp) {
+        let float = |generator: &mut Self, instruction: &str| {
+            generator.emit("movq %rax, %xmm0");
+            generator.emit("movq %rcx, %xmm1");
+            generator.emit(format!("{instruction} %xmm1, %xmm0"));
+            generator.emit("movq %xmm0, %rax");
+        };
+
+        match op {
+            BinaryOp::Add => self.emit("addq %rcx, %rax"),
+            BinaryOp::Sub => self.emit("subq %rcx, %rax"),
+            BinaryOp::Mul => self.emit("imulq %rcx, %rax"),
+            BinaryOp::Div | BinaryOp::Rem => {
+                self.emit("cqto");
+                self.emit("idivq %rcx");
+                if op == BinaryOp::Rem {
+                    self.emit("movq %rdx, %rax");
+                }
+            }
+            BinaryOp::UDiv | BinaryOp::URem => {
+                self.emit("xorl %edx, %edx");
+                self.emit("divq %rcx");
+                if op == BinaryOp::URem {
+                    self.emit("movq %rdx, %rax");
+                }
+            }
+            BinaryOp::FAdd => float(self, "addsd"),
+            BinaryOp::FSub => float(self, "subsd"),
+            BinaryOp::FMul => float(self, "mulsd"),
+            BinaryOp::FDiv => float(self, "divsd"),
+            BinaryOp::FRem => {
+                // a - trunc(a / b) * b
+                self.emit("movq %rax, %xmm0");
+                self.emit("movq %rcx, %xmm1");
+                self.emit("movapd %xmm0, %xmm2");
+                self.emit("divsd %xmm1, %xmm2");
+                self.emit("cvttsd2si %xmm2, %rax");
+                self.emit("cvtsi2sdq %rax, %xmm2");
+                self.emit("mulsd %xmm1, %xmm2");
+                self.emit("subsd %xmm2, %xmm0");
+                self.emit("movq %xmm0, %rax");
+            }
+            BinaryOp::And => self.emit("andq %rcx, %rax"),
+            BinaryOp::Or => self.emit("orq %rcx, %rax"),
+            BinaryOp::Xor => self.emit("xorq %rcx, %rax"),
+            BinaryOp::Shl => self.emit("shlq %cl, %rax"),
+            BinaryOp::Shr => self.emit("shrq %cl, %rax"),
+            BinaryOp::Cmp(condition) => {
+                if condition.is_float() {
+                    self.emit("movq %rax, %xmm0");
+                    self.emit("movq %rcx, %xmm1");
+                    if let Condition::FLt | Condition::FLe = condition {
+                        self.emit("ucomisd %xmm0, %xmm1");
+                    }
+                    else {
+                        self.emit("ucomisd %xmm1, %xmm0");
+                    }
+                }
+                else {
+                    self.emit("cmpq %rcx, %rax");
+                }
+                self.emit(format!("set{} %al", condition_code(condition)));
+                // NaN is unequal to everything, including itself
+                match condition {
+                    Condition::FEq => {
+                        self.emit("setnp %cl");
+                        self.emit("andb %cl, %al");
+                    }
+                    Condition::FNe => {
+                        self.emit("setp %cl");
+                        self.emit("orb %cl, %al");
+                    }
+                    _ => ()
+                }
+                self.emit("movzbq %al, %rax");
+            }
+        }
+    }
+
+    // calls `callee` according to the System V calling convention, leaving the result in `%rax`
+    fn call(&mut self, callee: &Callee, args: &[Operand]) {
+        // the stack is aligned to 16 bytes between instructions, since the frame size is too
+        let stack_args = args.len().saturating_sub(ARG_REGISTERS.len()) as i64;
+        let padding = stack_args % 2;
+        if padding != 0 {
+            self.emit(format!("subq ${WORD_SIZE}, %rsp"));
+        }
+
+        for arg in args.iter().skip(ARG_REGISTERS.len()).rev() {
+            match arg {
+                Operand::Var(var) => self.emit(format!("pushq {}", slot(*var))),
+                _ => {
+                    self.load(arg, "%rax");
+                    self.emit("pushq %rax");
+                }
+            }
+        }
+        for (arg, register) in args.iter().zip(ARG_REGISTERS) {
+            self.load(arg, register);
+        }
+
+        match callee {
+            Callee::Function(ident) => {
+                self.emit("xorl %eax, %eax");
+                self.emit(format!("call {}@PLT", mangle(ident)));
+            }
+            Callee::Runtime(ident) => {
+                self.emit("xorl %eax, %eax");
+                self.emit(format!("call {ident}@PLT"));
+            }
+            Callee::Indirect(pointer) => {
+                self.load(pointer, "%r11");
+                self.emit("xorl %eax, %eax");
+                self.emit("call *%r11");
+            }
+        }
+
+        if stack_args + padding > 0 {
+            self.emit(format!("addq ${}, %rsp", (stack_args + padding) * WORD_SIZE));
+        }
+    }
+
+    fn terminator(&mut self, func: &Function, block: BlockId, terminator: &Terminator) {
+        match terminator {
+            // blocks are laid out in order, so jumps to the following block can be omitted
+            Terminator::Jump(target) if *target == block + 1 => (),
+            Terminator::Jump(target) => self.emit(format!("jmp {}", Self::block_label(func, *target))),
+            Terminator::Branch(condition, on_true, on_false) => {
+                self.load(condition, "%rax");
+                self.emit("testq %rax, %rax");
+                self.emit(format!("jne {}", Self::block_label(func, *on_true)));
+                if *on_false != block + 1 {
+                    self.emit(format!("jmp {}", Self::block_label(func, *on_false)));
+                }
+            }
+            Terminator::Switch(value, cases, default) => {
+                self.load(value, "%rax");
+                self.switch(func, block, cases, *default);
+            }
+            Terminator::Return(value) => {
+                self.load(value, "%rax");
+                self.emit("leave");
+                self.emit("ret");
+            }
+            Terminator::Unreachable => self.emit("ud2")
+        }
+    }
+
+    // dispatches on the value in %rax, using a jump table if the cases are dense enough
+    fn switch(&mut self, func: &Function, block: BlockId, cases: &[(i64, BlockId)], default: BlockId) {
+        let min = cases.iter().map(|(case, _)| *case).min().unwrap_or(0);
+        let max = cases.iter().map(|(case, _)| *case).max().unwrap_or(0);
+        let range = (max as i128 - min as i128 + 1) as u128;
+
+        if cases.len() >= MIN_JUMP_TABLE_CASES && range <= 2 * cases.len() as u128 {
+            let table = format!(".LJ{}_{block}", func.ident());
+            if min != 0 {
+                self.load(&Operand::Const(min), "%rcx");
+                self.emit("subq %rcx, %rax");
+            }
+            self.emit(format!("cmpq ${}, %rax", range - 1));
+            self.emit(format!("ja {}", Self::block_label(func, default)));
+            self.emit(format!("leaq {table}(%rip), %rcx"));
+            self.emit("movslq (%rcx,%rax,4), %rax");
+            self.emit("addq %rcx, %rax");
+            self.emit("jmp *%rax");
+
+            self.emit(".p2align 2");
+            self.text.push_str(&format!("{table}:\n"));
+            for value in min..=max {
+                let target = cases.iter()
+                    .find(|(case, _)| *case == value)
+                    .map(|(_, target)| *target)
+                    .unwrap_or(default);
+                self.emit(format!(".long {} - {table}", Self::block_label(func, target)));
+            }
+            return
+        }
+
+        for (case, target) in cases {
+            if i32::try_from(*case).is_ok() {
+                self.emit(format!("cmpq ${case}, %rax"));
+            }
+            else {
+                self.load(&Operand::Const(*case), "%rcx");
+                self.emit("cmpq %rcx, %rax");
+            }
+            self.emit(format!("je {}", Self::block_label(func, *target)));
+        }
+        if default != block + 1 {
+            self.emit(format!("jmp {}", Self::block_label(func, default)));
+        }
+    }
+}
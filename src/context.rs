@@ -1,14 +1,17 @@
-use std::{collections::HashMap, sync::{Arc, Mutex}};
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
 
 use colorize::AnsiColor;
 
 use crate::{
     terminate,
-    source_file::{SourceFile, SourceFileId, Located},
+    source_file::{SourceFile, SourceFileId, Located, WithLocation},
     token::lexer::Lexer,
     ast,
     parser::{Parser, ParseError},
-    error::CompilerError, typechecker::typecheck_ast
+    error::{CompilerError, Severity}, typechecker::{typecheck_ast, is_external, TypeCheckError},
+    codegen::{compile_with_c_backend, compile_with_x86_64_backend},
+    ir,
+    interpreter::{self, RuntimeError}
 };
 
 #[derive(Default)]
@@ -45,6 +48,25 @@ impl BuildKind {
     }
 }
 
+#[derive(Default)]
+pub enum Backend {
+    #[default]
+    C,
+    X86_64
+}
+
+impl TryFrom<&str> for Backend {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "c" => Ok(Self::C),
+            "x86_64" | "asm" => Ok(Self::X86_64),
+            _ => Err(())
+        }
+    }
+}
+
 #[derive(Default)]
 pub enum OutputFile {
     Name(String),
@@ -53,21 +75,38 @@ pub enum OutputFile {
 }
 
 impl OutputFile {
-    pub fn to_filename(self, build_kind: &BuildKind) -> String {
+    pub fn to_filename(&self, build_kind: &BuildKind) -> String {
         match self {
-            Self::Name(filename) => filename,
+            Self::Name(filename) => filename.clone(),
             Self::Default => format!("a{}", build_kind.ext(std::env::consts::OS).expect("invalid operating system"))
         }
     }
 }
 
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
+// directories searched for required sections after the ones given with `-I`
+pub const SEARCH_PATH_VAR: &str = "BCPLPP_PATH";
+
+// sections embedded in the compiler, used when no file of the search directories defines them
+const BUNDLED_SECTIONS: &[(&str, &str)] = &[
+    ("Std", include_str!("lib/Std.bpp"))
+];
+
 #[derive(Default)]
 pub struct Context {
     program_name: String,
     output_file: OutputFile,
 
     build_kind: BuildKind,
+    backend: Backend,
+    run_mode: bool,
+    program_args: Vec<String>,
     tags: Vec<String>,
+    // syntax errors reported before giving up, unlimited if `None`
+    max_errors: Option<usize>,
+    // directories containing the files of required sections, like `Std.bpp` for `require Std`
+    search_dirs: Vec<PathBuf>,
 
     source_files: HashMap<SourceFileId, SourceFile>,
 
@@ -76,9 +115,11 @@ pub struct Context {
 
 impl Context {
     pub fn from_program_name(program_name: String) -> Self {
-        let mut ctx = Self::default();
-        ctx.program_name = program_name;
-        ctx
+        Self {
+            program_name,
+            max_errors: Some(DEFAULT_MAX_ERRORS),
+            ..Default::default()
+        }
     }
     
     pub fn set_output_file(&mut self, output_file: String) {
@@ -97,6 +138,31 @@ impl Context {
         self.build_kind = build_kind;
     }
 
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    // `0` disables the limit
+    pub fn set_max_errors(&mut self, max_errors: usize) {
+        self.max_errors = (max_errors > 0).then_some(max_errors);
+    }
+
+    pub fn add_search_dir(&mut self, dir: String) {
+        self.search_dirs.push(dir.into());
+    }
+
+    pub fn set_run_mode(&mut self, run_mode: bool) {
+        self.run_mode = run_mode;
+    }
+
+    pub fn run_mode(&self) -> bool {
+        self.run_mode
+    }
+
+    pub fn add_program_args(&mut self, args: impl IntoIterator<Item = String>) {
+        self.program_args.extend(args);
+    }
+
     pub fn add_source_files(&mut self, source_files: HashMap<SourceFileId, SourceFile>) {
         self.source_files.extend(source_files);
     }
@@ -108,7 +174,7 @@ impl Context {
     pub fn fatal_error(&self, err: &str) -> ! {
         eprintln!("{} {} {err}",
             format!("{}:", self.program_name()).bold(),
-            format!("fatal error:").bold().red()
+            "fatal error:".to_string().bold().red()
         );
         
         terminate();
@@ -119,41 +185,168 @@ impl Context {
     }
 
     //                              Warnings            Errors
-    pub fn compile(&self) -> CompileResult {
+    pub fn compile(&mut self) -> CompileResult {
         if self.source_files.is_empty() {
             self.fatal_error("no input files.");
         }
             
         let mut warnings = vec![];
-        let errors = self.source_files.values()
-            .map(|file| {
-                self.print_compiling_status(file.path());
-                Parser::new(Lexer::from(file), self.ast.clone())
-            })
-            .filter_map(|mut parser| {
-                let err = parser.parse();
-                warnings.extend(parser.warnings().iter().map(|warn| warn.clone().map(ParseError::into)));
-                err.err()
-            })
-            .map(|err| err.map(ParseError::into))
-            .collect::<Vec<_>>();
+        let mut errors: Vec<Located<CompilerError>> = vec![];
+        let mut ids = self.source_files.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            self.print_compiling_status(self.source_files[&id].path());
+            if !self.parse_file(id, &mut errors, &mut warnings) {
+                break
+            }
+        }
+
+        if errors.is_empty() {
+            self.load_required_sections(&mut errors, &mut warnings);
+        }
 
         if !errors.is_empty() {
-            return CompileResult::Err(errors)
+            return CompileResult::Err(sorted(errors))
         }
 
-        if let Err(err) = typecheck_ast(self.ast.clone()) {
-            println!("typechecker error...");
+        match typecheck_ast(self.ast.clone()) {
+            Ok(warns) => warnings.extend(warns.into_iter().map(|warn| warn.map(TypeCheckError::into))),
+            Err(errors) => return CompileResult::Err(sorted(errors.into_iter().map(|err| err.map(TypeCheckError::into)).collect()))
         }
 
 //        println!("generated ast: {:#?}", self.ast);
+        // in run mode, the program gets interpreted by `run()` instead
+        if !self.run_mode {
+            self.generate_code();
+        }
+
         if !warnings.is_empty() {
-            CompileResult::Warn(warnings)
+            CompileResult::Warn(sorted(warnings))
         }
         else {
             CompileResult::Ok
         }
     }
+
+    // returns `false` once the error limit is reached
+    fn parse_file(&self, id: SourceFileId, errors: &mut Vec<Located<CompilerError>>, warnings: &mut Vec<Located<CompilerError>>) -> bool {
+        // the error limit applies to all files together
+        let remaining = self.max_errors.map(|max| max.saturating_sub(errors.len()));
+        if remaining == Some(0) {
+            return false
+        }
+
+        let file = &self.source_files[&id];
+        let mut parser = Parser::new(Lexer::from(file), self.ast.clone());
+        parser.set_error_limit(remaining);
+        if let Err(errs) = parser.parse() {
+            errors.extend(errs.into_iter().map(|err| err.map(ParseError::into)));
+        }
+        warnings.extend(parser.warnings().iter().map(|warn| warn.clone().map(ParseError::into)));
+        true
+    }
+
+    // parses `<Section>.bpp` for each required section that is not defined by the input files,
+    // including the sections required by the files found this way; bundled sections are parsed silently
+    fn load_required_sections(&mut self, errors: &mut Vec<Located<CompilerError>>, warnings: &mut Vec<Located<CompilerError>>) {
+        let mut searched = HashSet::new();
+        while let Some(required) = self.next_missing_section(&searched) {
+            searched.insert(required.to_string());
+
+            let dirs = self.search_dirs(&self.source_files[&required.location().file_id()]);
+            let filename = format!("{}.bpp", *required);
+            let id = self.source_files.len() as SourceFileId;
+            let file = match dirs.iter().map(|dir| dir.join(&filename)).find(|path| path.is_file()) {
+                Some(path) => match SourceFile::read(path.to_string_lossy().into_owned(), id) {
+                    Ok(file) => {
+                        self.print_compiling_status(file.path());
+                        file
+                    }
+                    Err(err) => self.fatal_error(&format!("could not read `{}`: {err}.", path.display()))
+                },
+                None => match BUNDLED_SECTIONS.iter().find(|(section, _)| *section == required.as_str()) {
+                    Some((_, source)) => SourceFile::new(format!("<bundled>/{filename}"), source.to_string(), id),
+                    // sections provided by the runtime alone don't need a file
+                    None if is_external(&required) => continue,
+                    None => {
+                        errors.push(section_not_found(&required, &dirs));
+                        continue
+                    }
+                }
+            };
+
+            self.source_files.insert(id, file);
+            if !self.parse_file(id, errors, warnings) {
+                return
+            }
+        }
+    }
+
+    // first required section, by name, that is neither defined nor searched for yet
+    fn next_missing_section(&self, searched: &HashSet<String>) -> Option<Located<String>> {
+        let ast = self.ast.lock().unwrap();
+        ast.sections().values()
+            .flat_map(|section| section.required())
+            .filter(|required| !ast.sections().contains_key(&***required) && !searched.contains(&***required))
+            .min_by(|a, b| a.as_str().cmp(b.as_str()))
+            .cloned()
+    }
+
+    // the directory of the requiring file, then the `-I` directories, then the ones of `BCPLPP_PATH`
+    fn search_dirs(&self, requiring: &SourceFile) -> Vec<PathBuf> {
+        let parent = Path::new(requiring.path()).parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+
+        let mut dirs = vec![parent.to_path_buf()];
+        dirs.extend(self.search_dirs.iter().cloned());
+        if let Some(paths) = std::env::var_os(SEARCH_PATH_VAR) {
+            dirs.extend(std::env::split_paths(&paths).filter(|dir| !dir.as_os_str().is_empty()));
+        }
+        dirs
+    }
+
+    fn generate_code(&self) {
+        let output_file = self.output_file.to_filename(&self.build_kind);
+        let compile_with_backend = match self.backend {
+            Backend::C => compile_with_c_backend,
+            Backend::X86_64 => compile_with_x86_64_backend
+        };
+
+        let module = ir::lower_program(&self.ast.lock().unwrap(), &self.source_files);
+        if let Err(err) = compile_with_backend(&module, &output_file, &self.build_kind) {
+            self.fatal_error(&err.to_string());
+        }
+    }
+
+    // interprets the parsed program, returning the exit code of `main`
+    pub fn run(&self) -> Result<i32, Located<CompilerError>> {
+        let ast = self.ast.lock().unwrap();
+        let Some(main) = ast.find_function("main")
+        else {
+            self.fatal_error("no `main` function defined.");
+        };
+
+        let mut args = vec![self.program_name.clone()];
+        args.extend(self.program_args.iter().cloned());
+        interpreter::run(&ast, main, &args).map_err(|err| err.map(RuntimeError::into))
+    }
+}
+
+fn section_not_found(required: &Located<String>, searched: &[PathBuf]) -> Located<CompilerError> {
+    let searched = searched.iter().map(|dir| format!("`{}`", dir.display())).collect::<Vec<_>>().join(", ");
+    CompilerError::new(
+        Severity::Error,
+        format!("Section `{}` not found, searched: {searched}.", **required),
+        Some(format!("Pass the file defining it or add the directory containing `{}.bpp` with `-I` or `{SEARCH_PATH_VAR}`.", **required)),
+        vec![]
+    ).with_location(required.location().clone())
+}
+
+// diagnostics are collected from hash maps in parts, so they get printed in the order of the source instead
+fn sorted(mut diagnostics: Vec<Located<CompilerError>>) -> Vec<Located<CompilerError>> {
+    diagnostics.sort_by(|a, b| a.location().cmp(b.location()));
+    diagnostics
 }
 
 pub enum CompileResult {
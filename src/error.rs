@@ -56,7 +56,3 @@ impl CompilerError {
 
 impl WithLocation for CompilerError {}
 
-pub trait IntoCompilerError: Into<CompilerError> {}
-
-impl IntoCompilerError for CompilerError {}
-
@@ -0,0 +1,126 @@
+use std::io::{Read, Write};
+
+use crate::{typechecker::native_functions, writef::{parse_format, Directive, DirectiveKind, FormatPart}};
+
+use super::{value::Value, RuntimeError};
+
+// returned by `rdch` at the end of the input
+const ENDSTREAMCH: i64 = -1;
+
+// digits after the decimal point printed by `%f` without a precision
+const DEFAULT_PRECISION: usize = 6;
+
+pub(super) fn is_builtin(ident: &str) -> bool {
+    // functions of the `Std` section, implemented natively by the interpreter and the C runtime
+    native_functions("Std").contains(&ident)
+}
+
+pub(super) fn call<'a>(ident: &str, args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError> {
+    let arg = args.first().cloned().unwrap_or_default();
+    let arg = &arg;
+    match ident {
+        "rdch" => return Ok(Value::Int(rdch())),
+        // `newvec(upb)` has the elements `0` to `upb`, like `vec upb`
+        "newvec" => return Ok(Value::vector(vec![Value::default(); integer_of(ident, arg)?.max(-1) as usize + 1])),
+        // vectors are reference counted
+        "freevec" => return Ok(Value::default()),
+        "stop" => return Err(RuntimeError::Finish(integer_of(ident, arg)? as i32)),
+        _ => ()
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let output = match ident {
+        "writef" => match args.split_first() {
+            Some((format, args)) => writef(&string_of(ident, format)?, args)?,
+            None => return Err(RuntimeError::InvalidArgument(ident.into()))
+        },
+        "writes" => string_of(ident, arg)?,
+        "writen" => integer_of(ident, arg)?.to_string(),
+        "wrch" => char_of(ident, arg)?.to_string(),
+        "newline" => "\n".into(),
+        _ => unreachable!("unknown builtin `{ident}`")
+    };
+
+    let _ = stdout.write_all(output.as_bytes());
+    let _ = stdout.flush();
+    Ok(Value::default())
+}
+
+fn rdch() -> i64 {
+    // output written so far should be visible before waiting for input
+    let _ = std::io::stdout().flush();
+
+    let mut byte = [0u8];
+    match std::io::stdin().lock().read(&mut byte) {
+        Ok(1) => byte[0] as i64,
+        _ => ENDSTREAMCH
+    }
+}
+
+fn string_of(builtin: &str, value: &Value) -> Result<String, RuntimeError> {
+    match value.strip_pointers() {
+        Value::String(string) => Ok(string.to_string()),
+        Value::Vector(vector) => vector.borrow().iter()
+            .take_while(|elem| elem.as_int() != Some(0))
+            .map(|elem| char_of(builtin, elem))
+            .collect(),
+        _ => Err(RuntimeError::InvalidArgument(builtin.into()))
+    }
+}
+
+fn integer_of(builtin: &str, value: &Value) -> Result<i64, RuntimeError> {
+    value.as_int().ok_or_else(|| RuntimeError::InvalidArgument(builtin.into()))
+}
+
+fn char_of(builtin: &str, value: &Value) -> Result<char, RuntimeError> {
+    match value {
+        Value::Char(ch) => Ok(*ch),
+        _ => integer_of(builtin, value)
+            .and_then(|code| char::from_u32(code as u32).ok_or_else(|| RuntimeError::InvalidArgument(builtin.into())))
+    }
+}
+
+fn float_of(builtin: &str, value: &Value) -> Result<f64, RuntimeError> {
+    match value {
+        Value::Float(value) => Ok(*value),
+        _ => Err(RuntimeError::InvalidArgument(builtin.into()))
+    }
+}
+
+fn writef(format: &str, args: &[Value]) -> Result<String, RuntimeError> {
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut next_arg = || args.next().cloned().unwrap_or_default();
+
+    for (_, _, part) in parse_format(format) {
+        let Directive { kind, width, precision } = match part {
+            FormatPart::Text(ch) => {
+                output.push(ch);
+                continue
+            }
+            FormatPart::Unknown(ch) => {
+                output.push('%');
+                output.push(ch);
+                continue
+            }
+            FormatPart::Arg(directive) => directive
+        };
+
+        let arg = next_arg();
+        let formatted = match kind {
+            DirectiveKind::Number | DirectiveKind::Integer => integer_of("writef", &arg)?.to_string(),
+            DirectiveKind::Hex => format!("{:X}", integer_of("writef", &arg)? as u64),
+            DirectiveKind::Octal => format!("{:o}", integer_of("writef", &arg)? as u64),
+            DirectiveKind::Binary => format!("{:b}", integer_of("writef", &arg)? as u64),
+            DirectiveKind::String => string_of("writef", &arg)?,
+            DirectiveKind::Char => char_of("writef", &arg)?.to_string(),
+            DirectiveKind::Float => format!("{:.*}", precision.unwrap_or(DEFAULT_PRECISION), float_of("writef", &arg)?)
+        };
+
+        let padding = if kind.pads_with_zeros() { '0' } else { ' ' };
+        output.extend(std::iter::repeat_n(padding, width.saturating_sub(formatted.chars().count())));
+        output.push_str(&formatted);
+    }
+
+    Ok(output)
+}
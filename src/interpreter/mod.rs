@@ -0,0 +1,1006 @@
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{
+    ast::{
+        Program, Decl, Function, FunctionBody, BasicFunctionBody, GlobalDecl, ManifestDecl, Param, StaticDecl,
+        expr::{Expr, ExprKind, Lambda},
+        stmt::{Stmt, StmtKind},
+        pattern::{Pattern, PatternTerm},
+        types::{TypeIndex, TypeKind}
+    },
+    error::{CompilerError, Severity},
+    source_file::{Located, Location, WithLocation}
+};
+
+use self::value::{Pointer, Value};
+
+mod builtins;
+mod value;
+
+const MAX_CALL_DEPTH: usize = 10000;
+const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+pub enum RuntimeError {
+    UndefinedIdent(String),
+    NotCallable,
+    MatchFailure,
+    DivisionByZero,
+    IndexOutOfBounds(i64, usize),
+    InvalidDeref,
+    InvalidOperands(&'static str),
+    InvalidArgument(String),
+    StackOverflow,
+    // unwinds all calls for `finish` and `stop`, not an error when it reaches `main`
+    Finish(i32) // exit code
+}
+
+impl WithLocation for RuntimeError {}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message: String = match self {
+            Self::UndefinedIdent(ident) => format!("Use of undefined identifier `{ident}`."),
+            Self::NotCallable => "Called value is not a function.".into(),
+            Self::MatchFailure => "No pattern matched.".into(),
+            Self::DivisionByZero => "Division by zero.".into(),
+            Self::IndexOutOfBounds(index, len) => format!("Index {index} is out of bounds for length {len}."),
+            Self::InvalidDeref => "Dereferenced value is not a pointer.".into(),
+            Self::InvalidOperands(op) => format!("Invalid operands for {op}."),
+            Self::InvalidArgument(func) => format!("Invalid argument passed to `{func}`."),
+            Self::StackOverflow => format!("Stack overflow; exceeded {MAX_CALL_DEPTH} nested calls."),
+            Self::Finish(_) => "Program finished.".into()
+        };
+        f.write_str(&message)
+    }
+}
+
+impl From<RuntimeError> for CompilerError {
+    fn from(value: RuntimeError) -> Self {
+        CompilerError::new(Severity::Error, value.to_string(), None, vec![])
+    }
+}
+
+pub type RuntimeResult<T> = Result<T, Located<RuntimeError>>;
+
+// how control leaves a statement
+enum Flow<'a> {
+    Normal,
+    Break,
+    Next,
+    ResultIs(Value<'a>),
+    Return,
+    Goto(String)
+}
+
+type Scope<'a> = HashMap<String, Rc<RefCell<Value<'a>>>>;
+
+// runs `main` on a separate thread, since deeply recursive BCPL programs quickly exceed the default stack size
+pub fn run(program: &Program, main: &Function, args: &[String]) -> RuntimeResult<i32> {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name("interpreter".into())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || Interpreter::new(program).run_main(main, args))
+            .expect("error spawning interpreter thread")
+            .join()
+            .unwrap_or_else(|err| std::panic::resume_unwind(err))
+    })
+}
+
+pub struct Interpreter<'a> {
+    program: &'a Program,
+
+    scopes: Vec<Scope<'a>>,
+    statics: Scope<'a>,
+    globals: HashMap<i64, Rc<RefCell<Value<'a>>>>,
+    // tables are static, every evaluation of a table expression yields the same vector
+    tables: HashMap<*const Expr, Value<'a>>,
+    call_depth: usize
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            scopes: vec![],
+            statics: HashMap::new(),
+            globals: HashMap::new(),
+            tables: HashMap::new(),
+            call_depth: 0
+        }
+    }
+
+    fn run_main(&mut self, main: &'a Function, args: &[String]) -> RuntimeResult<i32> {
+        let argv = Value::vector(args.iter().map(|arg| Value::String(arg.as_str().into())).collect());
+        let args = [Value::Int(args.len() as i64), argv].into_iter()
+            .take(main.params().len())
+            .collect();
+
+        match self.call_function(main, args, main.location()) {
+            Ok(result) => Ok(result.as_int().unwrap_or_default() as i32),
+            Err(err) => match *err {
+                RuntimeError::Finish(code) => Ok(code),
+                _ => Err(err)
+            }
+        }
+    }
+
+    fn type_kind(&self, typ: &Option<TypeIndex>) -> Option<&'a TypeKind> {
+        typ.and_then(|typ| self.program.types().resolve(typ))
+    }
+
+    fn is_unsigned(&self, typ: &Option<TypeIndex>) -> bool {
+        self.type_kind(typ).map(TypeKind::is_unsigned).unwrap_or(false)
+    }
+
+    fn lookup_local(&self, ident: &str) -> Option<Rc<RefCell<Value<'a>>>> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident)).cloned()
+    }
+
+    fn declare_local(&mut self, ident: &str, value: Value<'a>) {
+        self.scopes.last_mut()
+            .expect("no scope to declare local in")
+            .insert(ident.to_string(), Rc::new(RefCell::new(value)));
+    }
+
+    // storage of a static or global variable, created on first use
+    fn lookup_variable(&mut self, ident: &str) -> Option<Rc<RefCell<Value<'a>>>> {
+        if let Some(static_decl) = self.program.find_decl::<StaticDecl>(ident) {
+            if !self.statics.contains_key(ident) {
+                let value = self.expr(static_decl.value()).unwrap_or_default();
+                self.statics.insert(ident.to_string(), Rc::new(RefCell::new(value)));
+            }
+            return self.statics.get(ident).cloned()
+        }
+
+        let global = self.program.find_decl::<GlobalDecl>(ident)?;
+        let ExprKind::IntLit(index) = global.index().kind() else {
+            unreachable!("global numbers are folded into literals")
+        };
+        Some(self.globals.entry(*index as i64).or_default().clone())
+    }
+
+    fn is_nullary_variant(&self, ident: &str) -> bool {
+        self.lookup_local(ident).is_none() && self.program.types().find_variant(ident)
+            .map(|(_, _, variant)| variant.fields().is_empty())
+            .unwrap_or(false)
+    }
+
+    fn call_function(&mut self, func: &'a Function, args: Vec<Value<'a>>, loc: &Location) -> RuntimeResult<Value<'a>> {
+        self.in_frame(loc, |interpreter| interpreter.function_body(func, &args))
+    }
+
+    // runs `body` with a fresh set of scopes, like a called function
+    fn in_frame(&mut self, loc: &Location, body: impl FnOnce(&mut Self) -> RuntimeResult<Value<'a>>) -> RuntimeResult<Value<'a>> {
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow.with_location(loc.clone()))
+        }
+
+        self.call_depth += 1;
+        let caller_scopes = std::mem::replace(&mut self.scopes, vec![Scope::new()]);
+        let result = body(self);
+        self.scopes = caller_scopes;
+        self.call_depth -= 1;
+
+        result
+    }
+
+    fn params(&mut self, params: &'a [Param], args: &[Value<'a>]) -> RuntimeResult<()> {
+        for (param, arg) in params.iter().zip(args) {
+            if !self.pattern(param.pattern(), arg)? {
+                return Err(RuntimeError::MatchFailure.with_location(param.pattern().location().clone()))
+            }
+        }
+        Ok(())
+    }
+
+    fn function_body(&mut self, func: &'a Function, args: &[Value<'a>]) -> RuntimeResult<Value<'a>> {
+        self.params(func.params(), args)?;
+
+        match func.body() {
+            FunctionBody::Expr(expr) => self.expr(expr),
+            FunctionBody::Stmt(stmt) => self.label_scope(stmt).map(|_| Value::default()),
+            FunctionBody::PatternMatchedExpr(branches) => {
+                for (patterns, expr) in branches {
+                    self.scopes.push(Scope::new());
+                    if self.patterns(patterns, args)? {
+                        return self.expr(expr)
+                    }
+                    self.scopes.pop();
+                }
+                Err(RuntimeError::MatchFailure.with_location(func.location().clone()))
+            }
+            FunctionBody::PatternMatchedStmt(branches) => {
+                for (patterns, stmt) in branches {
+                    self.scopes.push(Scope::new());
+                    if self.patterns(patterns, args)? {
+                        return self.label_scope(stmt).map(|_| Value::default())
+                    }
+                    self.scopes.pop();
+                }
+                Err(RuntimeError::MatchFailure.with_location(func.location().clone()))
+            }
+        }
+    }
+
+    // captured variables are declared in a scope outside of the parameters
+    fn call_lambda(&mut self, lambda: &'a Lambda, captures: &[Value<'a>], args: Vec<Value<'a>>, loc: &Location) -> RuntimeResult<Value<'a>> {
+        self.in_frame(loc, |interpreter| {
+            for (ident, value) in lambda.captures().iter().zip(captures) {
+                interpreter.declare_local(ident, value.clone());
+            }
+
+            interpreter.scopes.push(Scope::new());
+            interpreter.params(lambda.params(), &args)?;
+            match lambda.body() {
+                BasicFunctionBody::Expr(expr) => interpreter.expr(expr),
+                BasicFunctionBody::Stmt(stmt) => interpreter.label_scope(stmt).map(|_| Value::default())
+            }
+        })
+    }
+
+    fn patterns(&mut self, patterns: &'a [Located<Pattern>], values: &[Value<'a>]) -> RuntimeResult<bool> {
+        for (pattern, value) in patterns.iter().zip(values) {
+            if !self.pattern(pattern, value)? {
+                return Ok(false)
+            }
+        }
+        Ok(true)
+    }
+
+    // tests `value` against `pattern`, declaring its bindings in the current scope
+    fn pattern(&mut self, pattern: &'a Located<Pattern>, value: &Value<'a>) -> RuntimeResult<bool> {
+        match &**pattern {
+            Pattern::Any | Pattern::Remaining => Ok(true),
+            Pattern::Query(ident) if ident == "_" => Ok(true),
+            Pattern::Query(ident) if self.is_nullary_variant(ident) => {
+                let (_, tag, _) = self.program.types().find_variant(ident).unwrap();
+                Ok(matches!(value.strip_pointers(), Value::Variant(value_tag, _) if value_tag == tag))
+            }
+            Pattern::Query(ident) => {
+                self.declare_local(ident, value.clone());
+                Ok(true)
+            }
+            Pattern::Term(term) => self.pattern_term(term, value),
+            Pattern::And(lhs, rhs) => Ok(self.pattern(lhs, value)? && self.pattern(rhs, value)?),
+            Pattern::Or(lhs, rhs) => Ok(self.pattern(lhs, value)? || self.pattern(rhs, value)?),
+            Pattern::Variant(ident, args) => {
+                let Some((_, tag, _)) = self.program.types().find_variant(ident)
+                else {
+                    return Err(RuntimeError::UndefinedIdent(ident.clone()).with_location(pattern.location().clone()))
+                };
+
+                match value.strip_pointers() {
+                    Value::Variant(value_tag, fields) if value_tag == tag => self.patterns(args, &fields),
+                    _ => Ok(false)
+                }
+            }
+            Pattern::Record(fields) => {
+                for (ident, field) in fields {
+                    let Some(field_value) = value.field(ident).and_then(|pointer| pointer.load()) else {
+                        return Ok(false)
+                    };
+                    if !self.pattern(field, &field_value)? {
+                        return Ok(false)
+                    }
+                }
+                Ok(true)
+            }
+            Pattern::List(elems) => {
+                let values = match value.strip_pointers() {
+                    Value::Vector(vector) => vector.borrow().clone(),
+                    Value::String(string) => string.chars().map(Value::Char).collect(),
+                    _ => return Ok(false)
+                };
+
+                let remaining = elems.iter().position(|elem| matches!(**elem, Pattern::Remaining));
+                let matches_len = match remaining {
+                    Some(_) => values.len() >= elems.len() - 1,
+                    None => values.len() == elems.len()
+                };
+                if !matches_len {
+                    return Ok(false)
+                }
+
+                // `..` may match no elements, so the patterns after it are matched from the end of the list
+                for (i, elem) in elems.iter().enumerate() {
+                    let index = match remaining {
+                        Some(pos) if i == pos => continue,
+                        Some(pos) if i > pos => values.len() - (elems.len() - i),
+                        _ => i
+                    };
+                    if !self.pattern(elem, &values[index])? {
+                        return Ok(false)
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    fn pattern_term(&mut self, term: &'a PatternTerm, value: &Value<'a>) -> RuntimeResult<bool> {
+        let (expr, accepted): (&Expr, &[Ordering]) = match term {
+            PatternTerm::Range(from, to) => {
+                let unsigned = self.is_unsigned(from.typ());
+                let from = self.expr(from)?;
+                let to = self.expr(to)?;
+                return Ok(value.compare(&from, unsigned).map(Ordering::is_ge).unwrap_or(false)
+                    && value.compare(&to, unsigned).map(Ordering::is_le).unwrap_or(false))
+            }
+            PatternTerm::Basic(expr) | PatternTerm::Eq(expr) => {
+                let expected = self.expr(expr)?;
+                return Ok(value.equals(&expected))
+            }
+            PatternTerm::Ne(expr) => {
+                let expected = self.expr(expr)?;
+                return Ok(!value.equals(&expected))
+            }
+            PatternTerm::Lt(expr) => (expr, &[Ordering::Less]),
+            PatternTerm::Le(expr) => (expr, &[Ordering::Less, Ordering::Equal]),
+            PatternTerm::Gt(expr) => (expr, &[Ordering::Greater]),
+            PatternTerm::Ge(expr) => (expr, &[Ordering::Greater, Ordering::Equal]),
+        };
+
+        let unsigned = self.is_unsigned(expr.typ());
+        let operand = self.expr(expr)?;
+        Ok(value.compare(&operand, unsigned).map(|ordering| accepted.contains(&ordering)).unwrap_or(false))
+    }
+
+    // runs a function, lambda or `valof` body; jumps to labels of blocks are handled by the blocks,
+    // the typechecker only allows jumps to the labels of the body itself otherwise
+    fn label_scope(&mut self, body: &'a Stmt) -> RuntimeResult<Flow<'a>> {
+        let mut flow = self.stmt(body)?;
+        while let Flow::Goto(label) = &flow && body.is_labelled(label) {
+            flow = self.stmt(body)?;
+        }
+        Ok(flow)
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> RuntimeResult<Flow<'a>> {
+        match stmt.kind() {
+            StmtKind::Nop | StmtKind::Case(_) | StmtKind::DefaultCase => Ok(Flow::Normal),
+            StmtKind::Expr(expr) => self.expr(expr).map(|_| Flow::Normal),
+            StmtKind::Block(stmts) => {
+                self.scopes.push(Scope::new());
+                let flow = self.block(stmts, 0);
+                self.scopes.pop();
+                flow
+            }
+            StmtKind::ResultIs(expr) => self.expr(expr).map(Flow::ResultIs),
+            StmtKind::Return => Ok(Flow::Return),
+            StmtKind::If(condition, if_branch, else_branch) => {
+                if self.expr(condition)?.is_truthy() {
+                    self.stmt(if_branch)
+                }
+                else if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch)
+                }
+                else {
+                    Ok(Flow::Normal)
+                }
+            }
+            StmtKind::Unless(condition, body) => {
+                if self.expr(condition)?.is_truthy() {
+                    Ok(Flow::Normal)
+                }
+                else {
+                    self.stmt(body)
+                }
+            }
+            StmtKind::While(condition, body) => self.conditional_loop(condition, body, false),
+            StmtKind::Until(condition, body) => self.conditional_loop(condition, body, true),
+            StmtKind::Loop(body) => self.repeat_loop(body, None, false),
+            StmtKind::RepeatWhile(body, condition) => self.repeat_loop(body, Some(condition), false),
+            StmtKind::RepeatUntil(body, condition) => self.repeat_loop(body, Some(condition), true),
+            StmtKind::Label(_, body) => self.stmt(body),
+            StmtKind::Goto(label) => Ok(Flow::Goto(label.clone())),
+            StmtKind::Finish => Err(RuntimeError::Finish(0).with_location(stmt.location().clone())),
+            StmtKind::For(iter, init, limit, step, body) => self.for_loop(iter, init, limit, step, body),
+            StmtKind::SwitchOn(condition, body) => self.switchon(condition, body),
+            StmtKind::Break => Ok(Flow::Break),
+            StmtKind::Next => Ok(Flow::Next),
+            StmtKind::Match(args, branches) => self.match_stmt(stmt.location(), args, branches, false),
+            StmtKind::Every(args, branches) => self.match_stmt(stmt.location(), args, branches, true),
+            StmtKind::Binding(bindings) => {
+                let values = bindings.iter()
+                    .map(|(_, expr)| self.expr(expr))
+                    .collect::<RuntimeResult<Vec<_>>>()?;
+
+                for ((pattern, _), value) in bindings.iter().zip(values) {
+                    if !self.pattern(pattern, &value)? {
+                        return Err(RuntimeError::MatchFailure.with_location(pattern.location().clone()))
+                    }
+                }
+                Ok(Flow::Normal)
+            }
+            StmtKind::Assign(targets, values) => {
+                // all targets and values are evaluated before the first store
+                let pointers = targets.iter()
+                    .map(|target| self.address_of(target))
+                    .collect::<RuntimeResult<Vec<_>>>()?;
+                let values = values.iter()
+                    .map(|value| self.expr(value))
+                    .collect::<RuntimeResult<Vec<_>>>()?;
+
+                for ((pointer, value), target) in pointers.iter().zip(values).zip(targets) {
+                    self.store(pointer, self.narrow(value, target.typ()), target.location())?;
+                }
+                Ok(Flow::Normal)
+            }
+            StmtKind::CompoundAssign(operation) => {
+                let (target, value) = operation.binary_operands().expect("compound assignment without operator");
+                let pointer = self.address_of(target)?;
+                let current = self.index(Value::Pointer(pointer.clone()), 0, target.location())?;
+                let value = self.expr(value)?;
+
+                let result = self.operation(operation, current, value)?;
+                self.store(&pointer, self.narrow(result, target.typ()), target.location())?;
+                Ok(Flow::Normal)
+            }
+        }
+    }
+
+    fn stmts(&mut self, stmts: &'a [Stmt]) -> RuntimeResult<Flow<'a>> {
+        for stmt in stmts {
+            match self.stmt(stmt)? {
+                Flow::Normal => (),
+                flow => return Ok(flow)
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // runs the statements of a block from `start`; jumps to labels of the block itself continue at
+    // the labelled statement and keep its variables
+    fn block(&mut self, stmts: &'a [Stmt], start: usize) -> RuntimeResult<Flow<'a>> {
+        let mut flow = self.stmts(&stmts[start..]);
+        while let Ok(Flow::Goto(label)) = &flow && let Some(pos) = stmts.iter().position(|stmt| stmt.is_labelled(label)) {
+            flow = self.stmts(&stmts[pos..]);
+        }
+        flow
+    }
+
+    fn conditional_loop(&mut self, condition: &'a Expr, body: &'a Stmt, negate: bool) -> RuntimeResult<Flow<'a>> {
+        while self.expr(condition)?.is_truthy() != negate {
+            match self.stmt(body)? {
+                Flow::Normal | Flow::Next => (),
+                Flow::Break => break,
+                flow => return Ok(flow)
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // runs `body` before checking `condition`, or forever without one
+    fn repeat_loop(&mut self, body: &'a Stmt, condition: Option<&'a Expr>, negate: bool) -> RuntimeResult<Flow<'a>> {
+        loop {
+            match self.stmt(body)? {
+                Flow::Normal | Flow::Next => (),
+                Flow::Break => break,
+                flow => return Ok(flow)
+            }
+
+            if let Some(condition) = condition && self.expr(condition)?.is_truthy() == negate {
+                break
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn for_loop(&mut self, iter: &'a Located<Pattern>, init: &'a Expr, limit: &'a Option<Box<Expr>>, step: &'a Option<Box<Expr>>, body: &'a Stmt) -> RuntimeResult<Flow<'a>> {
+        let init = self.expr(init)?;
+        let limit = limit.as_ref().map(|limit| self.expr(limit)).transpose()?;
+        let step = match step {
+            Some(step) => self.expr(step)?.as_int().unwrap_or(1),
+            None => 1
+        };
+
+        self.scopes.push(Scope::new());
+        let ident = match &**iter {
+            Pattern::Query(ident) => ident.as_str(),
+            _ => "_"
+        };
+        self.declare_local(ident, init);
+        let iterator = self.lookup_local(ident).unwrap();
+
+        let flow = loop {
+            let current = iterator.borrow().as_int().unwrap_or_default();
+            if let Some(limit) = limit.as_ref().and_then(Value::as_int)
+                && ((step >= 0 && current > limit) || (step < 0 && current < limit)) {
+                break Flow::Normal
+            }
+
+            match self.stmt(body)? {
+                Flow::Normal | Flow::Next => (),
+                Flow::Break => break Flow::Normal,
+                flow => break flow
+            }
+
+            let next = iterator.borrow().as_int().unwrap_or_default().wrapping_add(step);
+            *iterator.borrow_mut() = Value::Int(next);
+        };
+
+        self.scopes.pop();
+        Ok(flow)
+    }
+
+    fn switchon(&mut self, condition: &'a Expr, body: &'a Stmt) -> RuntimeResult<Flow<'a>> {
+        let stmts = match body.kind() {
+            StmtKind::Block(stmts) => stmts.as_slice(),
+            _ => std::slice::from_ref(body)
+        };
+        let value = self.expr(condition)?;
+        let mut start = None;
+        for (i, stmt) in stmts.iter().enumerate() {
+            if let StmtKind::Case(expr) = stmt.kind() && self.expr(expr)?.equals(&value) {
+                start = Some(i);
+                break;
+            }
+        }
+
+        let start = start.or_else(|| stmts.iter().position(|stmt| matches!(stmt.kind(), StmtKind::DefaultCase)));
+        let Some(start) = start
+        else {
+            return Ok(Flow::Normal)
+        };
+        self.switchon_from(stmts, start)
+    }
+
+    fn switchon_from(&mut self, stmts: &'a [Stmt], start: usize) -> RuntimeResult<Flow<'a>> {
+        self.scopes.push(Scope::new());
+        let flow = self.block(stmts, start);
+        self.scopes.pop();
+
+        match flow? {
+            Flow::Break => Ok(Flow::Normal),
+            flow => Ok(flow)
+        }
+    }
+
+    fn match_stmt(&mut self, loc: &Location, args: &'a [Expr], branches: &'a [(Vec<Located<Pattern>>, Box<Stmt>)], every: bool) -> RuntimeResult<Flow<'a>> {
+        let values = args.iter().map(|arg| self.expr(arg)).collect::<RuntimeResult<Vec<_>>>()?;
+        let mut matched = false;
+
+        for (patterns, body) in branches {
+            self.scopes.push(Scope::new());
+            let flow = if self.patterns(patterns, &values)? {
+                matched = true;
+                Some(self.stmt(body))
+            }
+            else {
+                None
+            };
+            self.scopes.pop();
+
+            match flow.transpose()? {
+                None | Some(Flow::Next) => continue,
+                Some(Flow::Normal) if every => continue,
+                Some(Flow::Normal | Flow::Break) => return Ok(Flow::Normal),
+                Some(flow) => return Ok(flow)
+            }
+        }
+
+        if matched || every {
+            Ok(Flow::Normal)
+        }
+        else {
+            Err(RuntimeError::MatchFailure.with_location(loc.clone()))
+        }
+    }
+
+    fn match_expr(&mut self, loc: &Location, args: &'a [Expr], branches: &'a [(Vec<Located<Pattern>>, Box<Expr>)], every: bool) -> RuntimeResult<Value<'a>> {
+        let values = args.iter().map(|arg| self.expr(arg)).collect::<RuntimeResult<Vec<_>>>()?;
+        let mut result = None;
+
+        for (patterns, body) in branches {
+            self.scopes.push(Scope::new());
+            if self.patterns(patterns, &values)? {
+                result = Some(self.expr(body));
+            }
+            self.scopes.pop();
+
+            if !every && result.is_some() {
+                break;
+            }
+        }
+
+        match result {
+            Some(result) => result,
+            None if every => Ok(Value::default()),
+            None => Err(RuntimeError::MatchFailure.with_location(loc.clone()))
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr) -> RuntimeResult<Value<'a>> {
+        let loc = expr.location();
+        let error = |err: RuntimeError| err.with_location(loc.clone());
+
+        match expr.kind() {
+            ExprKind::Ident(ident) => self.ident(ident).ok_or_else(|| error(RuntimeError::UndefinedIdent(ident.clone()))),
+            ExprKind::Atom(atom) => Ok(Value::Atom(*atom)),
+            ExprKind::IntLit(value) => Ok(Value::Int(*value as i64)),
+            ExprKind::FloatLit(value) => Ok(Value::Float(*value)),
+            ExprKind::CharLit(ch) => Ok(Value::Char(*ch)),
+            ExprKind::StringLit(value) => Ok(Value::String(value.as_str().into())),
+            ExprKind::True => Ok(Value::Bool(true)),
+            ExprKind::False => Ok(Value::Bool(false)),
+
+            ExprKind::Add(lhs, rhs) | ExprKind::Sub(lhs, rhs) | ExprKind::Mul(lhs, rhs)
+                | ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs)
+                | ExprKind::XOr(lhs, rhs) | ExprKind::LShift(lhs, rhs) | ExprKind::RShift(lhs, rhs) => {
+                let lhs = self.expr(lhs)?;
+                let rhs = self.expr(rhs)?;
+                self.operation(expr, lhs, rhs)
+            }
+            ExprKind::Abs(inner) => match self.expr(inner)? {
+                Value::Float(value) => Ok(Value::Float(value.abs())),
+                value => value.as_int()
+                    .map(|value| self.narrow(Value::Int(value.wrapping_abs()), expr.typ()))
+                    .ok_or_else(|| error(RuntimeError::InvalidOperands("`abs`")))
+            }
+            ExprKind::Neg(inner) => match self.expr(inner)? {
+                Value::Float(value) => Ok(Value::Float(-value)),
+                value => value.as_int()
+                    .map(|value| self.narrow(Value::Int(value.wrapping_neg()), expr.typ()))
+                    .ok_or_else(|| error(RuntimeError::InvalidOperands("`-`")))
+            }
+
+            ExprKind::Not(inner) => match self.expr(inner)? {
+                Value::Bool(value) => Ok(Value::Bool(!value)),
+                value => value.as_int()
+                    .map(|value| self.narrow(Value::Int(!value), expr.typ()))
+                    .ok_or_else(|| error(RuntimeError::InvalidOperands("`not`")))
+            }
+            ExprKind::And(lhs, rhs) => match self.expr(lhs)? {
+                Value::Bool(false) => Ok(Value::Bool(false)),
+                Value::Bool(true) => Ok(Value::Bool(self.expr(rhs)?.is_truthy())),
+                lhs => {
+                    let rhs = self.expr(rhs)?;
+                    self.operation(expr, lhs, rhs)
+                }
+            }
+            ExprKind::Or(lhs, rhs) => match self.expr(lhs)? {
+                Value::Bool(true) => Ok(Value::Bool(true)),
+                Value::Bool(false) => Ok(Value::Bool(self.expr(rhs)?.is_truthy())),
+                lhs => {
+                    let rhs = self.expr(rhs)?;
+                    self.operation(expr, lhs, rhs)
+                }
+            }
+
+            ExprKind::Eq(lhs, rhs) => {
+                let lhs = self.expr(lhs)?;
+                Ok(Value::Bool(lhs.equals(&self.expr(rhs)?)))
+            }
+            ExprKind::Ne(lhs, rhs) => {
+                let lhs = self.expr(lhs)?;
+                Ok(Value::Bool(!lhs.equals(&self.expr(rhs)?)))
+            }
+            ExprKind::Gt(lhs, rhs) => self.comparison(lhs, rhs, Ordering::is_gt),
+            ExprKind::Ge(lhs, rhs) => self.comparison(lhs, rhs, Ordering::is_ge),
+            ExprKind::Lt(lhs, rhs) => self.comparison(lhs, rhs, Ordering::is_lt),
+            ExprKind::Le(lhs, rhs) => self.comparison(lhs, rhs, Ordering::is_le),
+
+            ExprKind::Ref(inner) => self.address_of(inner).map(Value::Pointer),
+            ExprKind::Deref(inner) => match self.expr(inner)? {
+                Value::Pointer(pointer) => pointer.load().ok_or_else(|| error(RuntimeError::InvalidDeref)),
+                array @ (Value::Vector(_) | Value::String(_)) => self.index(array, 0, loc),
+                _ => Err(error(RuntimeError::InvalidDeref))
+            }
+            ExprKind::Index(array, index) => {
+                let array = self.expr(array)?;
+                let index = self.expr(index)?.as_int().ok_or_else(|| error(RuntimeError::InvalidOperands("indexing")))?;
+                self.index(array, index, loc)
+            }
+            ExprKind::Vector(size) => {
+                let size = self.expr(size)?.as_int().unwrap_or_default().max(0) as usize;
+                Ok(Value::vector(vec![Value::default(); size + 1]))
+            }
+            ExprKind::Array(elems) => {
+                if let Some(table) = self.tables.get(&(expr as *const Expr)) {
+                    return Ok(table.clone())
+                }
+
+                let elems = elems.iter().map(|elem| self.expr(elem)).collect::<RuntimeResult<Vec<_>>>()?;
+                let table = Value::vector(elems);
+                self.tables.insert(expr, table.clone());
+                Ok(table)
+            }
+            ExprKind::Record(fields) => {
+                let values = fields.iter().map(|(_, value)| self.expr(value)).collect::<RuntimeResult<Vec<_>>>()?;
+                let idents = fields.iter().map(|(ident, _)| ident.clone()).collect();
+                Ok(Value::Record(idents, Rc::new(RefCell::new(values))))
+            }
+            ExprKind::Field(record, field) => self.expr(record)?.field(field)
+                .and_then(|pointer| pointer.load())
+                .ok_or_else(|| error(RuntimeError::InvalidOperands("field access"))),
+            ExprKind::Slice(array, from, to) => {
+                let array = self.expr(array)?;
+                let from = self.expr(from)?.as_int().unwrap_or_default().max(0) as usize;
+                let to = self.expr(to)?.as_int().unwrap_or_default().max(0) as usize;
+                match array {
+                    Value::Vector(vector) => {
+                        let vector = vector.borrow();
+                        let to = to.min(vector.len());
+                        Ok(Value::vector(vector.get(from..to).unwrap_or_default().to_vec()))
+                    }
+                    Value::String(string) => {
+                        let to = to.min(string.len());
+                        Ok(Value::String(string.get(from..to).unwrap_or_default().into()))
+                    }
+                    _ => Err(error(RuntimeError::InvalidOperands("slicing")))
+                }
+            }
+
+            ExprKind::Cast(inner) | ExprKind::ImplicitCast(inner) => {
+                let value = self.expr(inner)?;
+                Ok(self.cast(value, expr.typ()))
+            }
+            ExprKind::ValOf(body) => match self.label_scope(body)? {
+                Flow::ResultIs(value) => Ok(value),
+                _ => Ok(Value::default())
+            }
+            ExprKind::FuncCall(callee, args) => self.call(callee, args, loc),
+            // captured variables are copied into the closure
+            ExprKind::Lambda(lambda) => {
+                let captures = lambda.captures().iter()
+                    .map(|ident| self.lookup_local(ident).map(|cell| cell.borrow().clone()).unwrap_or_default())
+                    .collect();
+                Ok(Value::Closure(lambda, Rc::new(captures)))
+            }
+            ExprKind::Instance(..) => unreachable!("generic function left uninstantiated"),
+            ExprKind::Conditional(condition, if_branch, else_branch) => {
+                if self.expr(condition)?.is_truthy() {
+                    self.expr(if_branch)
+                }
+                else {
+                    self.expr(else_branch)
+                }
+            }
+            ExprKind::Match(args, branches) => self.match_expr(loc, args, branches, false),
+            ExprKind::Every(args, branches) => self.match_expr(loc, args, branches, true),
+        }
+    }
+
+    fn ident(&mut self, ident: &str) -> Option<Value<'a>> {
+        if let Some(local) = self.lookup_local(ident) {
+            Some(local.borrow().clone())
+        }
+        else if let Some(variable) = self.lookup_variable(ident) {
+            Some(variable.borrow().clone())
+        }
+        else if let Some(manifest) = self.program.find_decl::<ManifestDecl>(ident) {
+            self.expr(manifest.value()).ok()
+        }
+        else if let Some((_, tag, variant)) = self.program.types().find_variant(ident) && variant.fields().is_empty() {
+            Some(Value::Variant(tag, Rc::new(vec![])))
+        }
+        else if self.program.find_function(ident).is_some() || builtins::is_builtin(ident) {
+            Some(Value::Function(ident.to_string()))
+        }
+        else {
+            None
+        }
+    }
+
+    // applies the binary arithmetic or bitwise operator of `expr` to already evaluated operands
+    fn operation(&self, expr: &Expr, lhs: Value<'a>, rhs: Value<'a>) -> RuntimeResult<Value<'a>> {
+        let (op, result) = match expr.kind() {
+            ExprKind::Add(..) => ("`+`", arithmetic(lhs, rhs, i64::wrapping_add, |a, b| a + b)),
+            ExprKind::Sub(..) => ("`-`", arithmetic(lhs, rhs, i64::wrapping_sub, |a, b| a - b)),
+            ExprKind::Mul(..) => ("`*`", arithmetic(lhs, rhs, i64::wrapping_mul, |a, b| a * b)),
+            ExprKind::Div(..) => ("`/`", Some(self.division(expr, lhs, rhs, false)?)),
+            ExprKind::Mod(..) => ("`mod`", Some(self.division(expr, lhs, rhs, true)?)),
+            ExprKind::And(..) => ("`&`", bitwise(lhs, rhs, |a, b| a & b)),
+            ExprKind::Or(..) => ("`|`", bitwise(lhs, rhs, |a, b| a | b)),
+            ExprKind::XOr(..) => ("`xor`", bitwise(lhs, rhs, |a, b| a ^ b)),
+            ExprKind::LShift(..) => ("`<<`", bitwise(lhs, rhs, |a, b| (a as u64).wrapping_shl(b as u32) as i64)),
+            ExprKind::RShift(..) => ("`>>`", bitwise(lhs, rhs, |a, b| (a as u64).wrapping_shr(b as u32) as i64)),
+            _ => unreachable!("not a binary operation")
+        };
+        // results of sized integer arithmetic wrap around at their width
+        result
+            .map(|value| self.narrow(value, expr.typ()))
+            .ok_or_else(|| RuntimeError::InvalidOperands(op).with_location(expr.location().clone()))
+    }
+
+    fn division(&self, expr: &Expr, lhs: Value<'a>, rhs: Value<'a>, remainder: bool) -> RuntimeResult<Value<'a>> {
+        let loc = expr.location();
+        let op = if remainder { "`mod`" } else { "`/`" };
+
+        if let (Value::Float(_), _) | (_, Value::Float(_)) = (&lhs, &rhs) {
+            return match (lhs.as_float(), rhs.as_float()) {
+                (Some(a), Some(b)) if remainder => Ok(Value::Float(a % b)),
+                (Some(a), Some(b)) => Ok(Value::Float(a / b)),
+                _ => Err(RuntimeError::InvalidOperands(op).with_location(loc.clone()))
+            }
+        }
+
+        let (Some(a), Some(b)) = (lhs.as_int(), rhs.as_int())
+        else {
+            return Err(RuntimeError::InvalidOperands(op).with_location(loc.clone()))
+        };
+
+        if b == 0 {
+            return Err(RuntimeError::DivisionByZero.with_location(loc.clone()))
+        }
+
+        let result = match (self.is_unsigned(expr.typ()), remainder) {
+            (true, true) => ((a as u64) % (b as u64)) as i64,
+            (true, false) => ((a as u64) / (b as u64)) as i64,
+            (false, true) => a.wrapping_rem(b),
+            (false, false) => a.wrapping_div(b)
+        };
+        Ok(Value::Int(result))
+    }
+
+    fn comparison(&mut self, lhs: &'a Expr, rhs: &'a Expr, accept: fn(Ordering) -> bool) -> RuntimeResult<Value<'a>> {
+        let unsigned = self.is_unsigned(lhs.typ());
+        let loc = lhs.location();
+        let lhs = self.expr(lhs)?;
+        let rhs = self.expr(rhs)?;
+
+        // NaN is unordered, so all comparisons with it are false
+        if lhs.is_nan() || rhs.is_nan() {
+            return Ok(Value::Bool(false))
+        }
+        lhs.compare(&rhs, unsigned)
+            .map(|ordering| Value::Bool(accept(ordering)))
+            .ok_or_else(|| RuntimeError::InvalidOperands("comparison").with_location(loc.clone()))
+    }
+
+    fn index(&mut self, array: Value<'a>, index: i64, loc: &Location) -> RuntimeResult<Value<'a>> {
+        let out_of_bounds = |len: usize| RuntimeError::IndexOutOfBounds(index, len).with_location(loc.clone());
+        match array {
+            Value::Vector(vector) => {
+                let vector = vector.borrow();
+                usize::try_from(index).ok()
+                    .and_then(|index| vector.get(index).cloned())
+                    .ok_or_else(|| out_of_bounds(vector.len()))
+            }
+            Value::String(string) => match usize::try_from(index) {
+                // strings are NUL-terminated
+                Ok(index) if index == string.len() => Ok(Value::Char('\0')),
+                Ok(index) => string.as_bytes().get(index)
+                    .map(|byte| Value::Char(*byte as char))
+                    .ok_or_else(|| out_of_bounds(string.len())),
+                Err(_) => Err(out_of_bounds(string.len()))
+            }
+            Value::Pointer(Pointer::Element(vector, base)) => {
+                self.index(Value::Vector(vector), base as i64 + index, loc)
+            }
+            Value::Pointer(pointer @ Pointer::Variable(_)) if index == 0 => pointer.load().ok_or_else(|| out_of_bounds(1)),
+            Value::Pointer(Pointer::Variable(_)) => Err(out_of_bounds(1)),
+            _ => Err(RuntimeError::InvalidOperands("indexing").with_location(loc.clone()))
+        }
+    }
+
+    fn store(&self, pointer: &Pointer<'a>, value: Value<'a>, loc: &Location) -> RuntimeResult<()> {
+        if let Pointer::Element(vector, index) = pointer && *index >= vector.borrow().len() {
+            return Err(RuntimeError::IndexOutOfBounds(*index as i64, vector.borrow().len()).with_location(loc.clone()))
+        }
+
+        pointer.store(value);
+        Ok(())
+    }
+
+    fn address_of(&mut self, inner: &'a Expr) -> RuntimeResult<Pointer<'a>> {
+        match inner.kind() {
+            ExprKind::Ident(ident) if self.lookup_local(ident).is_some() => Ok(Pointer::Variable(self.lookup_local(ident).unwrap())),
+            ExprKind::Ident(ident) if self.lookup_variable(ident).is_some() => Ok(Pointer::Variable(self.lookup_variable(ident).unwrap())),
+            ExprKind::Deref(pointer) => match self.expr(pointer)? {
+                Value::Pointer(pointer) => Ok(pointer),
+                Value::Vector(vector) => Ok(Pointer::Element(vector, 0)),
+                _ => Err(RuntimeError::InvalidDeref.with_location(inner.location().clone()))
+            }
+            ExprKind::Index(array, index) => {
+                let array = self.expr(array)?;
+                let index = self.expr(index)?.as_int().unwrap_or_default().max(0) as usize;
+                match array {
+                    Value::Vector(vector) => Ok(Pointer::Element(vector, index)),
+                    Value::Pointer(Pointer::Element(vector, base)) => Ok(Pointer::Element(vector, base + index)),
+                    Value::Pointer(pointer @ Pointer::Variable(_)) if index == 0 => Ok(pointer),
+                    _ => Err(RuntimeError::InvalidOperands("indexing").with_location(inner.location().clone()))
+                }
+            }
+            ExprKind::Field(record, field) => self.expr(record)?.field(field)
+                .ok_or_else(|| RuntimeError::InvalidOperands("field access").with_location(inner.location().clone())),
+            _ => {
+                // rvalues are stored in a fresh variable to get an address
+                let value = self.expr(inner)?;
+                Ok(Pointer::Variable(Rc::new(RefCell::new(value))))
+            }
+        }
+    }
+
+    fn cast(&self, value: Value<'a>, target: &Option<TypeIndex>) -> Value<'a> {
+        let Some(target) = self.type_kind(target)
+        else {
+            return value
+        };
+
+        let int = match (&value, target) {
+            (_, TypeKind::Bool) => return Value::Bool(value.is_truthy()),
+            (Value::Float(_), kind) if kind.is_float() => return value,
+            (_, kind) if kind.is_float() => return value.as_float().map(Value::Float).unwrap_or(value),
+            (Value::Float(float), _) => *float as i64,
+            (_, _) => match value.as_int() {
+                Some(int) => int,
+                None => return value
+            }
+        };
+
+        match target {
+            TypeKind::Char => Value::Char(int as u8 as char),
+            TypeKind::Int8 => Value::Int(int as i8 as i64),
+            TypeKind::Int16 => Value::Int(int as i16 as i64),
+            TypeKind::Int32 => Value::Int(int as i32 as i64),
+            TypeKind::UInt8 => Value::Int(int as u8 as i64),
+            TypeKind::UInt16 => Value::Int(int as u16 as i64),
+            TypeKind::UInt32 => Value::Int(int as u32 as i64),
+            TypeKind::Int64 | TypeKind::UInt64 => Value::Int(int),
+            _ => value
+        }
+    }
+
+    // truncates or sign-extends integers if `typ` is an integer type narrower than a word, like the
+    // compiled backends do
+    fn narrow(&self, value: Value<'a>, typ: &Option<TypeIndex>) -> Value<'a> {
+        match (&value, self.type_kind(typ).and_then(TypeKind::narrow_width)) {
+            (Value::Int(_), Some(_)) => self.cast(value, typ),
+            _ => value
+        }
+    }
+
+    fn call(&mut self, callee: &'a Expr, args: &'a [Expr], loc: &Location) -> RuntimeResult<Value<'a>> {
+        let mut values = args.iter().map(|arg| self.expr(arg)).collect::<RuntimeResult<Vec<_>>>()?;
+
+        let ident = match callee.kind() {
+            ExprKind::Ident(ident) if self.lookup_local(ident).is_none() && self.lookup_variable(ident).is_none() => ident.clone(),
+            _ => match self.expr(callee)? {
+                Value::Function(ident) => ident,
+                Value::Closure(lambda, captures) => return self.call_lambda(lambda, &captures, values, loc),
+                _ => return Err(RuntimeError::NotCallable.with_location(callee.location().clone()))
+            }
+        };
+
+        if let Some((_, tag, _)) = self.program.types().find_variant(&ident) {
+            return Ok(Value::Variant(tag, Rc::new(values)))
+        }
+
+        if let Some(func) = self.program.find_function(&ident) {
+            // fill in default values of omitted parameters
+            for param in func.params().iter().skip(values.len()) {
+                let value = match param.default_value() {
+                    Some(default) => self.expr(default)?,
+                    None => Value::default()
+                };
+                values.push(value);
+            }
+
+            return self.call_function(func, values, loc)
+        }
+
+        if builtins::is_builtin(&ident) {
+            return builtins::call(&ident, &values).map_err(|err| err.with_location(loc.clone()))
+        }
+
+        Err(RuntimeError::UndefinedIdent(ident).with_location(callee.location().clone()))
+    }
+}
+
+fn arithmetic<'a>(lhs: Value<'a>, rhs: Value<'a>, int_op: fn(i64, i64) -> i64, float_op: fn(f64, f64) -> f64) -> Option<Value<'a>> {
+    match (&lhs, &rhs) {
+        (Value::Float(_), _) | (_, Value::Float(_)) => Some(Value::Float(float_op(lhs.as_float()?, rhs.as_float()?))),
+        _ => Some(Value::Int(int_op(lhs.as_int()?, rhs.as_int()?)))
+    }
+}
+
+fn bitwise<'a>(lhs: Value<'a>, rhs: Value<'a>, op: fn(i64, i64) -> i64) -> Option<Value<'a>> {
+    match (lhs, rhs) {
+        // compound assignments like `&:=` combine booleans without short-circuiting
+        (Value::Bool(a), Value::Bool(b)) => Some(Value::Bool(op(a as i64, b as i64) != 0)),
+        (lhs, rhs) => Some(Value::Int(op(lhs.as_int()?, rhs.as_int()?)))
+    }
+}
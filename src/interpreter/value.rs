@@ -0,0 +1,146 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use crate::ast::expr::{AtomIndex, Lambda};
+
+pub type Vector<'a> = Rc<RefCell<Vec<Value<'a>>>>;
+
+#[derive(Clone, Debug)]
+pub enum Value<'a> {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Atom(AtomIndex),
+    String(Rc<str>),
+    Variant(u32, Rc<Vec<Value<'a>>>), // tag, fields
+    Vector(Vector<'a>),
+    Record(Rc<[String]>, Vector<'a>), // field names, field values
+    Pointer(Pointer<'a>),
+    Function(String),
+    Closure(&'a Lambda, Rc<Vec<Value<'a>>>) // lambda, values of the captured variables
+}
+
+#[derive(Clone, Debug)]
+pub enum Pointer<'a> {
+    Variable(Rc<RefCell<Value<'a>>>),
+    Element(Vector<'a>, usize)
+}
+
+impl<'a> Pointer<'a> {
+    pub fn load(&self) -> Option<Value<'a>> {
+        match self {
+            Self::Variable(cell) => Some(cell.borrow().clone()),
+            Self::Element(vector, index) => vector.borrow().get(*index).cloned()
+        }
+    }
+
+    // stores outside of the bounds of vectors are ignored, like out-of-bounds loads
+    pub fn store(&self, value: Value<'a>) {
+        match self {
+            Self::Variable(cell) => *cell.borrow_mut() = value,
+            Self::Element(vector, index) => if let Some(elem) = vector.borrow_mut().get_mut(*index) {
+                *elem = value
+            }
+        }
+    }
+
+    fn same_address(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Variable(a), Self::Variable(b)) => Rc::ptr_eq(a, b),
+            (Self::Element(a, i), Self::Element(b, j)) => Rc::ptr_eq(a, b) && i == j,
+            _ => false
+        }
+    }
+}
+
+impl Default for Value<'_> {
+    fn default() -> Self {
+        Self::Int(0)
+    }
+}
+
+impl<'a> Value<'a> {
+    pub fn vector(elems: Vec<Value<'a>>) -> Self {
+        Self::Vector(Rc::new(RefCell::new(elems)))
+    }
+
+    // interprets the value as a machine word, like the compiled backends do
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            Self::Bool(value) => Some(*value as i64),
+            Self::Char(value) => Some(*value as i64),
+            Self::Atom(value) => Some(*value as i64),
+            Self::Float(value) => Some(value.to_bits() as i64),
+            _ => None
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Float(value) => Some(*value),
+            Self::Int(value) => Some(*value as f64),
+            Self::Char(value) => Some(*value as u32 as f64),
+            _ => None
+        }
+    }
+
+    pub fn is_nan(&self) -> bool {
+        matches!(self, Self::Float(value) if value.is_nan())
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Bool(value) => *value,
+            Self::Int(value) => *value != 0,
+            Self::Char(value) => *value != '\0',
+            Self::Float(value) => *value != 0.0,
+            _ => true
+        }
+    }
+
+    // pointer to the field `ident` of a record
+    pub fn field(&self, ident: &str) -> Option<Pointer<'a>> {
+        match self {
+            Self::Record(fields, values) => fields.iter()
+                .position(|field| field == ident)
+                .map(|index| Pointer::Element(values.clone(), index)),
+            _ => None
+        }
+    }
+
+    // strips all pointers the value is wrapped in
+    pub fn strip_pointers(&self) -> Value<'a> {
+        match self {
+            Self::Pointer(pointer) => pointer.load().map(|value| value.strip_pointers()).unwrap_or_default(),
+            _ => self.clone()
+        }
+    }
+
+    pub fn equals(&self, other: &Value<'a>) -> bool {
+        match (self, other) {
+            (Self::Float(a), b) | (b, Self::Float(a)) => b.as_float().map(|b| *a == b).unwrap_or(false),
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::Variant(tag_a, fields_a), Self::Variant(tag_b, fields_b)) => tag_a == tag_b
+                && fields_a.len() == fields_b.len()
+                && fields_a.iter().zip(fields_b.iter()).all(|(a, b)| a.equals(b)),
+            (Self::Vector(a), Self::Vector(b)) | (Self::Record(_, a), Self::Record(_, b)) => Rc::ptr_eq(a, b),
+            (Self::Pointer(a), Self::Pointer(b)) => a.same_address(b),
+            (Self::Function(a), Self::Function(b)) => a == b,
+            (Self::Closure(_, a), Self::Closure(_, b)) => Rc::ptr_eq(a, b),
+            (a, b) => match (a.as_int(), b.as_int()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false
+            }
+        }
+    }
+
+    pub fn compare(&self, other: &Value<'a>, unsigned: bool) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Float(_), _) | (_, Self::Float(_)) => self.as_float()?.partial_cmp(&other.as_float()?),
+            (Self::String(a), Self::String(b)) => Some(a.cmp(b)),
+            _ if unsigned => Some((self.as_int()? as u64).cmp(&(other.as_int()? as u64))),
+            _ => Some(self.as_int()?.cmp(&other.as_int()?))
+        }
+    }
+}
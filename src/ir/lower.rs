@@ -0,0 +1,1418 @@
+mod matching;
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    ast::{
+        Program, Decl, FunctionBody, BasicFunctionBody, GlobalDecl, ManifestDecl, StaticDecl,
+        expr::{Expr, ExprKind, Lambda},
+        stmt::{Stmt, StmtKind},
+        pattern::{Pattern, PatternTerm},
+        types::{TypeIndex, TypeKind},
+        self
+    },
+    source_file::{Located, Location, SourceFile, SourceFileId}
+};
+
+use super::{
+    BasicBlock, BinaryOp, BlockId, Callee, Condition, Function,
+    Instr, Module, Operand, Terminator, UnaryOp, Var, Width
+};
+
+use self::matching::Column;
+
+// targets of `break` and `next` statements
+struct JumpTarget {
+    break_block: BlockId,
+    next_block: Option<BlockId>
+}
+
+// storage an assignment writes to
+enum Place {
+    Var(Var),
+    //     base     index    (scaled by the width)
+    Memory(Operand, Operand, Width)
+}
+
+#[derive(Default)]
+struct FunctionBuilder {
+    num_vars: u32,
+    vectors: Vec<i64>,
+    blocks: Vec<(Vec<Instr>, Option<Terminator>)>,
+    current: BlockId
+}
+
+impl FunctionBuilder {
+    fn new(num_params: u32) -> Self {
+        Self {
+            num_vars: num_params,
+            vectors: vec![],
+            blocks: vec![(vec![], None)],
+            current: 0
+        }
+    }
+
+    fn finish(self, ident: String, num_params: u32) -> Function {
+        Function {
+            ident,
+            local: false,
+            num_params,
+            num_vars: self.num_vars,
+            vectors: self.vectors,
+            blocks: self.blocks.into_iter()
+                .map(|(instrs, terminator)| BasicBlock { instrs, terminator: terminator.unwrap_or_default() })
+                .collect()
+        }
+    }
+}
+
+pub(super) struct Lowerer<'a> {
+    program: &'a Program,
+    source_files: &'a HashMap<SourceFileId, SourceFile>,
+
+    strings: Vec<String>,
+    string_indices: HashMap<String, usize>,
+    tables: Vec<(Width, Vec<Operand>)>,
+    externs: BTreeSet<String>,
+    // functions lowered from lambdas
+    lambdas: Vec<Function>,
+    num_lambdas: usize,
+    // tables holding the closures of named functions used as values
+    function_closures: HashMap<String, usize>,
+
+    // per-function state
+    builder: FunctionBuilder,
+    scopes: Vec<HashMap<String, Var>>,
+    valofs: Vec<(Var, BlockId)>,
+    jumps: Vec<JumpTarget>,
+    cases: Vec<Vec<BlockId>>,
+    // blocks of the labels of each function, lambda and `valof` body currently lowered
+    labels: Vec<HashMap<String, BlockId>>
+}
+
+impl<'a> Lowerer<'a> {
+    pub(super) fn new(program: &'a Program, source_files: &'a HashMap<SourceFileId, SourceFile>) -> Self {
+        Self {
+            program,
+            source_files,
+            strings: vec![],
+            string_indices: HashMap::new(),
+            tables: vec![],
+            externs: BTreeSet::new(),
+            lambdas: vec![],
+            num_lambdas: 0,
+            function_closures: HashMap::new(),
+            builder: FunctionBuilder::default(),
+            scopes: vec![],
+            valofs: vec![],
+            jumps: vec![],
+            cases: vec![],
+            labels: vec![]
+        }
+    }
+
+    pub(super) fn lower(mut self) -> Module {
+        let sections = self.program.section_order().into_iter()
+            .map(|ident| &self.program.sections()[&ident])
+            .collect::<Vec<_>>();
+
+        let decls = sections.iter()
+            .flat_map(|section| {
+                let mut decls = section.declarations().values().collect::<Vec<_>>();
+                decls.sort_by_key(|decl| decl.ident());
+                decls
+            })
+            .collect::<Vec<_>>();
+
+        // generic functions are only called through their instances
+        let mut functions = decls.iter()
+            .filter_map(|decl| decl.as_any().downcast_ref::<ast::Function>())
+            .filter(|func| !func.is_generic())
+            .map(|func| self.function(func))
+            .collect::<Vec<_>>();
+        functions.append(&mut self.lambdas);
+
+        let statics = decls.iter()
+            .filter_map(|decl| decl.as_any().downcast_ref::<StaticDecl>())
+            .map(|static_decl| match self.expr(static_decl.value()) {
+                value @ (Operand::Const(_) | Operand::Table(_)) => (static_decl.ident().clone(), value),
+                _ => unreachable!("static initializers are folded into literals")
+            })
+            .collect();
+
+        let externs = self.externs.into_iter()
+            .filter(|ext| functions.iter().all(|func| func.ident() != ext))
+            .collect();
+
+        Module {
+            functions,
+            strings: self.strings,
+            tables: self.tables,
+            statics,
+            externs
+        }
+    }
+
+    fn new_var(&mut self) -> Var {
+        self.builder.num_vars += 1;
+        self.builder.num_vars - 1
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.builder.blocks.push((vec![], None));
+        self.builder.blocks.len() - 1
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.builder.current = block;
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.builder.blocks[self.builder.current].1.is_some()
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        // code following a jump is unreachable, but still needs a block to live in
+        if self.is_terminated() {
+            let dead = self.new_block();
+            self.switch_to(dead);
+        }
+        self.builder.blocks[self.builder.current].0.push(instr);
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        if !self.is_terminated() {
+            self.builder.blocks[self.builder.current].1 = Some(terminator);
+        }
+    }
+
+    fn jump(&mut self, block: BlockId) {
+        self.terminate(Terminator::Jump(block));
+    }
+
+    // continues in a new block if `condition` holds, jumps to `fail` otherwise
+    fn expect(&mut self, condition: Operand, fail: BlockId) {
+        let success = self.new_block();
+        self.terminate(Terminator::Branch(condition, success, fail));
+        self.switch_to(success);
+    }
+
+    fn string(&mut self, value: &str) -> Operand {
+        let index = match self.string_indices.get(value) {
+            Some(index) => *index,
+            None => {
+                self.strings.push(value.to_string());
+                self.string_indices.insert(value.to_string(), self.strings.len() - 1);
+                self.strings.len() - 1
+            }
+        };
+        Operand::String(index)
+    }
+
+    fn location_string(&self, loc: &Location) -> String {
+        let file = self.source_files.get(&loc.file_id())
+            .map(|file| file.path().as_str())
+            .unwrap_or("<unknown>");
+        format!("{file}:{}:{}", loc.line(), loc.column())
+    }
+
+    fn match_failure(&mut self, loc: &Location) {
+        let loc = self.location_string(loc);
+        let loc = self.string(&loc);
+        let unused = self.new_var();
+        self.emit(Instr::Call(unused, Callee::Runtime("bcplrt_match_failure"), vec![loc]));
+        self.terminate(Terminator::Unreachable);
+    }
+
+    fn lookup_local(&self, ident: &str) -> Option<Var> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident)).copied()
+    }
+
+    fn declare_local(&mut self, ident: &str) -> Var {
+        let var = self.new_var();
+        self.scopes.last_mut()
+            .expect("no scope to declare local in")
+            .insert(ident.to_string(), var);
+        self.emit(Instr::Copy(var, Operand::Const(0)));
+        var
+    }
+
+    fn type_kind(&self, typ: &Option<TypeIndex>) -> Option<&'a TypeKind> {
+        typ.and_then(|typ| self.program.types().resolve(typ))
+    }
+
+    fn is_float(&self, typ: &Option<TypeIndex>) -> bool {
+        self.type_kind(typ).map(TypeKind::is_float).unwrap_or(false)
+    }
+
+    fn is_unsigned(&self, typ: &Option<TypeIndex>) -> bool {
+        self.type_kind(typ).map(TypeKind::is_unsigned).unwrap_or(false)
+    }
+
+    fn is_bool(&self, typ: &Option<TypeIndex>) -> bool {
+        matches!(self.type_kind(typ), Some(TypeKind::Bool))
+    }
+
+    // element type of pointers, slices and arrays
+    fn element_type(&self, typ: &Option<TypeIndex>) -> Option<TypeIndex> {
+        match self.type_kind(typ)? {
+            TypeKind::Pointer(inner) | TypeKind::Slice(inner) | TypeKind::Array(inner, _) => Some(*inner),
+            _ => None
+        }
+    }
+
+    // width used to load and store an element of type `typ` from memory
+    fn memory_width(&self, typ: &Option<TypeIndex>) -> Width {
+        match self.type_kind(typ) {
+            Some(TypeKind::Char | TypeKind::UInt8) => Width::Byte,
+            Some(TypeKind::Int8) => Width::SignedByte,
+            _ => Width::Word
+        }
+    }
+
+    fn function(&mut self, func: &ast::Function) -> Function {
+        let num_params = func.params().len() as u32;
+        self.builder = FunctionBuilder::new(num_params);
+        self.scopes.push(HashMap::new());
+        self.labels.push(HashMap::new());
+
+        let fail = self.new_block();
+        for (i, param) in func.params().iter().enumerate() {
+            self.bind_pattern(param.pattern(), Operand::Var(i as Var), param.typ(), fail);
+        }
+
+        match func.body() {
+            FunctionBody::Expr(expr) => {
+                let value = self.expr(expr);
+                self.terminate(Terminator::Return(value));
+            }
+            FunctionBody::Stmt(stmt) => {
+                self.stmt(stmt);
+                self.terminate(Terminator::Return(Operand::Const(0)));
+            }
+            FunctionBody::PatternMatchedExpr(branches) => {
+                let patterns = branches.iter().map(|(patterns, _)| patterns.as_slice()).collect::<Vec<_>>();
+                self.function_branches(func, &patterns, fail, |lowerer, i| {
+                    let value = lowerer.expr(&branches[i].1);
+                    lowerer.terminate(Terminator::Return(value));
+                });
+            }
+            FunctionBody::PatternMatchedStmt(branches) => {
+                let patterns = branches.iter().map(|(patterns, _)| patterns.as_slice()).collect::<Vec<_>>();
+                self.function_branches(func, &patterns, fail, |lowerer, i| {
+                    lowerer.stmt(&branches[i].1);
+                    lowerer.terminate(Terminator::Return(Operand::Const(0)));
+                });
+            }
+        }
+
+        self.switch_to(fail);
+        self.match_failure(func.location());
+
+        self.scopes.pop();
+        self.labels.pop();
+        std::mem::take(&mut self.builder).finish(func.ident().clone(), num_params)
+    }
+
+    // pattern-matched function bodies dispatch on the parameters like a `match`
+    fn function_branches(&mut self, func: &ast::Function, patterns: &[&[Located<Pattern>]], fail: BlockId, mut body: impl FnMut(&mut Self, usize)) {
+        let columns = func.params().iter()
+            .enumerate()
+            .map(|(i, param)| Column::new(Operand::Var(i as Var), *param.typ()))
+            .collect::<Vec<_>>();
+        let arms = self.match_arms(patterns);
+        self.match_dispatch(&columns, patterns, &arms, false, fail, fail);
+
+        for (i, arm) in arms.into_iter().enumerate() {
+            self.switch_to(arm.block);
+            self.scopes.push(arm.scope);
+            body(self, i);
+            self.scopes.pop();
+        }
+    }
+
+    // declares all variables bound by `pattern` and tests `value` against it, jumping to `fail` on mismatch
+    fn bind_pattern(&mut self, pattern: &Pattern, value: Operand, typ: &Option<TypeIndex>, fail: BlockId) {
+        let mut bindings = vec![];
+        self.pattern_bindings(pattern, &mut bindings);
+        for ident in bindings {
+            if self.scopes.last().unwrap().get(&ident).is_none() {
+                self.declare_local(&ident);
+            }
+        }
+
+        self.pattern(pattern, value, typ, fail);
+    }
+
+    fn is_nullary_variant(&self, ident: &str) -> bool {
+        self.lookup_local(ident).is_none() && self.program.types().find_variant(ident)
+            .map(|(_, _, variant)| variant.fields().is_empty())
+            .unwrap_or(false)
+    }
+
+    fn pattern_bindings(&self, pattern: &Pattern, bindings: &mut Vec<String>) {
+        match pattern {
+            Pattern::Query(ident) if ident != "_" && !self.is_nullary_variant(ident) => {
+                if !bindings.contains(ident) {
+                    bindings.push(ident.clone())
+                }
+            }
+            Pattern::Or(lhs, rhs) | Pattern::And(lhs, rhs) => {
+                self.pattern_bindings(lhs, bindings);
+                self.pattern_bindings(rhs, bindings);
+            }
+            Pattern::Variant(_, args) | Pattern::List(args) => args.iter().for_each(|arg| self.pattern_bindings(arg, bindings)),
+            Pattern::Record(fields) => fields.iter().for_each(|(_, field)| self.pattern_bindings(field, bindings)),
+            _ => ()
+        }
+    }
+
+    fn load(&mut self, base: Operand, index: Operand, width: Width) -> Operand {
+        let var = self.new_var();
+        self.emit(Instr::Load(var, base, index, width));
+        Operand::Var(var)
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: Operand, rhs: Operand) -> Operand {
+        let var = self.new_var();
+        self.emit(Instr::Binary(var, op, lhs, rhs));
+        Operand::Var(var)
+    }
+
+    fn unary(&mut self, op: UnaryOp, value: Operand) -> Operand {
+        let var = self.new_var();
+        self.emit(Instr::Unary(var, op, value));
+        Operand::Var(var)
+    }
+
+    // copies `value` into a fresh variable, so later code can't change it
+    fn spill(&mut self, value: Operand) -> Operand {
+        let var = self.new_var();
+        self.emit(Instr::Copy(var, value));
+        Operand::Var(var)
+    }
+
+    // sum types and lists are matched through pointers
+    fn strip_pointers(&mut self, value: Operand, typ: &Option<TypeIndex>) -> (Operand, Option<TypeIndex>) {
+        match self.type_kind(typ) {
+            Some(TypeKind::Pointer(inner)) => {
+                let inner = Some(*inner);
+                let value = self.load(value, Operand::Const(0), Width::Word);
+                self.strip_pointers(value, &inner)
+            }
+            _ => (value, *typ)
+        }
+    }
+
+    fn expect_tag(&mut self, value: Operand, tag: u32, fail: BlockId) {
+        let value_tag = self.load(value, Operand::Const(0), Width::Word);
+        let matches = self.binary(BinaryOp::Cmp(Condition::Eq), value_tag, Operand::Const(tag as i64));
+        self.expect(matches, fail);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, value: Operand, typ: &Option<TypeIndex>, fail: BlockId) {
+        match pattern {
+            Pattern::Any | Pattern::Remaining => (),
+            Pattern::Query(ident) if ident == "_" => (),
+            Pattern::Query(ident) if self.is_nullary_variant(ident) => {
+                let (tag, _) = self.variant_tag(ident);
+                let (value, _) = self.strip_pointers(value, typ);
+                self.expect_tag(value, tag, fail);
+            }
+            Pattern::Query(ident) => {
+                let local = self.lookup_local(ident).expect("undeclared pattern binding");
+                self.emit(Instr::Copy(local, value));
+            }
+            Pattern::Term(term) => {
+                let condition = self.pattern_term(term, value, typ);
+                self.expect(condition, fail);
+            }
+            Pattern::And(lhs, rhs) => {
+                self.pattern(lhs, value.clone(), typ, fail);
+                self.pattern(rhs, value, typ, fail);
+            }
+            Pattern::Or(lhs, rhs) => {
+                let try_rhs = self.new_block();
+                let matched = self.new_block();
+                self.pattern(lhs, value.clone(), typ, try_rhs);
+                self.jump(matched);
+                self.switch_to(try_rhs);
+                self.pattern(rhs, value, typ, fail);
+                self.jump(matched);
+                self.switch_to(matched);
+            }
+            Pattern::Variant(ident, args) => {
+                let (value, typ) = self.strip_pointers(value, typ);
+                let (tag, fields) = self.variant_of(&typ, ident);
+                self.expect_tag(value.clone(), tag, fail);
+                for (i, arg) in args.iter().enumerate() {
+                    let field = self.load(value.clone(), Operand::Const(i as i64 + 1), Width::Word);
+                    self.pattern(arg, field, &fields.get(i).copied(), fail);
+                }
+            }
+            Pattern::Record(fields) => {
+                for (ident, field) in fields {
+                    let (_, _, field_typ) = self.record_field(typ, ident);
+                    let field_value = self.load_field(value.clone(), typ, ident);
+                    self.pattern(field, field_value, &Some(field_typ), fail);
+                }
+            }
+            Pattern::List(elems) => {
+                let (value, typ) = self.strip_pointers(value, typ);
+                let elem_typ = self.element_type(&typ);
+                let width = self.memory_width(&elem_typ);
+                let len = self.load(value.clone(), Operand::Const(-1), Width::Word);
+
+                let remaining = elems.iter().position(|elem| matches!(**elem, Pattern::Remaining));
+                let required = elems.len() - remaining.map(|_| 1).unwrap_or(0);
+                let condition = if remaining.is_some() { Condition::Ge } else { Condition::Eq };
+                let matches = self.binary(BinaryOp::Cmp(condition), len.clone(), Operand::Const(required as i64));
+                self.expect(matches, fail);
+
+                for (i, elem) in elems.iter().enumerate() {
+                    if matches!(**elem, Pattern::Any | Pattern::Remaining) {
+                        continue;
+                    }
+
+                    let index = match remaining {
+                        Some(pos) if i > pos => self.binary(BinaryOp::Sub, len.clone(), Operand::Const((elems.len() - i) as i64)),
+                        _ => Operand::Const(i as i64)
+                    };
+                    let elem_value = self.load(value.clone(), index, width);
+                    self.pattern(elem, elem_value, &elem_typ, fail);
+                }
+            }
+        }
+    }
+
+    fn pattern_term(&mut self, term: &PatternTerm, value: Operand, typ: &Option<TypeIndex>) -> Operand {
+        let (op, expr) = match term {
+            PatternTerm::Range(from, to) => {
+                let typ = typ.or(*from.typ());
+                let (from, to) = (self.expr(from), self.expr(to));
+                let lower = self.comparison(value.clone(), ">=", from, &typ);
+                let upper = self.comparison(value, "<=", to, &typ);
+                return self.binary(BinaryOp::And, lower, upper)
+            }
+            PatternTerm::Basic(expr) | PatternTerm::Eq(expr) => ("==", expr),
+            PatternTerm::Ne(expr) => ("!=", expr),
+            PatternTerm::Lt(expr) => ("<", expr),
+            PatternTerm::Le(expr) => ("<=", expr),
+            PatternTerm::Gt(expr) => (">", expr),
+            PatternTerm::Ge(expr) => (">=", expr),
+        };
+
+        let typ = typ.or(*expr.typ());
+        let expr = self.expr(expr);
+        self.comparison(value, op, expr, &typ)
+    }
+
+    fn comparison(&mut self, lhs: Operand, op: &str, rhs: Operand, typ: &Option<TypeIndex>) -> Operand {
+        let condition = Condition::from_operator(op, self.is_float(typ), self.is_unsigned(typ));
+        self.binary(BinaryOp::Cmp(condition), lhs, rhs)
+    }
+
+    fn variant_tag(&self, ident: &str) -> (u32, Vec<TypeIndex>) {
+        self.program.types().find_variant(ident)
+            .map(|(_, tag, variant)| (tag, variant.fields().clone()))
+            .unwrap_or_default()
+    }
+
+    // like `variant_tag`, with the field types of instances of generic sum types
+    fn variant_of(&self, typ: &Option<TypeIndex>, ident: &str) -> (u32, Vec<TypeIndex>) {
+        self.program.types().find_variant_of(*typ, ident)
+            .map(|(_, tag, variant)| (tag, variant.fields().clone()))
+            .unwrap_or_default()
+    }
+
+    // byte offset, width and type of the field `ident` of a record of type `typ`
+    fn record_field(&self, typ: &Option<TypeIndex>, ident: &str) -> (i64, Width, TypeIndex) {
+        let (field_typ, offset) = typ.and_then(|typ| self.program.types().find_field(typ, ident))
+            .expect("field of a non-record");
+        (offset as i64, self.memory_width(&Some(field_typ)), field_typ)
+    }
+
+    // fields are aligned to their width, so they can be loaded by index
+    fn load_field(&mut self, record: Operand, typ: &Option<TypeIndex>, ident: &str) -> Operand {
+        let (offset, width, _) = self.record_field(typ, ident);
+        self.load(record, Operand::Const(offset / width.size()), width)
+    }
+
+    fn construct_variant(&mut self, ident: &str, args: Vec<Operand>) -> Operand {
+        let (tag, _) = self.variant_tag(ident);
+        let block = self.new_var();
+        self.emit(Instr::Call(block, Callee::Runtime("bcplrt_alloc"), vec![Operand::Const(args.len() as i64 + 1)]));
+        self.emit(Instr::Store(Operand::Var(block), Operand::Const(0), Operand::Const(tag as i64), Width::Word));
+        for (i, arg) in args.into_iter().enumerate() {
+            self.emit(Instr::Store(Operand::Var(block), Operand::Const(i as i64 + 1), arg, Width::Word));
+        }
+        Operand::Var(block)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind() {
+            StmtKind::Nop => (),
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+            }
+            StmtKind::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                stmts.iter().for_each(|stmt| self.stmt(stmt));
+                self.scopes.pop();
+            }
+            StmtKind::ResultIs(expr) => {
+                let value = self.expr(expr);
+                let (result, end) = *self.valofs.last().expect("`resultis` outside of `valof`");
+                self.emit(Instr::Copy(result, value));
+                self.jump(end);
+            }
+            StmtKind::Return => self.terminate(Terminator::Return(Operand::Const(0))),
+            StmtKind::If(condition, if_branch, else_branch) => {
+                let condition = self.expr(condition);
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                let end = if else_branch.is_some() { self.new_block() } else { else_block };
+
+                self.terminate(Terminator::Branch(condition, then_block, else_block));
+                self.switch_to(then_block);
+                self.stmt(if_branch);
+                self.jump(end);
+
+                if let Some(else_branch) = else_branch {
+                    self.switch_to(else_block);
+                    self.stmt(else_branch);
+                    self.jump(end);
+                }
+                self.switch_to(end);
+            }
+            StmtKind::Unless(condition, body) => {
+                let condition = self.expr(condition);
+                let body_block = self.new_block();
+                let end = self.new_block();
+
+                self.terminate(Terminator::Branch(condition, end, body_block));
+                self.switch_to(body_block);
+                self.stmt(body);
+                self.jump(end);
+                self.switch_to(end);
+            }
+            StmtKind::While(condition, body) => self.conditional_loop(condition, body, false),
+            StmtKind::Until(condition, body) => self.conditional_loop(condition, body, true),
+            StmtKind::Loop(body) => self.repeat_loop(body, None, false),
+            StmtKind::RepeatWhile(body, condition) => self.repeat_loop(body, Some(condition), false),
+            StmtKind::RepeatUntil(body, condition) => self.repeat_loop(body, Some(condition), true),
+            StmtKind::For(iter, init, limit, step, body) => self.for_loop(iter, init, limit, step, body),
+            StmtKind::SwitchOn(condition, body) => self.switchon(condition, body),
+            StmtKind::Case(_) | StmtKind::DefaultCase => {
+                // cases are dispatched by `switchon`, control falls through from the previous case
+                let cases = self.cases.last_mut().expect("`case` outside of `switchon`");
+                let block = cases.remove(0);
+                self.jump(block);
+                self.switch_to(block);
+            }
+            StmtKind::Break => {
+                let target = self.jumps.last().expect("`break` outside of breakable statement").break_block;
+                self.jump(target);
+            }
+            StmtKind::Next => {
+                let target = self.jumps.iter().rev()
+                    .find_map(|target| target.next_block)
+                    .expect("`next` outside of loop or match");
+                self.jump(target);
+            }
+            StmtKind::Label(label, body) => {
+                let block = self.label_block(label);
+                self.jump(block);
+                self.switch_to(block);
+                self.stmt(body);
+            }
+            StmtKind::Goto(label) => {
+                let block = self.label_block(label);
+                self.jump(block);
+            }
+            StmtKind::Finish => {
+                let unused = self.new_var();
+                self.emit(Instr::Call(unused, Callee::Runtime("bcplrt_finish"), vec![]));
+                self.terminate(Terminator::Unreachable);
+            }
+            StmtKind::Match(args, branches) => {
+                let branches = branches.iter().map(|(patterns, stmt)| (patterns, stmt.as_ref())).collect::<Vec<_>>();
+                self.match_stmt(stmt.location(), args, &branches, false);
+            }
+            StmtKind::Every(args, branches) => {
+                let branches = branches.iter().map(|(patterns, stmt)| (patterns, stmt.as_ref())).collect::<Vec<_>>();
+                self.match_stmt(stmt.location(), args, &branches, true);
+            }
+            StmtKind::Binding(bindings) => {
+                let values = bindings.iter()
+                    .map(|(_, expr)| {
+                        let value = self.expr(expr);
+                        (self.spill(value), *expr.typ())
+                    })
+                    .collect::<Vec<_>>();
+
+                let fail = self.new_block();
+                let done = self.new_block();
+                for ((pattern, _), (value, typ)) in bindings.iter().zip(values) {
+                    self.bind_pattern(pattern, value, &typ, fail);
+                }
+                self.jump(done);
+                self.switch_to(fail);
+                self.match_failure(stmt.location());
+                self.switch_to(done);
+            }
+            StmtKind::Assign(targets, values) => {
+                // all targets and values are evaluated before the first store
+                let places = targets.iter().map(|target| self.place(target)).collect::<Vec<_>>();
+                let values = values.iter()
+                    .map(|value| {
+                        let value = self.expr(value);
+                        if targets.len() > 1 { self.spill(value) } else { value }
+                    })
+                    .collect::<Vec<_>>();
+
+                for ((place, value), target) in places.into_iter().zip(values).zip(targets) {
+                    let value = self.narrow(value, target.typ());
+                    self.store(place, value);
+                }
+            }
+            StmtKind::CompoundAssign(operation) => {
+                let (target, value) = operation.binary_operands().expect("compound assignment without operator");
+                let place = self.place(target);
+                let current = match &place {
+                    Place::Var(var) => Operand::Var(*var),
+                    Place::Memory(base, index, width) => self.load(base.clone(), index.clone(), *width)
+                };
+                let current = if value.has_sideeffect() { self.spill(current) } else { current };
+                let value = self.expr(value);
+
+                let result = self.operation(operation, current, value);
+                let result = self.narrow(result, target.typ());
+                self.store(place, result);
+            }
+        }
+    }
+
+    fn place(&mut self, target: &Expr) -> Place {
+        let (base, index, width) = match target.kind() {
+            ExprKind::Ident(ident) if self.lookup_local(ident).is_some() => return Place::Var(self.lookup_local(ident).unwrap()),
+            ExprKind::Ident(ident) => {
+                let address = self.variable_address(ident).expect("assignment to a non-variable");
+                (address, Operand::Const(0), Width::Word)
+            }
+            ExprKind::Deref(pointer) => {
+                let width = self.memory_width(&self.element_type(pointer.typ()));
+                (self.expr(pointer), Operand::Const(0), width)
+            }
+            ExprKind::Index(array, index) => {
+                let width = self.memory_width(&self.element_type(array.typ()));
+                let (array, index) = self.operands(array, index);
+                (array, index, width)
+            }
+            ExprKind::Field(record, field) => {
+                let (offset, width, _) = self.record_field(record.typ(), field);
+                (self.expr(record), Operand::Const(offset / width.size()), width)
+            }
+            _ => unreachable!("assignment to a non-lvalue")
+        };
+
+        // later stores of the same assignment must not change where this one goes
+        let protect = |lowerer: &mut Self, operand: Operand| match operand {
+            Operand::Var(_) => lowerer.spill(operand),
+            _ => operand
+        };
+        let base = protect(self, base);
+        let index = protect(self, index);
+        Place::Memory(base, index, width)
+    }
+
+    fn store(&mut self, place: Place, value: Operand) {
+        match place {
+            Place::Var(var) => self.emit(Instr::Copy(var, value)),
+            Place::Memory(base, index, width) => self.emit(Instr::Store(base, index, value, width))
+        }
+    }
+
+    fn conditional_loop(&mut self, condition: &Expr, body: &Stmt, negate: bool) {
+        let next_block = self.new_block();
+        let body_block = self.new_block();
+        let break_block = self.new_block();
+
+        self.jump(next_block);
+        self.switch_to(next_block);
+        let condition = self.expr(condition);
+        let (on_true, on_false) = if negate { (break_block, body_block) } else { (body_block, break_block) };
+        self.terminate(Terminator::Branch(condition, on_true, on_false));
+
+        self.switch_to(body_block);
+        self.jumps.push(JumpTarget { break_block, next_block: Some(next_block) });
+        self.stmt(body);
+        self.jumps.pop();
+
+        self.jump(next_block);
+        self.switch_to(break_block);
+    }
+
+    // labels can be jumped to before they are defined
+    fn label_block(&mut self, label: &str) -> BlockId {
+        if let Some(block) = self.labels.last().and_then(|labels| labels.get(label)) {
+            return *block
+        }
+
+        let block = self.new_block();
+        self.labels.last_mut().expect("label outside of function").insert(label.to_string(), block);
+        block
+    }
+
+    // runs `body` before checking `condition`, or forever without one
+    fn repeat_loop(&mut self, body: &Stmt, condition: Option<&Expr>, negate: bool) {
+        let body_block = self.new_block();
+        let next_block = self.new_block();
+        let break_block = self.new_block();
+
+        self.jump(body_block);
+        self.switch_to(body_block);
+        self.jumps.push(JumpTarget { break_block, next_block: Some(next_block) });
+        self.stmt(body);
+        self.jumps.pop();
+        self.jump(next_block);
+
+        self.switch_to(next_block);
+        match condition {
+            Some(condition) => {
+                let condition = self.expr(condition);
+                let (on_true, on_false) = if negate { (break_block, body_block) } else { (body_block, break_block) };
+                self.terminate(Terminator::Branch(condition, on_true, on_false));
+            }
+            None => self.jump(body_block)
+        }
+
+        self.switch_to(break_block);
+    }
+
+    fn for_loop(&mut self, iter: &Located<Pattern>, init: &Expr, limit: &Option<Box<Expr>>, step: &Option<Box<Expr>>, body: &Stmt) {
+        let loop_block = self.new_block();
+        let body_block = self.new_block();
+        let next_block = self.new_block();
+        let break_block = self.new_block();
+
+        self.scopes.push(HashMap::new());
+
+        let init = self.expr(init);
+        let iterator = match &**iter {
+            Pattern::Query(ident) => self.declare_local(ident),
+            _ => self.declare_local("_")
+        };
+        self.emit(Instr::Copy(iterator, init));
+
+        let limit = limit.as_ref().map(|limit| {
+            let value = self.expr(limit);
+            self.spill(value)
+        });
+
+        let (step, constant_step) = match step.as_deref() {
+            Some(step) => {
+                let constant = match step.kind() {
+                    ExprKind::IntLit(value) => Some(*value as i64),
+                    _ => None
+                };
+                let value = self.expr(step);
+                (self.spill(value), constant)
+            }
+            None => (Operand::Const(1), Some(1))
+        };
+
+        self.jump(loop_block);
+        self.switch_to(loop_block);
+        if let Some(limit) = limit {
+            let condition = match constant_step {
+                Some(step) if step >= 0 => self.binary(BinaryOp::Cmp(Condition::Le), Operand::Var(iterator), limit),
+                Some(_) => self.binary(BinaryOp::Cmp(Condition::Ge), Operand::Var(iterator), limit),
+                None => {
+                    // the direction of the loop is only known at runtime
+                    let upwards = self.binary(BinaryOp::Cmp(Condition::Ge), step.clone(), Operand::Const(0));
+                    let below = self.binary(BinaryOp::Cmp(Condition::Le), Operand::Var(iterator), limit.clone());
+                    let above = self.binary(BinaryOp::Cmp(Condition::Ge), Operand::Var(iterator), limit);
+                    let downwards = self.binary(BinaryOp::Xor, upwards.clone(), Operand::Const(1));
+                    let up_ok = self.binary(BinaryOp::And, upwards, below);
+                    let down_ok = self.binary(BinaryOp::And, downwards, above);
+                    self.binary(BinaryOp::Or, up_ok, down_ok)
+                }
+            };
+            self.terminate(Terminator::Branch(condition, body_block, break_block));
+        }
+        else {
+            self.jump(body_block);
+        }
+
+        self.switch_to(body_block);
+        self.jumps.push(JumpTarget { break_block, next_block: Some(next_block) });
+        self.stmt(body);
+        self.jumps.pop();
+        self.jump(next_block);
+
+        self.switch_to(next_block);
+        self.emit(Instr::Binary(iterator, BinaryOp::Add, Operand::Var(iterator), step));
+        self.jump(loop_block);
+
+        self.scopes.pop();
+        self.switch_to(break_block);
+    }
+
+    fn collect_cases<'s>(stmt: &'s Stmt, cases: &mut Vec<Option<&'s Expr>>) {
+        match stmt.kind() {
+            StmtKind::Case(expr) => cases.push(Some(expr)),
+            StmtKind::DefaultCase => cases.push(None),
+            StmtKind::Block(stmts) => stmts.iter().for_each(|stmt| Self::collect_cases(stmt, cases)),
+            StmtKind::If(_, if_branch, else_branch) => {
+                Self::collect_cases(if_branch, cases);
+                if let Some(else_branch) = else_branch {
+                    Self::collect_cases(else_branch, cases);
+                }
+            }
+            StmtKind::Unless(_, body) | StmtKind::While(_, body)
+                | StmtKind::Until(_, body) | StmtKind::For(.., body) | StmtKind::Loop(body)
+                | StmtKind::RepeatWhile(body, _) | StmtKind::RepeatUntil(body, _)
+                | StmtKind::Label(_, body) => Self::collect_cases(body, cases),
+            _ => ()
+        }
+    }
+
+    fn switchon(&mut self, condition: &Expr, body: &Stmt) {
+        let typ = *condition.typ();
+        let condition = self.expr(condition);
+        let value = self.spill(condition);
+        let break_block = self.new_block();
+
+        let mut cases = vec![];
+        Self::collect_cases(body, &mut cases);
+        let blocks = cases.iter().map(|_| self.new_block()).collect::<Vec<_>>();
+
+        // dispatch to the matching case in order of appearance
+        let mut default = break_block;
+        for (case, block) in cases.iter().zip(&blocks) {
+            match case {
+                Some(expr) => {
+                    let expected = self.expr(expr);
+                    let matches = self.comparison(value.clone(), "==", expected, &typ);
+                    let next = self.new_block();
+                    self.terminate(Terminator::Branch(matches, *block, next));
+                    self.switch_to(next);
+                }
+                None => default = *block
+            }
+        }
+        self.jump(default);
+
+        // code before the first case is unreachable
+        let dead = self.new_block();
+        self.switch_to(dead);
+
+        self.cases.push(blocks);
+        self.jumps.push(JumpTarget { break_block, next_block: None });
+        self.stmt(body);
+        self.jumps.pop();
+        self.cases.pop();
+
+        self.jump(break_block);
+        self.switch_to(break_block);
+    }
+
+    fn match_scrutinees(&mut self, args: &[Expr]) -> Vec<Column> {
+        args.iter()
+            .map(|arg| {
+                let value = self.expr(arg);
+                Column::new(self.spill(value), *arg.typ())
+            })
+            .collect()
+    }
+
+    fn match_stmt(&mut self, loc: &Location, args: &[Expr], branches: &[(&Vec<Located<Pattern>>, &Stmt)], every: bool) {
+        let scrutinees = self.match_scrutinees(args);
+        let end = self.new_block();
+        let fail = self.new_block();
+
+        let patterns = branches.iter().map(|(patterns, _)| patterns.as_slice()).collect::<Vec<_>>();
+        let arms = self.match_arms(&patterns);
+        let nexts = self.match_dispatch(&scrutinees, &patterns, &arms, every, end, fail);
+
+        for ((arm, (_, body)), next) in arms.iter().zip(branches).zip(&nexts) {
+            self.switch_to(arm.block);
+            self.scopes.push(arm.scope.clone());
+
+            self.jumps.push(JumpTarget { break_block: end, next_block: Some(*next) });
+            self.stmt(body);
+            self.jumps.pop();
+
+            self.jump(if every { *next } else { end });
+            self.scopes.pop();
+        }
+
+        if !every {
+            // `next` continues matching with the following branches
+            self.match_resume(&scrutinees, &patterns, &arms, &nexts, fail);
+        }
+
+        self.switch_to(fail);
+        self.match_failure(loc);
+        self.switch_to(end);
+    }
+
+    fn match_expr(&mut self, loc: &Location, args: &[Expr], branches: &[(Vec<Located<Pattern>>, Box<Expr>)], every: bool) -> Operand {
+        let scrutinees = self.match_scrutinees(args);
+        let result = self.new_var();
+        let end = self.new_block();
+        let fail = self.new_block();
+        self.emit(Instr::Copy(result, Operand::Const(0)));
+
+        let patterns = branches.iter().map(|(patterns, _)| patterns.as_slice()).collect::<Vec<_>>();
+        let arms = self.match_arms(&patterns);
+        let nexts = self.match_dispatch(&scrutinees, &patterns, &arms, every, end, fail);
+
+        for ((arm, (_, body)), next) in arms.iter().zip(branches).zip(&nexts) {
+            self.switch_to(arm.block);
+            self.scopes.push(arm.scope.clone());
+
+            let value = self.expr(body);
+            self.emit(Instr::Copy(result, value));
+            self.jump(if every { *next } else { end });
+
+            self.scopes.pop();
+        }
+
+        self.switch_to(fail);
+        self.match_failure(loc);
+        self.switch_to(end);
+        Operand::Var(result)
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Operand {
+        match expr.kind() {
+            ExprKind::Ident(ident) => self.ident(ident),
+            ExprKind::Atom(atom) => Operand::Const(*atom as i64),
+            ExprKind::IntLit(value) => Operand::Const(*value as i64),
+            ExprKind::FloatLit(value) => Operand::Const(value.to_bits() as i64),
+            ExprKind::CharLit(ch) => Operand::Const(*ch as i64),
+            ExprKind::StringLit(value) => self.string(value),
+            ExprKind::True => Operand::Const(1),
+            ExprKind::False => Operand::Const(0),
+
+            ExprKind::Add(lhs, rhs) | ExprKind::Sub(lhs, rhs) | ExprKind::Mul(lhs, rhs)
+                | ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs) => {
+                let (lhs, rhs) = self.operands(lhs, rhs);
+                self.operation(expr, lhs, rhs)
+            }
+            ExprKind::Abs(inner) => {
+                let value = self.expr(inner);
+                let op = if self.is_float(expr.typ()) { UnaryOp::FAbs } else { UnaryOp::Abs };
+                let value = self.unary(op, value);
+                self.narrow(value, expr.typ())
+            }
+            ExprKind::Neg(inner) => {
+                let value = self.expr(inner);
+                let op = if self.is_float(expr.typ()) { UnaryOp::FNeg } else { UnaryOp::Neg };
+                let value = self.unary(op, value);
+                self.narrow(value, expr.typ())
+            }
+
+            ExprKind::Not(inner) => {
+                let value = self.expr(inner);
+                if self.is_bool(inner.typ()) {
+                    self.binary(BinaryOp::Cmp(Condition::Eq), value, Operand::Const(0))
+                }
+                else {
+                    let value = self.unary(UnaryOp::Not, value);
+                    self.narrow(value, expr.typ())
+                }
+            }
+            ExprKind::And(lhs, rhs) if self.is_bool(lhs.typ()) => self.short_circuit(lhs, rhs, true),
+            ExprKind::Or(lhs, rhs) if self.is_bool(lhs.typ()) => self.short_circuit(lhs, rhs, false),
+            ExprKind::And(lhs, rhs) | ExprKind::Or(lhs, rhs) | ExprKind::XOr(lhs, rhs) => {
+                let (lhs, rhs) = self.operands(lhs, rhs);
+                self.operation(expr, lhs, rhs)
+            }
+
+            ExprKind::Eq(lhs, rhs) => self.compare(lhs, rhs, "=="),
+            ExprKind::Ne(lhs, rhs) => self.compare(lhs, rhs, "!="),
+            ExprKind::Gt(lhs, rhs) => self.compare(lhs, rhs, ">"),
+            ExprKind::Ge(lhs, rhs) => self.compare(lhs, rhs, ">="),
+            ExprKind::Lt(lhs, rhs) => self.compare(lhs, rhs, "<"),
+            ExprKind::Le(lhs, rhs) => self.compare(lhs, rhs, "<="),
+
+            ExprKind::LShift(lhs, rhs) | ExprKind::RShift(lhs, rhs) => {
+                let (lhs, rhs) = self.operands(lhs, rhs);
+                self.operation(expr, lhs, rhs)
+            }
+
+            ExprKind::Ref(inner) => self.reference(inner),
+            ExprKind::Deref(inner) => {
+                let width = self.memory_width(&self.element_type(inner.typ()));
+                let pointer = self.expr(inner);
+                self.load(pointer, Operand::Const(0), width)
+            }
+            ExprKind::Index(array, index) => {
+                let width = self.memory_width(&self.element_type(array.typ()));
+                let array = self.expr(array);
+                let index = self.expr(index);
+                self.load(array, index, width)
+            }
+            ExprKind::Vector(size) => match size.kind() {
+                // `vec n` has the elements 0 to n
+                ExprKind::IntLit(size) => {
+                    self.builder.vectors.push(*size as i64 + 1);
+                    Operand::Vector(self.builder.vectors.len() - 1)
+                }
+                _ => unreachable!("vector sizes are folded into literals")
+            }
+            ExprKind::Array(elems) => {
+                let width = self.memory_width(&self.element_type(expr.typ()));
+                let elems = elems.iter().map(|elem| self.expr(elem)).collect();
+                self.tables.push((width, elems));
+                Operand::Table(self.tables.len() - 1)
+            }
+            // records are allocated on the heap, like variants
+            ExprKind::Record(fields) => {
+                let size = expr.typ().map(|typ| self.program.types().size_of(typ)).unwrap_or(0);
+                let record = self.new_var();
+                self.emit(Instr::Call(record, Callee::Runtime("bcplrt_alloc"), vec![Operand::Const(size as i64 / Width::Word.size())]));
+                for (ident, value) in fields {
+                    let value = self.expr(value);
+                    let (offset, width, _) = self.record_field(expr.typ(), ident);
+                    self.emit(Instr::Store(Operand::Var(record), Operand::Const(offset / width.size()), value, width));
+                }
+                Operand::Var(record)
+            }
+            ExprKind::Field(record, field) => {
+                let value = self.expr(record);
+                self.load_field(value, record.typ(), field)
+            }
+            ExprKind::Slice(array, from, to) => {
+                let args = vec![self.expr(array), self.expr(from), self.expr(to)];
+                let slice = self.new_var();
+                self.emit(Instr::Call(slice, Callee::Runtime("bcplrt_slice"), args));
+                Operand::Var(slice)
+            }
+
+            ExprKind::Cast(inner) | ExprKind::ImplicitCast(inner) => self.cast(inner, expr.typ()),
+            ExprKind::ValOf(body) => {
+                let result = self.new_var();
+                let end = self.new_block();
+                self.emit(Instr::Copy(result, Operand::Const(0)));
+
+                self.valofs.push((result, end));
+                self.labels.push(HashMap::new());
+                self.stmt(body);
+                self.labels.pop();
+                self.valofs.pop();
+
+                self.jump(end);
+                self.switch_to(end);
+                Operand::Var(result)
+            }
+            ExprKind::FuncCall(callee, args) => self.call(callee, args),
+            ExprKind::Lambda(lambda) => self.lambda(lambda, expr.location()),
+            ExprKind::Instance(..) => unreachable!("generic function left uninstantiated"),
+            ExprKind::Conditional(condition, if_branch, else_branch) => {
+                let condition = self.expr(condition);
+                let result = self.new_var();
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                let end = self.new_block();
+                self.terminate(Terminator::Branch(condition, then_block, else_block));
+
+                self.switch_to(then_block);
+                let value = self.expr(if_branch);
+                self.emit(Instr::Copy(result, value));
+                self.jump(end);
+
+                self.switch_to(else_block);
+                let value = self.expr(else_branch);
+                self.emit(Instr::Copy(result, value));
+                self.jump(end);
+
+                self.switch_to(end);
+                Operand::Var(result)
+            }
+            ExprKind::Match(args, branches) => self.match_expr(expr.location(), args, branches, false),
+            ExprKind::Every(args, branches) => self.match_expr(expr.location(), args, branches, true),
+        }
+    }
+
+    fn ident(&mut self, ident: &str) -> Operand {
+        if let Some(local) = self.lookup_local(ident) {
+            Operand::Var(local)
+        }
+        else if let Some(address) = self.variable_address(ident) {
+            self.load(address, Operand::Const(0), Width::Word)
+        }
+        else if let Some(manifest) = self.program.find_decl::<ManifestDecl>(ident) {
+            self.expr(manifest.value())
+        }
+        else if self.is_nullary_variant(ident) {
+            self.construct_variant(ident, vec![])
+        }
+        else {
+            self.externs.insert(ident.to_string());
+            self.function_closure(ident)
+        }
+    }
+
+    // function values are closures; named functions capture nothing, so all their uses share one
+    fn function_closure(&mut self, ident: &str) -> Operand {
+        let index = match self.function_closures.get(ident) {
+            Some(index) => *index,
+            None => {
+                self.tables.push((Width::Word, vec![Operand::Function(ident.to_string())]));
+                self.function_closures.insert(ident.to_string(), self.tables.len() - 1);
+                self.tables.len() - 1
+            }
+        };
+        Operand::Table(index)
+    }
+
+    // Lambdas become functions taking their closure as an extra last parameter.
+    // Closures hold the function followed by the values of the captured variables.
+    fn lambda(&mut self, lambda: &Lambda, loc: &Location) -> Operand {
+        let captures = lambda.captures().iter()
+            .map(|ident| self.ident(ident))
+            .collect::<Vec<_>>();
+
+        let ident = format!("lambda.{}", self.num_lambdas);
+        self.num_lambdas += 1;
+
+        let num_params = lambda.params().len() as u32 + 1;
+        let builder = std::mem::replace(&mut self.builder, FunctionBuilder::new(num_params));
+        let scopes = std::mem::take(&mut self.scopes);
+        let valofs = std::mem::take(&mut self.valofs);
+        let jumps = std::mem::take(&mut self.jumps);
+        let cases = std::mem::take(&mut self.cases);
+        self.labels.push(HashMap::new());
+
+        self.scopes.push(HashMap::new());
+        let closure = Operand::Var(num_params - 1);
+        for (i, capture) in lambda.captures().iter().enumerate() {
+            let local = self.declare_local(capture);
+            self.emit(Instr::Load(local, closure.clone(), Operand::Const(i as i64 + 1), Width::Word));
+        }
+
+        self.scopes.push(HashMap::new());
+        let fail = self.new_block();
+        for (i, param) in lambda.params().iter().enumerate() {
+            self.bind_pattern(param.pattern(), Operand::Var(i as Var), param.typ(), fail);
+        }
+
+        match lambda.body() {
+            BasicFunctionBody::Expr(expr) => {
+                let value = self.expr(expr);
+                self.terminate(Terminator::Return(value));
+            }
+            BasicFunctionBody::Stmt(stmt) => {
+                self.stmt(stmt);
+                self.terminate(Terminator::Return(Operand::Const(0)));
+            }
+        }
+
+        self.switch_to(fail);
+        self.match_failure(loc);
+
+        let mut function = std::mem::replace(&mut self.builder, builder).finish(ident.clone(), num_params);
+        function.local = true;
+        self.lambdas.push(function);
+        self.scopes = scopes;
+        self.valofs = valofs;
+        self.jumps = jumps;
+        self.cases = cases;
+        self.labels.pop();
+
+        if captures.is_empty() {
+            self.tables.push((Width::Word, vec![Operand::Function(ident)]));
+            return Operand::Table(self.tables.len() - 1)
+        }
+
+        let block = self.new_var();
+        self.emit(Instr::Call(block, Callee::Runtime("bcplrt_alloc"), vec![Operand::Const(captures.len() as i64 + 1)]));
+        self.emit(Instr::Store(Operand::Var(block), Operand::Const(0), Operand::Function(ident), Width::Word));
+        for (i, value) in captures.into_iter().enumerate() {
+            self.emit(Instr::Store(Operand::Var(block), Operand::Const(i as i64 + 1), value, Width::Word));
+        }
+        Operand::Var(block)
+    }
+
+    // address of a static or global variable
+    fn variable_address(&self, ident: &str) -> Option<Operand> {
+        if let Some(static_decl) = self.program.find_decl::<StaticDecl>(ident) {
+            return Some(Operand::Static(static_decl.ident().clone()))
+        }
+
+        let global = self.program.find_decl::<GlobalDecl>(ident)?;
+        match global.index().kind() {
+            ExprKind::IntLit(index) => Some(Operand::Global(*index as i64)),
+            _ => unreachable!("global numbers are folded into literals")
+        }
+    }
+
+    // evaluates both operands, protecting the left one from being changed by the right one
+    fn operands(&mut self, lhs: &Expr, rhs: &Expr) -> (Operand, Operand) {
+        let lhs = self.expr(lhs);
+        let lhs = match lhs {
+            Operand::Var(_) if rhs.has_sideeffect() => self.spill(lhs),
+            _ => lhs
+        };
+        (lhs, self.expr(rhs))
+    }
+
+    // applies the binary arithmetic or bitwise operator of `expr` to already evaluated operands
+    fn operation(&mut self, expr: &Expr, lhs: Operand, rhs: Operand) -> Operand {
+        let float = self.is_float(expr.typ());
+        let unsigned = self.is_unsigned(expr.typ());
+        let op = match expr.kind() {
+            ExprKind::Add(..) if float => BinaryOp::FAdd,
+            ExprKind::Add(..) => BinaryOp::Add,
+            ExprKind::Sub(..) if float => BinaryOp::FSub,
+            ExprKind::Sub(..) => BinaryOp::Sub,
+            ExprKind::Mul(..) if float => BinaryOp::FMul,
+            ExprKind::Mul(..) => BinaryOp::Mul,
+            ExprKind::Div(..) if float => BinaryOp::FDiv,
+            ExprKind::Div(..) if unsigned => BinaryOp::UDiv,
+            ExprKind::Div(..) => BinaryOp::Div,
+            ExprKind::Mod(..) if float => BinaryOp::FRem,
+            ExprKind::Mod(..) if unsigned => BinaryOp::URem,
+            ExprKind::Mod(..) => BinaryOp::Rem,
+            ExprKind::And(..) => BinaryOp::And,
+            ExprKind::Or(..) => BinaryOp::Or,
+            ExprKind::XOr(..) => BinaryOp::Xor,
+            ExprKind::LShift(..) => BinaryOp::Shl,
+            ExprKind::RShift(..) => BinaryOp::Shr,
+            _ => unreachable!("not a binary operation")
+        };
+        // results of sized integer arithmetic wrap around at their width
+        let result = self.binary(op, lhs, rhs);
+        self.narrow(result, expr.typ())
+    }
+
+    fn compare(&mut self, lhs: &Expr, rhs: &Expr, op: &str) -> Operand {
+        let typ = *lhs.typ();
+        let (lhs, rhs) = self.operands(lhs, rhs);
+        self.comparison(lhs, op, rhs, &typ)
+    }
+
+    fn short_circuit(&mut self, lhs: &Expr, rhs: &Expr, is_and: bool) -> Operand {
+        let lhs = self.expr(lhs);
+        let result = self.new_var();
+        self.emit(Instr::Binary(result, BinaryOp::Cmp(Condition::Ne), lhs, Operand::Const(0)));
+
+        let rhs_block = self.new_block();
+        let end = self.new_block();
+        let (on_true, on_false) = if is_and { (rhs_block, end) } else { (end, rhs_block) };
+        self.terminate(Terminator::Branch(Operand::Var(result), on_true, on_false));
+
+        self.switch_to(rhs_block);
+        let rhs = self.expr(rhs);
+        self.emit(Instr::Binary(result, BinaryOp::Cmp(Condition::Ne), rhs, Operand::Const(0)));
+        self.jump(end);
+
+        self.switch_to(end);
+        Operand::Var(result)
+    }
+
+    fn reference(&mut self, inner: &Expr) -> Operand {
+        let var = match inner.kind() {
+            ExprKind::Ident(ident) if self.lookup_local(ident).is_some() => self.lookup_local(ident).unwrap(),
+            ExprKind::Ident(ident) if self.variable_address(ident).is_some() => return self.variable_address(ident).unwrap(),
+            ExprKind::Deref(pointer) => return self.expr(pointer),
+            ExprKind::Index(array, index) => {
+                let size = self.memory_width(&self.element_type(array.typ())).size();
+                let (array, index) = self.operands(array, index);
+                let offset = self.binary(BinaryOp::Mul, index, Operand::Const(size));
+                return self.binary(BinaryOp::Add, array, offset)
+            }
+            ExprKind::Field(record, field) => {
+                let (offset, _, _) = self.record_field(record.typ(), field);
+                let record = self.expr(record);
+                return self.binary(BinaryOp::Add, record, Operand::Const(offset))
+            }
+            _ => {
+                // store rvalues in a fresh variable to get an address
+                let value = self.expr(inner);
+                let temp = self.new_var();
+                self.emit(Instr::Copy(temp, value));
+                temp
+            }
+        };
+
+        let address = self.new_var();
+        self.emit(Instr::AddrOf(address, var));
+        Operand::Var(address)
+    }
+
+    fn cast(&mut self, inner: &Expr, target: &Option<TypeIndex>) -> Operand {
+        let from_float = self.is_float(inner.typ());
+        let from_unsigned = self.is_unsigned(inner.typ());
+        let value = self.expr(inner);
+
+        let value = match (from_float, self.is_float(target)) {
+            (true, false) => self.unary(UnaryOp::FloatToInt, value),
+            (false, true) if from_unsigned => return self.unary(UnaryOp::UIntToFloat, value),
+            (false, true) => return self.unary(UnaryOp::IntToFloat, value),
+            _ => value
+        };
+
+        match self.type_kind(target) {
+            Some(TypeKind::Bool) => self.binary(BinaryOp::Cmp(Condition::Ne), value, Operand::Const(0)),
+            _ => self.narrow(value, target)
+        }
+    }
+
+    // truncates or sign-extends `value` if `typ` is an integer type narrower than a word
+    fn narrow(&mut self, value: Operand, typ: &Option<TypeIndex>) -> Operand {
+        match self.type_kind(typ).and_then(TypeKind::narrow_width) {
+            Some((bits, signed)) => self.unary(UnaryOp::Truncate { bits, signed }, value),
+            None => value
+        }
+    }
+
+    fn call(&mut self, callee: &Expr, args: &[Expr]) -> Operand {
+        let mut values = args.iter()
+            .map(|arg| {
+                let value = self.expr(arg);
+                self.spill(value)
+            })
+            .collect::<Vec<_>>();
+
+        let callee = match callee.kind() {
+            ExprKind::Ident(ident) if self.lookup_local(ident).is_none() && self.variable_address(ident).is_none() => {
+                if self.program.types().find_variant(ident).is_some() {
+                    return self.construct_variant(ident, values)
+                }
+
+                match self.program.find_function(ident) {
+                    Some(func) => {
+                        // fill in default values of omitted parameters
+                        for param in func.params().iter().skip(values.len()) {
+                            let value = param.default_value().as_ref()
+                                .map(|default| self.expr(default))
+                                .unwrap_or(Operand::Const(0));
+                            values.push(value);
+                        }
+                    }
+                    None => {
+                        self.externs.insert(ident.clone());
+                    }
+                }
+                Callee::Function(ident.clone())
+            }
+            // closures are passed to their function as an extra last argument
+            _ => {
+                let closure = self.expr(callee);
+                let closure = self.spill(closure);
+                let function = self.load(closure.clone(), Operand::Const(0), Width::Word);
+                values.push(closure);
+                Callee::Indirect(function)
+            }
+        };
+
+        let result = self.new_var();
+        self.emit(Instr::Call(result, callee, values));
+        Operand::Var(result)
+    }
+}
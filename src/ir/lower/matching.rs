@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        expr::{Expr, ExprKind},
+        pattern::{Pattern, PatternTerm},
+        types::{TypeIndex, TypeKind}
+    },
+    source_file::Located
+};
+
+use super::{BlockId, Instr, Lowerer, Operand, Terminator, Var, Width};
+
+// Matches are compiled into decision trees: each scrutinee (or part of it) is inspected at most once
+// on any path, dispatching on variant tags, constants and list lengths with a single `Switch`.
+// Records have a single shape, so their fields are inspected without a dispatch.
+// Patterns without such a structure (relational terms, lists with `..`) fall back to a sequential test.
+
+// a value being matched, together with its type
+#[derive(Clone)]
+pub(super) struct Column {
+    value: Operand,
+    typ: Option<TypeIndex>
+}
+
+impl Column {
+    pub(super) fn new(value: Operand, typ: Option<TypeIndex>) -> Self {
+        Self { value, typ }
+    }
+}
+
+// a branch of a match: the variables its patterns bind and the block its body starts in
+pub(super) struct Arm {
+    pub(super) scope: HashMap<String, Var>,
+    pub(super) block: BlockId
+}
+
+// the patterns of a branch still to be tested against the columns
+#[derive(Clone)]
+struct Row<'p> {
+    cells: Vec<Option<&'p Pattern>>, // `None` once the column is known to match
+    bindings: Vec<(Var, Operand)>,
+    arm: usize
+}
+
+impl<'p> Row<'p> {
+    fn new(arm: usize, patterns: &'p [Located<Pattern>], num_columns: usize) -> Self {
+        let mut cells = patterns.iter().map(|pattern| Some(&**pattern)).collect::<Vec<_>>();
+        cells.resize(num_columns, None);
+        Self { cells, bindings: vec![], arm }
+    }
+}
+
+// what the dispatch of a `Switch` is based on
+#[derive(Clone, Copy, PartialEq)]
+enum Ctor {
+    Tag(u32),
+    Value(i64),
+    Length(usize),
+    MinLength(usize) // lists with `..` match a range of lengths, so they are not dispatched on
+}
+
+impl Ctor {
+    fn key(&self) -> i64 {
+        match self {
+            Self::Tag(tag) => *tag as i64,
+            Self::Value(value) => *value,
+            Self::Length(len) => *len as i64,
+            Self::MinLength(_) => unreachable!("lists with `..` have no single length")
+        }
+    }
+}
+
+// relation of a pattern to a constructor
+enum Specialized<'p> {
+    Matches(Vec<Option<&'p Pattern>>), // matches if the sub-patterns match the constructor's fields
+    Mismatch,
+    Unknown // needs to be tested separately
+}
+
+fn constant(expr: &Expr) -> Option<i64> {
+    match expr.kind() {
+        ExprKind::IntLit(value) => Some(*value as i64),
+        ExprKind::CharLit(ch) => Some(*ch as i64),
+        ExprKind::Atom(atom) => Some(*atom as i64),
+        ExprKind::Neg(inner) => constant(inner).map(i64::wrapping_neg),
+        ExprKind::True => Some(1),
+        ExprKind::False => Some(0),
+        _ => None
+    }
+}
+
+// splits a list pattern into the elements before and after a `..`, if there is one
+fn split_list(elems: &[Located<Pattern>]) -> (&[Located<Pattern>], Option<&[Located<Pattern>]>) {
+    match elems.iter().position(|elem| matches!(**elem, Pattern::Remaining)) {
+        Some(pos) => (&elems[..pos], Some(&elems[pos + 1..])),
+        None => (elems, None)
+    }
+}
+
+impl<'a> Lowerer<'a> {
+    // declares the variables bound by each branch and allocates the blocks of their bodies
+    pub(super) fn match_arms(&mut self, branches: &[&[Located<Pattern>]]) -> Vec<Arm> {
+        branches.iter()
+            .map(|patterns| {
+                self.scopes.push(HashMap::new());
+                let mut bindings = vec![];
+                patterns.iter().for_each(|pattern| self.pattern_bindings(pattern, &mut bindings));
+                bindings.iter().for_each(|ident| { self.declare_local(ident); });
+                let scope = self.scopes.pop().unwrap();
+
+                Arm { scope, block: self.new_block() }
+            })
+            .collect()
+    }
+
+    // emits the tests selecting a branch of a `match` and returns the blocks `next` continues with.
+    // with `every`, each branch is tested in turn and its body continues with the next one.
+    pub(super) fn match_dispatch(
+        &mut self, columns: &[Column], branches: &[&[Located<Pattern>]], arms: &[Arm],
+        every: bool, end: BlockId, fail: BlockId
+    ) -> Vec<BlockId> {
+        if !every {
+            let rows = branches.iter().enumerate()
+                .map(|(i, patterns)| Row::new(i, patterns, columns.len()))
+                .collect();
+            self.decide(columns.to_vec(), rows, arms, fail);
+            return branches.iter().map(|_| self.new_block()).collect()
+        }
+
+        let nexts = branches.iter().enumerate()
+            .map(|(i, patterns)| {
+                let next = self.new_block();
+                self.decide(columns.to_vec(), vec![Row::new(i, patterns, columns.len())], arms, next);
+                self.switch_to(next);
+                next
+            })
+            .collect();
+        self.jump(end);
+        nexts
+    }
+
+    // continues matching with the branches after the one a `next` statement was executed in
+    pub(super) fn match_resume(
+        &mut self, columns: &[Column], branches: &[&[Located<Pattern>]], arms: &[Arm],
+        resumes: &[BlockId], fail: BlockId
+    ) {
+        for (i, resume) in resumes.iter().enumerate() {
+            if !self.is_jump_target(*resume) {
+                continue;
+            }
+
+            self.switch_to(*resume);
+            let rows = branches.iter().enumerate()
+                .skip(i + 1)
+                .map(|(i, patterns)| Row::new(i, patterns, columns.len()))
+                .collect();
+            self.decide(columns.to_vec(), rows, arms, fail);
+        }
+    }
+
+    fn is_jump_target(&self, block: BlockId) -> bool {
+        self.builder.blocks.iter().any(|(_, terminator)| match terminator {
+            Some(Terminator::Jump(target)) => *target == block,
+            Some(Terminator::Branch(_, on_true, on_false)) => *on_true == block || *on_false == block,
+            Some(Terminator::Switch(_, cases, default)) => *default == block || cases.iter().any(|(_, target)| *target == block),
+            _ => false
+        })
+    }
+
+    fn decide<'p>(&mut self, mut columns: Vec<Column>, mut rows: Vec<Row<'p>>, arms: &[Arm], fail: BlockId) {
+        self.normalize(&mut columns, &mut rows, arms);
+
+        let Some(first) = rows.first() else {
+            return self.jump(fail)
+        };
+
+        let Some(col) = first.cells.iter().position(Option::is_some) else {
+            // the first remaining branch matches
+            for (var, value) in first.bindings.clone() {
+                self.emit(Instr::Copy(var, value));
+            }
+            return self.jump(arms[first.arm].block)
+        };
+
+        if let Some(Pattern::Record(_)) = first.cells[col] {
+            return self.destructure(columns, rows, col, arms, fail)
+        }
+
+        match self.ctor(first.cells[col].unwrap(), &columns[col]) {
+            Some(Ctor::MinLength(_)) | None => self.test(columns, rows, col, arms, fail),
+            Some(_) => self.switch(columns, rows, col, arms, fail)
+        }
+    }
+
+    // expands or-patterns into separate rows, and-patterns into separate columns, and records bindings
+    fn normalize<'p>(&self, columns: &mut Vec<Column>, rows: &mut Vec<Row<'p>>, arms: &[Arm]) {
+        let mut i = 0;
+        while i < rows.len() {
+            let or = rows[i].cells.iter().enumerate().find_map(|(j, cell)| match cell {
+                Some(Pattern::Or(lhs, rhs)) => Some((j, &***lhs, &***rhs)),
+                _ => None
+            });
+            if let Some((j, lhs, rhs)) = or {
+                let mut alternative = rows[i].clone();
+                rows[i].cells[j] = Some(lhs);
+                alternative.cells[j] = Some(rhs);
+                rows.insert(i + 1, alternative);
+                continue;
+            }
+
+            let and = rows[i].cells.iter().enumerate().find_map(|(j, cell)| match cell {
+                Some(Pattern::And(lhs, rhs)) => Some((j, &***lhs, &***rhs)),
+                _ => None
+            });
+            if let Some((j, lhs, rhs)) = and {
+                columns.push(columns[j].clone());
+                rows.iter_mut().for_each(|row| row.cells.push(None));
+                rows[i].cells[j] = Some(lhs);
+                *rows[i].cells.last_mut().unwrap() = Some(rhs);
+                continue;
+            }
+
+            let row = &mut rows[i];
+            for (cell, column) in row.cells.iter_mut().zip(columns.iter()) {
+                match cell {
+                    Some(Pattern::Any | Pattern::Remaining) => *cell = None,
+                    Some(Pattern::Query(ident)) if ident == "_" => *cell = None,
+                    Some(Pattern::Query(ident)) if !self.is_nullary_variant(ident) => {
+                        let var = arms[row.arm].scope.get(ident).expect("undeclared pattern binding");
+                        row.bindings.push((*var, column.value.clone()));
+                        *cell = None;
+                    }
+                    _ => ()
+                }
+            }
+            i += 1;
+        }
+    }
+
+    // the constructor a pattern requires, if it can be dispatched on with a `Switch`
+    fn ctor(&self, pattern: &Pattern, column: &Column) -> Option<Ctor> {
+        match pattern {
+            Pattern::Variant(ident, _) => Some(Ctor::Tag(self.variant_tag(ident).0)),
+            Pattern::Query(ident) if self.is_nullary_variant(ident) => Some(Ctor::Tag(self.variant_tag(ident).0)),
+            Pattern::Term(PatternTerm::Basic(expr) | PatternTerm::Eq(expr)) if !self.is_float(&column.typ) => constant(expr).map(Ctor::Value),
+            Pattern::List(elems) => match split_list(elems) {
+                (prefix, None) => Some(Ctor::Length(prefix.len())),
+                (prefix, Some(suffix)) => Some(Ctor::MinLength(prefix.len() + suffix.len()))
+            },
+            _ => None
+        }
+    }
+
+    fn specialize<'p>(&self, pattern: &'p Pattern, ctor: Ctor, column: &Column) -> Specialized<'p> {
+        let matches = |cells: Vec<Option<&'p Pattern>>| Specialized::Matches(cells);
+        match (pattern, ctor) {
+            (Pattern::Variant(ident, args), Ctor::Tag(tag)) => {
+                let (variant_tag, fields) = self.variant_tag(ident);
+                if variant_tag != tag {
+                    return Specialized::Mismatch
+                }
+
+                let mut cells = args.iter().map(|arg| Some(&**arg)).collect::<Vec<_>>();
+                cells.resize(fields.len(), None);
+                matches(cells)
+            }
+            (Pattern::Query(ident), Ctor::Tag(tag)) if self.is_nullary_variant(ident) => {
+                if self.variant_tag(ident).0 == tag { matches(vec![]) } else { Specialized::Mismatch }
+            }
+            (Pattern::List(elems), Ctor::Length(len)) => match split_list(elems) {
+                (prefix, None) if prefix.len() == len => matches(prefix.iter().map(|elem| Some(&**elem)).collect()),
+                (prefix, Some(suffix)) if prefix.len() + suffix.len() <= len => {
+                    let mut cells = prefix.iter().map(|elem| Some(&**elem)).collect::<Vec<_>>();
+                    cells.resize(len - suffix.len(), None);
+                    cells.extend(suffix.iter().map(|elem| Some(&**elem)));
+                    matches(cells)
+                }
+                _ => Specialized::Mismatch
+            },
+            (pattern, Ctor::Value(value)) => match self.ctor(pattern, column) {
+                Some(Ctor::Value(other)) if other == value => matches(vec![]),
+                Some(Ctor::Value(_)) => Specialized::Mismatch,
+                _ => Specialized::Unknown
+            },
+            _ => Specialized::Unknown
+        }
+    }
+
+    // dispatches on the constructors of column `col` with a single `Switch`
+    fn switch<'p>(&mut self, columns: Vec<Column>, rows: Vec<Row<'p>>, col: usize, arms: &[Arm], fail: BlockId) {
+        let column = columns[col].clone();
+
+        let mut ctors = vec![];
+        for row in &rows {
+            if let Some(ctor) = row.cells[col].and_then(|cell| self.ctor(cell, &column))
+                && !matches!(ctor, Ctor::MinLength(_)) && !ctors.contains(&ctor) {
+                ctors.push(ctor);
+            }
+        }
+
+        // sum types and lists are matched through pointers
+        let (value, typ) = match ctors[0] {
+            Ctor::Value(_) => (column.value.clone(), column.typ),
+            _ => self.strip_pointers(column.value.clone(), &column.typ)
+        };
+        let scrutinee = match ctors[0] {
+            Ctor::Tag(_) => self.load(value.clone(), Operand::Const(0), Width::Word),
+            Ctor::Length(_) | Ctor::MinLength(_) => self.load(value.clone(), Operand::Const(-1), Width::Word),
+            Ctor::Value(_) => value.clone()
+        };
+
+        let complete = match (ctors[0], self.type_kind(&typ)) {
+            (Ctor::Tag(_), Some(TypeKind::Sum(variants))) => ctors.len() == variants.len(),
+            (Ctor::Value(_), Some(TypeKind::Bool)) => ctors.contains(&Ctor::Value(0)) && ctors.contains(&Ctor::Value(1)),
+            _ => false
+        };
+
+        let mut cases = ctors.iter().map(|ctor| (ctor.key(), self.new_block())).collect::<Vec<_>>();
+        let default = if complete { cases.last().unwrap().1 } else { self.new_block() };
+        if complete {
+            cases.pop();
+        }
+        let blocks = ctors.iter().enumerate()
+            .map(|(i, _)| cases.get(i).map(|(_, block)| *block).unwrap_or(default))
+            .collect::<Vec<_>>();
+        self.terminate(Terminator::Switch(scrutinee, cases, default));
+
+        for (ctor, block) in ctors.iter().zip(blocks) {
+            self.switch_to(block);
+
+            let fields = match ctor {
+                Ctor::Tag(tag) => {
+                    let sum_fields = match self.type_kind(&typ) {
+                        Some(TypeKind::Sum(variants)) => variants.get(*tag as usize).map(|variant| variant.fields().clone()),
+                        _ => None
+                    };
+                    sum_fields.unwrap_or_default().into_iter()
+                        .enumerate()
+                        .map(|(i, field)| {
+                            let field_value = self.load(value.clone(), Operand::Const(i as i64 + 1), Width::Word);
+                            Column::new(field_value, Some(field))
+                        })
+                        .collect()
+                }
+                Ctor::Length(len) => {
+                    let elem_typ = self.element_type(&typ);
+                    let width = self.memory_width(&elem_typ);
+                    (0..*len)
+                        .map(|i| {
+                            let elem = self.load(value.clone(), Operand::Const(i as i64), width);
+                            Column::new(elem, elem_typ)
+                        })
+                        .collect()
+                }
+                Ctor::Value(_) | Ctor::MinLength(_) => vec![]
+            };
+
+            let specialized_rows = rows.iter()
+                .filter_map(|row| {
+                    let mut row = row.clone();
+                    let extension = match row.cells[col] {
+                        None => vec![None; fields.len()],
+                        Some(cell) => match self.specialize(cell, *ctor, &column) {
+                            Specialized::Matches(cells) => {
+                                row.cells[col] = None;
+                                cells
+                            }
+                            Specialized::Mismatch => return None,
+                            Specialized::Unknown => vec![None; fields.len()]
+                        }
+                    };
+                    row.cells.extend(extension);
+                    Some(row)
+                })
+                .collect();
+
+            let mut specialized_columns = columns.clone();
+            specialized_columns.extend(fields);
+            self.decide(specialized_columns, specialized_rows, arms, fail);
+        }
+
+        if !complete {
+            self.switch_to(default);
+            let default_rows = rows.into_iter()
+                .filter(|row| matches!(
+                    row.cells[col].and_then(|cell| self.ctor(cell, &column)),
+                    Some(Ctor::MinLength(_)) | None
+                ))
+                .collect();
+            self.decide(columns, default_rows, arms, fail);
+        }
+    }
+
+    // replaces the record patterns of column `col` by patterns for each field of the record
+    fn destructure<'p>(&mut self, mut columns: Vec<Column>, mut rows: Vec<Row<'p>>, col: usize, arms: &[Arm], fail: BlockId) {
+        let column = columns[col].clone();
+        let fields = match self.type_kind(&column.typ) {
+            Some(TypeKind::Record(fields)) => fields,
+            _ => unreachable!("record pattern on a non-record")
+        };
+
+        for field in fields {
+            let value = self.load_field(column.value.clone(), &column.typ, field.ident());
+            columns.push(Column::new(value, Some(field.typ())));
+        }
+
+        for row in rows.iter_mut() {
+            let extension = match row.cells[col] {
+                Some(Pattern::Record(patterns)) => {
+                    row.cells[col] = None;
+                    fields.iter()
+                        .map(|field| patterns.iter().find(|(ident, _)| ident == field.ident()).map(|(_, pattern)| &**pattern))
+                        .collect()
+                }
+                _ => vec![None; fields.len()]
+            };
+            row.cells.extend(extension);
+        }
+
+        self.decide(columns, rows, arms, fail)
+    }
+
+    // tests the first row's pattern of column `col` on its own
+    fn test<'p>(&mut self, columns: Vec<Column>, rows: Vec<Row<'p>>, col: usize, arms: &[Arm], fail: BlockId) {
+        let pattern = rows[0].cells[col].unwrap();
+        let failure = self.new_block();
+
+        self.scopes.push(arms[rows[0].arm].scope.clone());
+        self.pattern(pattern, columns[col].value.clone(), &columns[col].typ, failure);
+        self.scopes.pop();
+
+        // other rows with the same pattern are decided by this test as well, unless they bind variables
+        let mut bindings = vec![];
+        self.pattern_bindings(pattern, &mut bindings);
+        let same_test = |row: &Row| bindings.is_empty() && row.cells[col] == Some(pattern);
+
+        let matched_rows = rows.iter().enumerate()
+            .map(|(i, row)| {
+                let mut row = row.clone();
+                if i == 0 || same_test(&row) {
+                    row.cells[col] = None;
+                }
+                row
+            })
+            .collect();
+        self.decide(columns.clone(), matched_rows, arms, fail);
+
+        self.switch_to(failure);
+        let failed_rows = rows.iter()
+            .skip(1)
+            .filter(|row| !same_test(row))
+            .cloned()
+            .collect();
+        self.decide(columns, failed_rows, arms, fail);
+    }
+}
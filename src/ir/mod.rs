@@ -0,0 +1,261 @@
+// Mid-level intermediate representation, consumed by all code generation backends.
+//
+// Every function consists of basic blocks of three-address instructions operating on
+// virtual registers (`Var`s). All values are machine words; operations are typed instead
+// of registers, e.g. `FAdd` interprets its operands as floats and `Width::Byte` loads
+// a single unsigned byte.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    ast::Program,
+    source_file::{SourceFile, SourceFileId}
+};
+
+mod lower;
+
+pub type Var = u32;
+pub type BlockId = usize;
+
+#[derive(Debug)]
+pub struct Module {
+    functions: Vec<Function>,
+    strings: Vec<String>,
+    tables: Vec<(Width, Vec<Operand>)>, // element width, elements
+    statics: Vec<(String, Operand)>, // identifier, initial value
+    externs: BTreeSet<String>
+}
+
+impl Module {
+    pub fn functions(&self) -> &Vec<Function> {
+        &self.functions
+    }
+
+    pub fn find_function(&self, ident: &str) -> Option<&Function> {
+        self.functions.iter().find(|func| func.ident == ident)
+    }
+
+    pub fn strings(&self) -> &Vec<String> {
+        &self.strings
+    }
+
+    // static data of `table` expressions; like vectors, their length is stored in the word
+    // before the first element. Elements are constants, strings, functions or other tables.
+    // Function values are closures: tables or heap blocks holding the function followed
+    // by its captured values, passed to the function as an extra last argument.
+    pub fn tables(&self) -> &Vec<(Width, Vec<Operand>)> {
+        &self.tables
+    }
+
+    // initial values are constants or tables
+    pub fn statics(&self) -> &Vec<(String, Operand)> {
+        &self.statics
+    }
+
+    // functions referenced, but not defined in this module
+    pub fn externs(&self) -> &BTreeSet<String> {
+        &self.externs
+    }
+}
+
+#[derive(Debug)]
+pub struct Function {
+    ident: String,
+    local: bool,
+    num_params: u32,
+    num_vars: u32,
+    vectors: Vec<i64>,
+    blocks: Vec<BasicBlock>
+}
+
+impl Function {
+    pub fn ident(&self) -> &String {
+        &self.ident
+    }
+
+    // local functions, like the ones lowered from lambdas, are not visible outside of the module
+    pub fn is_local(&self) -> bool {
+        self.local
+    }
+
+    // parameters are passed in the first `num_params` variables
+    pub fn num_params(&self) -> u32 {
+        self.num_params
+    }
+
+    pub fn num_vars(&self) -> u32 {
+        self.num_vars
+    }
+
+    // sizes in words of the vectors in the stack frame, allocated by `vec`
+    pub fn vectors(&self) -> &Vec<i64> {
+        &self.vectors
+    }
+
+    // the first block is the entry point
+    pub fn blocks(&self) -> &Vec<BasicBlock> {
+        &self.blocks
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BasicBlock {
+    instrs: Vec<Instr>,
+    terminator: Terminator
+}
+
+impl BasicBlock {
+    pub fn instrs(&self) -> &Vec<Instr> {
+        &self.instrs
+    }
+
+    pub fn terminator(&self) -> &Terminator {
+        &self.terminator
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Operand {
+    Var(Var),
+    Const(i64),
+    String(usize), // index into `Module::strings`
+    Function(String), // address of a function
+    Static(String), // address of a static variable
+    Global(i64), // address of a slot of the global vector
+    Vector(usize), // address of a vector in the stack frame, index into `Function::vectors`
+    Table(usize) // address of the first element of a table, index into `Module::tables`
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Width {
+    Word,
+    Byte,
+    SignedByte
+}
+
+impl Width {
+    pub fn size(&self) -> i64 {
+        match self {
+            Self::Word => 8,
+            Self::Byte | Self::SignedByte => 1
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Abs,
+    FAbs,
+    Neg,
+    FNeg,
+    IntToFloat,
+    UIntToFloat,
+    FloatToInt,
+    Truncate { bits: u8, signed: bool }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    UDiv,
+    URem,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FRem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr, // logical
+    Cmp(Condition)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+    FEq,
+    FNe,
+    FLt,
+    FLe,
+    FGt,
+    FGe
+}
+
+impl Condition {
+    pub fn from_operator(op: &str, float: bool, unsigned: bool) -> Self {
+        match (op, float, unsigned) {
+            ("==", true, _) => Self::FEq,
+            ("!=", true, _) => Self::FNe,
+            ("<", true, _) => Self::FLt,
+            ("<=", true, _) => Self::FLe,
+            (">", true, _) => Self::FGt,
+            (">=", true, _) => Self::FGe,
+            ("==", _, _) => Self::Eq,
+            ("!=", _, _) => Self::Ne,
+            ("<", _, true) => Self::ULt,
+            ("<=", _, true) => Self::ULe,
+            (">", _, true) => Self::UGt,
+            (">=", _, true) => Self::UGe,
+            ("<", _, _) => Self::Lt,
+            ("<=", _, _) => Self::Le,
+            (">", _, _) => Self::Gt,
+            (">=", _, _) => Self::Ge,
+            _ => unreachable!("invalid comparison operator `{op}`")
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::FEq | Self::FNe | Self::FLt | Self::FLe | Self::FGt | Self::FGe)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Callee {
+    Function(String),
+    Runtime(&'static str), // function of the runtime library
+    Indirect(Operand)
+}
+
+#[derive(Clone, Debug)]
+pub enum Instr {
+    Copy(Var, Operand),
+    Unary(Var, UnaryOp, Operand),
+    Binary(Var, BinaryOp, Operand, Operand),
+    AddrOf(Var, Var),
+    //   dest base     index    (scaled by the width)
+    Load(Var, Operand, Operand, Width),
+    //    base     index    value
+    Store(Operand, Operand, Operand, Width),
+    Call(Var, Callee, Vec<Operand>)
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum Terminator {
+    Jump(BlockId),
+    Branch(Operand, BlockId, BlockId),
+    //     value    cases                 default
+    Switch(Operand, Vec<(i64, BlockId)>, BlockId),
+    Return(Operand),
+    #[default]
+    Unreachable
+}
+
+pub fn lower_program(program: &Program, source_files: &HashMap<SourceFileId, SourceFile>) -> Module {
+    lower::Lowerer::new(program, source_files).lower()
+}
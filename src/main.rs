@@ -1,5 +1,4 @@
 #![feature(let_chains)]
-#![feature(impl_trait_in_assoc_type)]
 #![feature(trait_alias)]
 
 use std::collections::{HashSet, HashMap};
@@ -10,7 +9,7 @@ use source_file::Located;
 use crate:: {
     error::CompilerError,
     source_file::{SourceFile, SourceFileId},
-    context::{Context, BuildKind, OutputFile},
+    context::{Context, BuildKind, Backend, OutputFile},
 };
 
 mod context;
@@ -20,6 +19,10 @@ mod ast;
 mod parser;
 mod error;
 mod typechecker;
+mod ir;
+mod codegen;
+mod interpreter;
+mod writef;
 
 trait ExpectArg<T> {
     fn expect_arg(self, program_name: &str, arg: &str) -> T;
@@ -49,8 +52,33 @@ fn main() {
             "-h" | "--help" => help(ctx.program_name()),
             "-o" => ctx.set_output_file(args.next().expect_arg(ctx.program_name(), arg.as_str())),
             "-D" => ctx.define_tag(args.next().expect_arg(ctx.program_name(), arg.as_str())),
+            "-I" | "-L" => ctx.add_search_dir(args.next().expect_arg(ctx.program_name(), arg.as_str())),
             "-c" => ctx.set_build_kind(BuildKind::Object),
             "--shared" => ctx.set_build_kind(BuildKind::SharedObject),
+            "--run" => ctx.set_run_mode(true),
+            "--max-errors" => {
+                let max_errors = args.next().expect_arg(ctx.program_name(), arg.as_str());
+                match max_errors.parse() {
+                    Ok(max_errors) => ctx.set_max_errors(max_errors),
+                    Err(_) => {
+                        eprintln!("{}: invalid error limit -- '{}'", ctx.program_name(), max_errors);
+                        eprintln!("Try `{} --help` for more information.", ctx.program_name());
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--" => ctx.add_program_args(args.by_ref()),
+            "--backend" => {
+                let backend = args.next().expect_arg(ctx.program_name(), arg.as_str());
+                match Backend::try_from(backend.as_str()) {
+                    Ok(backend) => ctx.set_backend(backend),
+                    Err(_) => {
+                        eprintln!("{}: invalid backend -- '{}'", ctx.program_name(), backend);
+                        eprintln!("Try `{} --help` for more information.", ctx.program_name());
+                        std::process::exit(1);
+                    }
+                }
+            }
             _ if arg.starts_with("-") => {
                 eprintln!("{}: invalid option -- {}", ctx.program_name(), arg);
                 eprintln!("Try `{} --help` for more information.", ctx.program_name());
@@ -76,6 +104,16 @@ fn main() {
             terminate()
         }
     }
+
+    if ctx.run_mode() {
+        match ctx.run() {
+            Ok(exit_code) => std::process::exit(exit_code),
+            Err(err) => {
+                highlight_error(err, ctx.source_files());
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
 fn usage(program_name: &str) {
@@ -88,10 +126,16 @@ fn help(program_name: &str) -> ! {
     println!("Options:
   -o <output file>  Set an output file; default: `{}`
   -D <tag name>     Set a BCPL tag.
+  -I, -L <dir>      Search <dir> for `<Section>.bpp` files of required sections.
+                    Directories in `{}` are searched afterwards.
   -c                Skip linking and emit `.o` file.
   --shared          Create a shared library.
+  --backend <name>  Select the code generator: `c` (default) or `x86_64`.
+  --max-errors <n>  Stop after <n> syntax errors, `0` for no limit; default: {}
+  --run             Interpret the program instead of compiling it.
+  -- <args...>      Pass the remaining arguments to the program when using `--run`.
   -h, --help        Print this help text and exit.",
-    OutputFile::default().to_filename(&BuildKind::default())); 
+    OutputFile::default().to_filename(&BuildKind::default()), context::SEARCH_PATH_VAR, context::DEFAULT_MAX_ERRORS); 
 
     std::process::exit(0);
 }
@@ -111,7 +155,7 @@ fn highlight_error(err: Located<CompilerError>, source_files: &HashMap<SourceFil
     let line = file.line(loc.line()).unwrap();
     let mark_start = loc.column();
     let mark_end = loc.column() + loc.width();
-    println!("{}{}{}", &line[..mark_start], (&line[mark_start..mark_end]).to_owned().bold().b_yellow(), &line[mark_end..]);
+    println!("{}{}{}", &line[..mark_start], line[mark_start..mark_end].to_owned().bold().b_yellow(), &line[mark_end..]);
 
     print!("      {} {}{}", "|".b_black(), " ".repeat(mark_start), "~".repeat(loc.width()).yellow());
 
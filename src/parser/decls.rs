@@ -1,46 +1,70 @@
-use std::cell::RefCell;
-
 use crate::{
     token::TokenKind, 
     source_file::{Location, Located, WithLocation}, 
-    ast::{Decl, Function, FunctionBody, Param, IntoDecl, Section, types::TypeKind, BasicFunctionBody, pattern::Pattern}
+    ast::{Decl, Function, FunctionBody, Param, IntoDecl, Section, ManifestDecl, GlobalDecl, StaticDecl, types::TypeKind, BasicFunctionBody, pattern::Pattern}
 };
 
-use super::{Parser, ParseResult, ParseError, stmt::StmtContext, pattern};
+use super::{Parser, ParseResult, ParseError, stmt::StmtContext};
 
 impl<'a> Parser<'a> {
     pub(super) fn parse_section(&mut self) -> ParseResult<'a, ()> {
         let section_loc = self.current_token.location().clone();
         self.expect(&[TokenKind::Section])?;
 
-        let mut section = Section::new(self.expect_ident()?.into(), section_loc);
+        let mut section = Section::new(self.expect_ident()?, section_loc);
 
         let mut had_decls = false;
         loop {
-            match self.current().kind() {
+            let loop_jumps = self.loop_jumps.len();
+            let result = match self.current().kind() {
                 TokenKind::Eof | TokenKind::Section => break,
                 TokenKind::Require => {
                     if had_decls {
                         self.push_warning(ParseError::RequireAfterDecl.with_location(self.current_token.location().clone()));
                     }
-                    section.add_require(self.parse_require()?);
+                    self.parse_require().map(|required| section.add_require(required))
                 }
                 TokenKind::Type => {
                     had_decls = true;
-                    self.parse_type_alias()?;
+                    self.parse_type_alias()
                 }
                 _ => {
-                    let decl = self.parse_decl()?;
-                    if let Some(prev) = section.defines(decl.ident()) {
-                        return Err(ParseError::Redefinition(prev.location().clone(), decl.ident().clone()).with_location(decl.location().clone()))
-                    }
-                    section.declare(decl);
                     had_decls = true;
+                    self.parse_decl().and_then(|decls| self.declare_all(&mut section, decls))
                 }
+            };
+
+            if let Err(err) = result {
+                self.report(err)?;
+                // state of the declaration left behind by the error
+                self.type_params.clear();
+                self.loop_jumps.truncate(loop_jumps);
+                self.synchronize(&[
+                    TokenKind::Let, TokenKind::And, TokenKind::Section, TokenKind::Require,
+                    TokenKind::Type, TokenKind::Manifest, TokenKind::Global, TokenKind::Static
+                ])?;
             }
         }
 
-        self.ast.lock().unwrap().add_section(section);
+        let (ident, loc) = (section.ident().clone(), section.location().clone());
+        let added = self.ast.lock().unwrap().add_section(section);
+        if let Err(prev) = added {
+            self.report(ParseError::Redefinition(prev, ident).with_location(loc))?;
+        }
+        Ok(())
+    }
+
+    // redefinitions don't affect parsing, so the other declarations are still declared
+    fn declare_all(&mut self, section: &mut Section, decls: Vec<Box<dyn Decl>>) -> ParseResult<'a, ()> {
+        for decl in decls {
+            if let Some(prev) = section.defines(decl.ident()) {
+                let err = ParseError::Redefinition(prev.location().clone(), decl.ident().clone()).with_location(decl.location().clone());
+                self.report(err)?;
+            }
+            else {
+                section.declare(decl);
+            }
+        }
         Ok(())
     }
 
@@ -51,19 +75,83 @@ impl<'a> Parser<'a> {
         Ok(self.expect_ident()?.to_string().with_location(loc))
     }
 
-    pub(super) fn parse_decl(&mut self) -> ParseResult<'a, Box<dyn Decl>> {
+    // `manifest`, `global` and `static` declare all the entries of a block at once
+    pub(super) fn parse_decl(&mut self) -> ParseResult<'a, Vec<Box<dyn Decl>>> {
         let loc = self.current_token.location().clone();
         let decl_tok = self.expect(&[TokenKind::Let, TokenKind::And, TokenKind::Global, TokenKind::Manifest, TokenKind::Static])?;
         match decl_tok.kind() {
-            TokenKind::Let => self.parse_function_decl(loc, false).map(Function::into_decl),
-            TokenKind::And => self.parse_function_decl(loc, true).map(Function::into_decl),
+            TokenKind::Let => self.parse_function_decl(loc, false).map(|func| vec![func.into_decl()]),
+            TokenKind::And => self.parse_function_decl(loc, true).map(|func| vec![func.into_decl()]),
+            TokenKind::Manifest => self.parse_decl_block(Self::parse_manifest_entry),
+            TokenKind::Global => self.parse_decl_block(Self::parse_global_entry),
+            TokenKind::Static => self.parse_decl_block(Self::parse_static_entry),
             _ => unreachable!()
         }
     }
 
+    // `{ <entry>; <entry>; ... }`, the semicolon after the last entry is optional
+    fn parse_decl_block(&mut self, parse_entry: fn(&mut Self, Location) -> ParseResult<'a, Box<dyn Decl>>) -> ParseResult<'a, Vec<Box<dyn Decl>>> {
+        self.expect(&[TokenKind::LBrace])?;
+
+        let mut decls = vec![];
+        while self.advance_if(&[TokenKind::RBrace])?.is_none() {
+            let loc = self.current_token.location().clone();
+            decls.push(parse_entry(self, loc)?);
+
+            if self.current().kind() != &TokenKind::RBrace {
+                self.expect(&[TokenKind::Semicolon])?;
+            }
+        }
+        Ok(decls)
+    }
+
+    // `A = <constant>`
+    fn parse_manifest_entry(&mut self, loc: Location) -> ParseResult<'a, Box<dyn Decl>> {
+        let ident = self.expect_ident()?;
+        self.expect(&[TokenKind::Eq])?;
+        let value = self.parse_expr(&StmtContext::Empty)?;
+        Ok(ManifestDecl::new(loc, ident, value).into_decl())
+    }
+
+    // `G: <global number>`
+    fn parse_global_entry(&mut self, loc: Location) -> ParseResult<'a, Box<dyn Decl>> {
+        let ident = self.expect_ident()?;
+        self.expect(&[TokenKind::Colon])?;
+        let index = self.parse_expr(&StmtContext::Empty)?;
+        Ok(GlobalDecl::new(loc, ident, None, index).into_decl())
+    }
+
+    // `S = <initial value>` or `S :: <type> = <initial value>`
+    fn parse_static_entry(&mut self, loc: Location) -> ParseResult<'a, Box<dyn Decl>> {
+        let ident = self.expect_ident()?;
+        let typ = if self.advance_if(&[TokenKind::Of])?.is_some() {
+            Some(self.parse_type()?)
+        }
+        else {
+            None
+        };
+
+        self.expect(&[TokenKind::Eq])?;
+        let value = self.parse_expr(&StmtContext::Empty)?;
+        let value = match typ {
+            Some(typ) if value.typ() != &Some(typ) => value.implicit_cast(typ),
+            _ => value
+        };
+        Ok(StaticDecl::new(loc, ident, typ, value).into_decl())
+    }
+
     pub(super) fn parse_function_decl(&mut self, decl_loc: Location, tailcall_recursive: bool) -> ParseResult<'a, Function> {
         let ident = self.expect_ident()?;
-        
+        self.type_params = self.parse_optional_list(TokenKind::LBracket, TokenKind::RBracket, TokenKind::Comma, Self::parse_type_param_ident, &())?;
+
+        let func = self.parse_function_signature_and_body(decl_loc, ident, tailcall_recursive);
+        self.type_params.clear();
+        func
+    }
+
+    fn parse_function_signature_and_body(&mut self, decl_loc: Location, ident: String, tailcall_recursive: bool) -> ParseResult<'a, Function> {
+        let type_params = self.type_params.clone();
+        let loop_jumps = self.loop_jumps.len();
         let params = self.parse_optional_list(TokenKind::LParen, TokenKind::RParen, TokenKind::Comma, Self::parse_function_param, &())?;
 
         let context = StmtContext::Function(&params);
@@ -74,11 +162,13 @@ impl<'a> Parser<'a> {
             self.parse_function_body(&context)?.into() 
         };
         
+        self.check_loop_jumps(loop_jumps)?;
+
         let return_type = self.get_return_type(&body);
-        Ok(Function::new(decl_loc, ident, params, return_type, tailcall_recursive, body))
+        Ok(Function::new(decl_loc, ident, type_params, params, return_type, tailcall_recursive, body))
     }
 
-    fn parse_function_param(&mut self, _: &()) -> ParseResult<'a, Param> {
+    pub(super) fn parse_function_param(&mut self, _: &()) -> ParseResult<'a, Param> {
         let loc = self.current_token.location().clone();
         let ident = self.parse_pattern()?;
         
@@ -90,7 +180,7 @@ impl<'a> Parser<'a> {
         };
 
         let value = if self.advance_if(&[TokenKind::Eq])?.is_some() {
-            Some(self.parse_expr(&mut StmtContext::Empty)?)
+            Some(self.parse_expr(&StmtContext::Empty)?)
         }
         else {
             None
@@ -124,8 +214,8 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn check_correct_pattern_length(&self, patterns: &Vec<Located<Pattern>>, num_params: usize) -> ParseResult<'a, ()> {
-        (patterns.len() == num_params).then(|| ())
+    fn check_correct_pattern_length(&self, patterns: &[Located<Pattern>], num_params: usize) -> ParseResult<'a, ()> {
+        (patterns.len() == num_params).then_some(())
             .ok_or_else(|| ParseError::WrongNumOfPatterns(num_params)
                         .with_location(patterns[0].location().clone()))
     }
@@ -178,8 +268,8 @@ impl<'a> Parser<'a> {
 
     fn get_return_type(&self, body: &FunctionBody) -> Option<u32> {
         match body {
-            FunctionBody::Expr(expr) => expr.typ().clone(),
-            FunctionBody::PatternMatchedExpr(bodies) => bodies.first().map(|(_, expr)| expr.typ().clone()).flatten(),
+            FunctionBody::Expr(expr) => *expr.typ(),
+            FunctionBody::PatternMatchedExpr(bodies) => bodies.first().and_then(|(_, expr)| *expr.typ()),
             FunctionBody::Stmt(_) | FunctionBody::PatternMatchedStmt(_) => Some(self.get_type(TypeKind::Unit))
         }
     }
@@ -1,16 +1,20 @@
 use std::cell::RefCell;
 
 use crate::{
-    ast::{expr::{Expr, ExprKind},
-    types::TypeKind, stmt::StmtKind, pattern::Pattern},
-    token::TokenKind, source_file::{WithLocation, Located}
+    ast::{expr::{Expr, ExprKind, Lambda},
+    types::TypeKind, pattern::Pattern, BasicFunctionBody},
+    token::TokenKind, source_file::{WithLocation, Located, Location}
 };
 
-use super::{Parser, ParseResult, stmt::StmtContext, ParseError};
+use super::{Parser, ParseResult, stmt::StmtContext, ParseError, sort_fields};
+
+// builds a `match` or `every` expression from its arguments and branches
+type MatchExprInit = fn(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Expr>)>) -> ExprKind;
 
 #[derive(PartialEq, PartialOrd)]
 enum OperatorPrecedence {
-    Call = 9,
+    Call = 10,
+    Subscript = 9,
     Cast = 8,
     Product = 7,
     Sum = 6,
@@ -27,7 +31,8 @@ impl<'a> TryFrom<&TokenKind<'a>> for OperatorPrecedence {
 
     fn try_from(value: &TokenKind<'a>) -> Result<Self, Self::Error> {
         match value {
-            TokenKind::LParen | TokenKind::LBracket => Ok(Self::Call),
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::Period => Ok(Self::Call),
+            TokenKind::Bang | TokenKind::Percent => Ok(Self::Subscript),
             TokenKind::Plus | TokenKind::Minus => Ok(Self::Sum),
             TokenKind::Star | TokenKind::Slash | TokenKind::Mod => Ok(Self::Product),
             TokenKind::Eq | TokenKind::Ne
@@ -43,11 +48,51 @@ impl<'a> TryFrom<&TokenKind<'a>> for OperatorPrecedence {
     }
 }
 
+// decodes the escape sequences of a character or string literal, either C-style like `\n` or BCPL-style
+// like `*n`; `None` if it contains an unknown one
+fn decode_escapes(literal: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(ch) = chars.next() {
+        let escaped = match ch {
+            '\\' | '*' => chars.next(),
+            _ => None
+        };
+        decoded.push(match escaped {
+            Some('n' | 'N') => '\n',
+            Some('t' | 'T') => '\t',
+            Some('r' | 'R') => '\r',
+            Some('s' | 'S') => ' ',
+            Some('b' | 'B') => '\x08',
+            Some('e' | 'E') => '\x1b',
+            Some('0') => '\0',
+            Some(escaped @ ('\\' | '*' | '\'' | '"')) => escaped,
+            Some(_) => return None,
+            // a single `*` or `\` at the end stands for itself
+            None => ch
+        });
+    }
+    Some(decoded)
+}
+
+// decodes a single character or escape sequence
+fn decode_char_lit(literal: &str) -> Option<char> {
+    let decoded = decode_escapes(literal)?;
+    let mut chars = decoded.chars();
+    let ch = chars.next()?;
+    chars.next().is_none().then_some(ch)
+}
+
 impl<'a> Parser<'a> {
     pub(super) fn parse_expr(&mut self, context: &StmtContext) -> ParseResult<'a, Expr> {
         self.parse_expr_with_precedence(context, OperatorPrecedence::Lowest)
     }
 
+    // expressions in patterns may not contain `&` and `|`, since they combine patterns
+    pub(super) fn parse_pattern_expr(&mut self, context: &StmtContext) -> ParseResult<'a, Expr> {
+        self.parse_expr_with_precedence(context, OperatorPrecedence::And)
+    }
+
     fn parse_expr_with_precedence(&mut self, context: &StmtContext, precedence: OperatorPrecedence) -> ParseResult<'a, Expr> {
         let mut expr = self.parse_prefix_expr(context)?;
 
@@ -80,7 +125,10 @@ impl<'a> Parser<'a> {
             TokenKind::Condition => self.parse_conditional(context, left),
             TokenKind::Of => self.parse_explicit_cast(left),
             TokenKind::LBracket => self.parse_index_expr(context, left),
-            _ => self.unexpected(&[TokenKind::Ident("operator".into())])
+            TokenKind::Bang => self.parse_word_subscript(context, left),
+            TokenKind::Percent => self.parse_byte_subscript(context, left),
+            TokenKind::Period => self.parse_field_access(left),
+            _ => self.unexpected(&[TokenKind::Ident("operator")])
         }
     }
 
@@ -90,16 +138,27 @@ impl<'a> Parser<'a> {
             TokenKind::Atom(atom) => self.parse_atom(atom.to_string()),
             TokenKind::True | TokenKind::False => self.parse_bool_lit(),
             TokenKind::IntegerLit(int) => self.parse_integer_lit(*int),
+            TokenKind::FloatLit(mantissa, exponent) => self.parse_float_lit(mantissa.to_string(), exponent.map(str::to_string)),
+            TokenKind::CharLit(literal) => self.parse_char_lit(literal.to_string()),
             TokenKind::StringLit(str) => self.parse_string_lit(str.to_string()),
             TokenKind::ValOf => self.parse_valof(context),
             TokenKind::LParen => self.parse_parens(context),
             TokenKind::Match => self.parse_match_expr(context, ExprKind::Match),
             TokenKind::Every => self.parse_match_expr(context, ExprKind::Every),
-            TokenKind::Abs => self.parse_prefix_op(context, ExprKind::Abs),
-            TokenKind::Not => self.parse_prefix_op(context, ExprKind::Not),
+            TokenKind::Abs => self.parse_prefix_op(context, ExprKind::Abs, OperatorPrecedence::Lowest),
+            TokenKind::Not => self.parse_prefix_op(context, ExprKind::Not, OperatorPrecedence::Lowest),
+            // `-a * b` negates only `a`
+            TokenKind::Minus => self.parse_prefix_op(context, ExprKind::Neg, OperatorPrecedence::Product),
             TokenKind::LogAnd => self.parse_ref(context),
             TokenKind::At => self.parse_deref(context),
-            _ => self.unexpected(&[TokenKind::Ident("expression".into())])
+            // `!p` is the word at `p`, `!v!i` the word at `v!i`
+            TokenKind::Bang => self.parse_prefix_op(context, ExprKind::Deref, OperatorPrecedence::Cast),
+            TokenKind::Vec => self.parse_vec(context),
+            TokenKind::LBracket => self.parse_array_lit(context),
+            TokenKind::Table => self.parse_table(context),
+            TokenKind::LBrace => self.parse_record_lit(context),
+            TokenKind::Fn => self.parse_lambda(),
+            _ => self.unexpected(&[TokenKind::Ident("expression")])
         }
     }
 
@@ -130,17 +189,40 @@ impl<'a> Parser<'a> {
         let loc = self.advance()?.location().clone();
 
         let typ = match value {
-            _ if value > std::i64::MAX as u64 => TypeKind::UInt64,
-            _ if value > std::u32::MAX as u64 => TypeKind::Int64,
-            _ if value > std::i32::MAX as u64 => TypeKind::UInt32,
+            _ if value > i64::MAX as u64 => TypeKind::UInt64,
+            _ if value > u32::MAX as u64 => TypeKind::Int64,
+            _ if value > i32::MAX as u64 => TypeKind::UInt32,
             _ => TypeKind::Int32
         };
 
         Ok(Expr::new(loc, Some(self.get_type(typ)), ExprKind::IntLit(value)))
     }
 
-    fn parse_string_lit(&mut self, value: String) -> ParseResult<'a, Expr> {
+    fn parse_float_lit(&mut self, mantissa: String, exponent: Option<String>) -> ParseResult<'a, Expr> {
+        let literal = match exponent {
+            Some(exponent) => format!("{mantissa}e{exponent}"),
+            None => mantissa
+        };
         let loc = self.advance()?.location().clone();
+
+        let value = literal.parse::<f64>()
+            .map_err(|_| ParseError::InvalidLiteral("float", literal).with_location(loc.clone()))?;
+        Ok(Expr::new(loc, Some(self.get_type(TypeKind::Float64)), ExprKind::FloatLit(value)))
+    }
+
+    fn parse_char_lit(&mut self, literal: String) -> ParseResult<'a, Expr> {
+        let loc = self.advance()?.location().clone();
+
+        let value = decode_char_lit(&literal)
+            .ok_or_else(|| ParseError::InvalidLiteral("character", format!("'{literal}'")).with_location(loc.clone()))?;
+        Ok(Expr::new(loc, Some(self.get_type(TypeKind::Char)), ExprKind::CharLit(value)))
+    }
+
+    fn parse_string_lit(&mut self, literal: String) -> ParseResult<'a, Expr> {
+        let loc = self.advance()?.location().clone();
+
+        let value = decode_escapes(&literal)
+            .ok_or_else(|| ParseError::InvalidLiteral("string", format!("\"{literal}\"")).with_location(loc.clone()))?;
         Ok(Expr::new(loc, Some(self.get_string_type()), ExprKind::StringLit(value)))
     }
 
@@ -163,6 +245,35 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    // `fn (params) = expr` or `fn (params) be stmt`
+    fn parse_lambda(&mut self) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::Fn])?.location().clone();
+
+        let params = self.parse_optional_list(TokenKind::LParen, TokenKind::RParen, TokenKind::Comma, Self::parse_function_param, &())?;
+        // lambdas are called through function pointers, which always pass all arguments
+        if let Some(param) = params.iter().find(|param| param.default_value().is_some()) {
+            return Err(
+                ParseError::Generic("Lambda parameters cannot have default values.".into())
+                    .with_location(param.location().clone())
+            )
+        }
+
+        let context = StmtContext::Function(&params);
+        let loop_jumps = self.loop_jumps.len();
+        let (body, return_type) = if let TokenKind::Eq = self.expect(&[TokenKind::Eq, TokenKind::Be])?.kind() {
+            let expr = self.parse_expr(&context)?;
+            let typ = *expr.typ();
+            (BasicFunctionBody::Expr(expr), typ)
+        }
+        else {
+            (BasicFunctionBody::Stmt(self.parse_stmt(&context)?), Some(self.get_type(TypeKind::Unit)))
+        };
+        self.check_loop_jumps(loop_jumps)?;
+
+        let lambda = Lambda::new(params, return_type, body);
+        Ok(Expr::new(loc, None, ExprKind::Lambda(Box::new(lambda))))
+    }
+
     fn parse_function_call(&mut self, context: &StmtContext, callee: Expr) -> ParseResult<'a, Expr> {
         let loc = self.expect(&[TokenKind::LParen])?.location().clone();
 
@@ -173,13 +284,10 @@ impl<'a> Parser<'a> {
 
     fn parse_binop(&mut self, context: &StmtContext, left: Expr, op_init: fn(Box<Expr>, Box<Expr>) -> ExprKind, precedence: OperatorPrecedence) -> ParseResult<'a, Expr> {
         let tok = self.advance()?;
-        let mut right = self.parse_expr_with_precedence(context, precedence)?;
-
-        let typ = left.typ().clone();
-        if let Some(typ) = &typ && &Some(*typ) != right.typ() {
-            right = right.implicit_cast(*typ);
-        }
+        let right = self.parse_expr_with_precedence(context, precedence)?;
 
+        // the operands get coerced to their common type during type inference
+        let typ = *left.typ();
         Ok(Expr::new(tok.location().clone(), typ, op_init(Box::new(left), Box::new(right))))
     }
 
@@ -189,12 +297,12 @@ impl<'a> Parser<'a> {
         Ok(binop)
     }
 
-    fn parse_prefix_op(&mut self, context: &StmtContext, op_init: fn(Box<Expr>) -> ExprKind) -> ParseResult<'a, Expr> {
+    fn parse_prefix_op(&mut self, context: &StmtContext, op_init: fn(Box<Expr>) -> ExprKind, precedence: OperatorPrecedence) -> ParseResult<'a, Expr> {
         let loc = self.advance()?.location().clone();
         
-        let expr = self.parse_expr(context)?;
+        let expr = self.parse_expr_with_precedence(context, precedence)?;
 
-        Ok(Expr::new(loc, expr.typ().clone(), op_init(Box::new(expr))))
+        Ok(Expr::new(loc, *expr.typ(), op_init(Box::new(expr))))
     }
 
     fn parse_conditional(&mut self, context: &StmtContext, mut condition: Expr) -> ParseResult<'a, Expr> {
@@ -209,9 +317,9 @@ impl<'a> Parser<'a> {
         self.expect(&[TokenKind::Comma])?;
         let mut else_branch = self.parse_expr_with_precedence(context, OperatorPrecedence::Conditional)?;
 
-        let typ = if_branch.typ().clone();
-        if typ.is_some() && else_branch.typ() != &typ {
-            else_branch = else_branch.implicit_cast(typ.unwrap());
+        let typ = *if_branch.typ();
+        if let Some(typ) = typ && else_branch.typ() != &Some(typ) {
+            else_branch = else_branch.implicit_cast(typ);
         }
 
         Ok(Expr::new(loc, typ, ExprKind::Conditional(Box::new(condition), Box::new(if_branch), Box::new(else_branch))))
@@ -255,7 +363,74 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_match_expr(&mut self, context: &StmtContext, init: fn(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Expr>)>) -> ExprKind) -> ParseResult<'a, Expr> {
+    // `v!i` is the `i`th word of the vector `v`, like `v[i]`
+    fn parse_word_subscript(&mut self, context: &StmtContext, left: Expr) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::Bang])?.location().clone();
+        let index = self.parse_expr_with_precedence(context, OperatorPrecedence::Subscript)?;
+
+        Ok(Expr::new(loc, None, ExprKind::Index(Box::new(left), Box::new(index))))
+    }
+
+    // `v%i` is the `i`th byte of the vector `v`, regardless of its element type
+    fn parse_byte_subscript(&mut self, context: &StmtContext, left: Expr) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::Percent])?.location().clone();
+        let index = self.parse_expr_with_precedence(context, OperatorPrecedence::Subscript)?;
+
+        let byte = self.get_type(TypeKind::UInt8);
+        let bytes = Expr::new(loc.clone(), Some(self.pointer_to(byte)), ExprKind::Cast(Box::new(left)));
+        Ok(Expr::new(loc, None, ExprKind::Index(Box::new(bytes), Box::new(index))))
+    }
+
+    // `vec n` allocates `n + 1` words in the stack frame of the current function
+    fn parse_vec(&mut self, context: &StmtContext) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::Vec])?.location().clone();
+        let size = self.parse_expr(context)?;
+
+        Ok(Expr::new(loc, None, ExprKind::Vector(Box::new(size))))
+    }
+
+    fn parse_array_lit(&mut self, context: &StmtContext) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::LBracket])?.location().clone();
+        let elems = self.parse_list(TokenKind::RBracket, TokenKind::Comma, Self::parse_expr, context)?;
+
+        Ok(Expr::new(loc, None, ExprKind::Array(elems)))
+    }
+
+    // `table a, b, c` takes all following comma-separated expressions
+    fn parse_table(&mut self, context: &StmtContext) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::Table])?.location().clone();
+
+        let mut elems = vec![self.parse_expr(context)?];
+        while self.advance_if(&[TokenKind::Comma])?.is_some() {
+            elems.push(self.parse_expr(context)?);
+        }
+
+        Ok(Expr::new(loc, None, ExprKind::Array(elems)))
+    }
+
+    fn parse_record_field(&mut self, context: &StmtContext) -> ParseResult<'a, (Location, String, Expr)> {
+        let loc = self.current().location().clone();
+        let ident = self.expect_ident()?;
+        self.expect(&[TokenKind::Eq])?;
+        Ok((loc, ident, self.parse_expr(context)?))
+    }
+
+    // `{ x = a, y = b }`
+    fn parse_record_lit(&mut self, context: &StmtContext) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::LBrace])?.location().clone();
+        let fields = self.parse_list(TokenKind::RBrace, TokenKind::Comma, Self::parse_record_field, context)?;
+
+        Ok(Expr::new(loc, None, ExprKind::Record(sort_fields(fields)?)))
+    }
+
+    fn parse_field_access(&mut self, left: Expr) -> ParseResult<'a, Expr> {
+        let loc = self.expect(&[TokenKind::Period])?.location().clone();
+        let field = self.expect_ident()?;
+
+        Ok(Expr::new(loc, None, ExprKind::Field(Box::new(left), field)))
+    }
+
+    fn parse_match_expr(&mut self, context: &StmtContext, init: MatchExprInit) -> ParseResult<'a, Expr> {
         let loc = self.advance()?.location().clone();
 
         let args = if self.current().kind() != &TokenKind::LParen {
@@ -282,7 +457,7 @@ impl<'a> Parser<'a> {
                 }
             }
             else {
-                typ = Some(expr.typ().clone());
+                typ = Some(*expr.typ());
             }
 
             branches.push((patterns, Box::new(expr)))
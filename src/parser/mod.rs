@@ -1,10 +1,10 @@
-use std::{ops::Deref, sync::{Arc, Mutex}};
+use std::{fmt::Display, ops::Deref, sync::{Arc, Mutex}};
 
 use crate::{
     token::{lexer::Lexer, Token, TokenKind},
     source_file::{Location, Located, WithLocation},
-    ast::{Program, stmt::StmtKind},
-    error::{IntoCompilerError, CompilerError, Severity}
+    ast::Program,
+    error::{CompilerError, Severity}
 };
 
 mod types;
@@ -17,16 +17,27 @@ pub struct Parser<'a> {
     lexer: Lexer<'a>,
     ast: Arc<Mutex<Program>>,
     warnings: Vec<Located<ParseError<'a>>>,
-    current_token: Token<'a>
+    errors: Vec<Located<ParseError<'a>>>,
+    // parsing stops after this many errors
+    error_limit: Option<usize>,
+    current_token: Token<'a>,
+    // type parameters of the generic type alias or function currently parsed
+    type_params: Vec<String>,
+    // `break` and `next` outside of loops, which are valid if a `repeat` turns the statement around them into one
+    loop_jumps: Vec<Located<ParseError<'a>>>
 }
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: Lexer<'a>, ast: Arc<Mutex<Program>>) -> Self {
         Self {
             warnings: vec![],
+            errors: vec![],
+            error_limit: None,
             current_token: Token::eof(lexer.current_loc()),
             lexer,
-            ast
+            ast,
+            type_params: vec![],
+            loop_jumps: vec![]
         }
     }
 
@@ -38,6 +49,59 @@ impl<'a> Parser<'a> {
         &self.warnings
     }
 
+    pub fn set_error_limit(&mut self, error_limit: Option<usize>) {
+        self.error_limit = error_limit;
+    }
+
+    fn error_limit_reached(&self) -> bool {
+        self.error_limit.is_some_and(|limit| self.errors.len() >= limit)
+    }
+
+    // records `err` to continue parsing after it; errors at the location of the previous one or at the end
+    // of the file after others are most likely caused by the recovery itself and left out
+    fn report(&mut self, err: Located<ParseError<'a>>) -> ParseResult<'a, ()> {
+        if self.error_limit_reached() {
+            return Err(err)
+        }
+
+        let is_cascading = self.errors.last().is_some_and(|last| {
+            last.location() == err.location() || matches!(*err, ParseError::UnexpectedEof(_))
+        });
+        if !is_cascading {
+            self.errors.push(err);
+        }
+
+        match self.error_limit {
+            Some(limit) if self.error_limit_reached() => {
+                let loc = self.errors.last().unwrap().location().clone();
+                Err(ParseError::ErrorLimit(limit).with_location(loc))
+            }
+            _ => Ok(())
+        }
+    }
+
+    // panic-mode recovery: skips tokens up to one of `stop` outside of nested blocks
+    fn synchronize(&mut self, stop: &[TokenKind<'a>]) -> ParseResult<'a, ()> {
+        let mut depth = 0usize;
+        while !self.current().is_eof() {
+            let kind = self.current().kind();
+            if depth == 0 && stop.contains(kind) {
+                break
+            }
+
+            match kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth = depth.saturating_sub(1),
+                _ => ()
+            }
+
+            if let Err(err) = self.advance() {
+                self.report(err)?;
+            }
+        }
+        Ok(())
+    }
+
     fn current(&self) -> &Token<'a> {
         &self.current_token
     }
@@ -99,18 +163,46 @@ impl<'a> Parser<'a> {
         )
     }
 
-    pub fn parse(&mut self) -> ParseResult<'a, ()> {
-        self.advance()?;
-        
+    // returns all errors of the file, followed by a note if parsing stopped at the error limit
+    pub fn parse(&mut self) -> Result<(), Vec<Located<ParseError<'a>>>> {
+        if let Err(limit) = self.parse_sections() {
+            self.errors.push(limit);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn parse_sections(&mut self) -> ParseResult<'a, ()> {
+        if let Err(err) = self.advance() {
+            self.report(err)?;
+        }
+
         while !self.current_token.is_eof() {
-            self.parse_section()?;
+            if let Err(err) = self.parse_section() {
+                self.report(err)?;
+                self.synchronize(&[TokenKind::Section])?;
+            }
+            self.check_loop_jumps(0)?;
         }
 
         Ok(())
-    } 
+    }
+
+    // reports `break` and `next` since `start` which no `repeat` made part of a loop
+    fn check_loop_jumps(&mut self, start: usize) -> ParseResult<'a, ()> {
+        for err in self.loop_jumps.drain(start..).collect::<Vec<_>>() {
+            self.report(err)?;
+        }
+        Ok(())
+    }
 
     fn parse_optional_list<T, U>(&mut self, start: TokenKind<'a>, end: TokenKind<'a>, delim: TokenKind<'a>, parse_func: fn(&mut Self, &U) -> ParseResult<'a, T>, param: &U) -> ParseResult<'a, Vec<T>> {
-        if let Some(_) = self.advance_if(&[start])? {
+        if self.advance_if(&[start])?.is_some() {
             self.parse_list(end, delim, parse_func, param)
         }
         else {
@@ -121,7 +213,7 @@ impl<'a> Parser<'a> {
     fn parse_list<T, U>(&mut self, end: TokenKind<'a>, delim: TokenKind<'a>, parse_func: fn(&mut Self, &U) -> ParseResult<'a, T>, param: &U) -> ParseResult<'a, Vec<T>> {
         let mut elems = vec![];
         let delims = [end.clone(), delim];
-        while self.current_token.kind() != &end {
+        while self.advance_if(&delims[..1])?.is_none() {
             elems.push(parse_func(self, param)?); 
             
             if self.expect(&delims)?.kind() == &end {
@@ -144,17 +236,19 @@ pub type ParseResult<'a, T> = Result<T, Located<ParseError<'a>>>;
 
 #[derive(Clone, Debug)]
 pub enum ParseError<'a> {
-    NotImplemented,
     Generic(String),
     UnexpectedEof(Vec<TokenKind<'a>>),
     UnexpectedToken(String, Vec<TokenKind<'a>>),
     Redefinition(Location, String),
     InvalidStmt(String, String),
     WrongNumOfPatterns(usize),
+    WrongNumOfValues(usize),
+    InvalidLiteral(&'static str, String),
     NoResultValue,
     RequireAfterDecl,
     ExprWithoutSideEffect,
     MissingBranch(String),
+    ErrorLimit(usize),
 }
 
 impl<'a> ParseError<'a> {
@@ -162,6 +256,7 @@ impl<'a> ParseError<'a> {
         match self {
             Self::RequireAfterDecl => Severity::Warning,
             Self::ExprWithoutSideEffect => Severity::Warning,
+            Self::ErrorLimit(_) => Severity::Hint,
             _ => Severity::Error
         }
     }
@@ -169,6 +264,9 @@ impl<'a> ParseError<'a> {
     fn hint(&self) -> Option<String> {
         match self {
             Self::RequireAfterDecl => Some("Move this over the first declaration.".into()),
+            Self::ErrorLimit(_) => Some("Change the limit with `--max-errors`.".into()),
+            Self::InvalidLiteral("character", _) => Some("Character literals contain a single character or an escape sequence like `\\n` or `*n`.".into()),
+            Self::InvalidLiteral("string", _) => Some("Escape sequences are `n`, `t`, `r`, `s`, `b`, `e`, `0`, `\\`, `*`, `'` and `\"` after a `\\` or `*`.".into()),
             _ => None
         }
     }
@@ -184,6 +282,17 @@ impl<'a> ParseError<'a> {
     }
 }
 
+// sorts record fields by name, so records with the same fields are the same type
+fn sort_fields<'a, T>(mut fields: Vec<(Location, String, T)>) -> ParseResult<'a, Vec<(String, T)>> {
+    fields.sort_by(|(_, a, _), (_, b, _)| a.cmp(b));
+    if let Some(pair) = fields.windows(2).find(|pair| pair[0].1 == pair[1].1) {
+        let (first, (loc, ident, _)) = (&pair[0].0, &pair[1]);
+        return Err(ParseError::Redefinition(first.clone(), ident.clone()).with_location(loc.clone()))
+    }
+
+    Ok(fields.into_iter().map(|(_, ident, value)| (ident, value)).collect())
+}
+
 fn tokens_to_string(list: &[TokenKind]) -> String {
     if list.len() == 1 {
         format!{"`{}`", list[0]}
@@ -201,27 +310,29 @@ fn tokens_to_string(list: &[TokenKind]) -> String {
 
 impl<'a> WithLocation for ParseError<'a> {}
 
-impl<'a> ToString for ParseError<'a> {
-    fn to_string(&self) -> String {
-        match self {
-            Self::NotImplemented => "<internal> Not Implemented.".into(),
+impl<'a> Display for ParseError<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message: String = match self {
             Self::Generic(err) => err.clone(),
             Self::UnexpectedEof(tk) => format!("Unexpected end of file; Expected {}.", tokens_to_string(tk)),
             Self::UnexpectedToken(got, want) => format!("Unexpected token `{got}`; Expected {}.", tokens_to_string(want)),
             Self::Redefinition(_, ident) => format!("Redefinition of `{ident}`."),
-            Self::RequireAfterDecl => format!("Encountered `require` after declarations."),
+            Self::RequireAfterDecl => "Encountered `require` after declarations.".to_string(),
             Self::InvalidStmt(stmt, err) => format!("Encountered `{stmt}` statement outside of `{err}`."),
-            Self::NoResultValue => format!("No `resultis` statement found in `valof` body."),
-            Self::ExprWithoutSideEffect => format!("Resuld of expression is unused."),
+            Self::NoResultValue => "No `resultis` statement found in `valof` body.".to_string(),
+            Self::ExprWithoutSideEffect => "Resuld of expression is unused.".to_string(),
             Self::WrongNumOfPatterns(expect) => format!("Wrong number of patterns, expected {expect}."),
-            Self::MissingBranch(expr) => format!("Expect at least one branch in `{expr}` expression.")
-        }
+            Self::WrongNumOfValues(expect) => format!("Wrong number of assigned values, expected {expect}."),
+            Self::InvalidLiteral(kind, literal) => format!("Invalid {kind} literal `{literal}`."),
+            Self::MissingBranch(expr) => format!("Expect at least one branch in `{expr}` expression."),
+            Self::ErrorLimit(limit) => format!("Stopped parsing after {limit} errors.")
+        };
+        f.write_str(&message)
     }
 }
 
-impl<'a> IntoCompilerError for ParseError<'a> {}
-impl<'a> Into<CompilerError> for ParseError<'a> {
-    fn into(self) -> CompilerError {
-        CompilerError::new(self.severity(), self.to_string(), self.hint(), self.additional())   
+impl<'a> From<ParseError<'a>> for CompilerError {
+    fn from(value: ParseError<'a>) -> Self {
+        CompilerError::new(value.severity(), value.to_string(), value.hint(), value.additional())
     }
 }
@@ -1,6 +1,6 @@
-use crate::{ast::{pattern::{Pattern, PatternTerm}, expr::Expr}, source_file::{WithLocation, Located}, token::TokenKind};
+use crate::{ast::{pattern::{Pattern, PatternTerm}, expr::Expr}, source_file::{WithLocation, Located, Location}, token::TokenKind};
 
-use super::{Parser, ParseResult, ParseError, stmt::StmtContext};
+use super::{Parser, ParseResult, stmt::StmtContext, sort_fields};
 
 #[derive(PartialEq, PartialOrd)]
 enum PatternPrecedence {
@@ -73,6 +73,7 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::List(inner))
                 }
             }
+            TokenKind::LBrace => self.parse_record_pattern(),
             TokenKind::LParen => {
                 self.advance()?;
                 let pattern = self.parse_pattern()?;
@@ -87,10 +88,10 @@ impl<'a> Parser<'a> {
             TokenKind::Le => self.parse_prefix_pattern_term(PatternTerm::Le),
             TokenKind::Range => self.advance().map(|_| Pattern::Remaining),
             _ => {
-                let expr = self.parse_expr(&StmtContext::Empty)?;
+                let expr = self.parse_pattern_expr(&StmtContext::Empty)?;
                 if self.current().kind() == &TokenKind::Range {
                     self.advance()?;
-                    Ok(Pattern::Term(PatternTerm::Range(expr, self.parse_expr(&StmtContext::Empty)?)))
+                    Ok(Pattern::Term(PatternTerm::Range(expr, self.parse_pattern_expr(&StmtContext::Empty)?)))
                 }
                 else {
                     Ok(Pattern::Term(PatternTerm::Basic(expr)))
@@ -99,9 +100,30 @@ impl<'a> Parser<'a> {
         }.map(|pattern| pattern.with_location(loc))
     }
 
+    // `{ x = A, y }` matches the field `x` against `A` and binds the field `y` to `y`
+    fn parse_record_field_pattern(&mut self, _: &()) -> ParseResult<'a, (Location, String, Located<Pattern>)> {
+        let loc = self.current().location().clone();
+        let ident = self.expect_ident()?;
+        let pattern = if self.advance_if(&[TokenKind::Eq])?.is_some() {
+            self.parse_pattern()?
+        }
+        else {
+            Pattern::Query(ident.clone()).with_location(loc.clone())
+        };
+
+        Ok((loc, ident, pattern))
+    }
+
+    fn parse_record_pattern(&mut self) -> ParseResult<'a, Pattern> {
+        self.expect(&[TokenKind::LBrace])?;
+        let fields = self.parse_list(TokenKind::RBrace, TokenKind::Comma, Self::parse_record_field_pattern, &())?;
+
+        Ok(Pattern::Record(sort_fields(fields)?))
+    }
+
     fn parse_prefix_pattern_term(&mut self, init: fn(Expr) -> PatternTerm) -> ParseResult<'a, Pattern> {
         self.advance()?;
-        let expr = self.parse_expr(&StmtContext::Empty)?;
+        let expr = self.parse_pattern_expr(&StmtContext::Empty)?;
         Ok(Pattern::Term(init(expr)))
     }
 
@@ -8,6 +8,10 @@ use crate::{
 
 use super::{Parser, ParseResult, ParseError};
 
+// builds a `match` or `every` statement from its arguments and branches
+type MatchStmtInit = fn(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Stmt>)>) -> StmtKind;
+type BinaryExprInit = fn(Box<Expr>, Box<Expr>) -> ExprKind;
+
 pub(super) enum StmtContext<'a> {
     ValOf(&'a RefCell<Option<Option<TypeIndex>>>, &'a StmtContext<'a>),
     Block(&'a StmtContext<'a>),
@@ -36,14 +40,14 @@ impl<'a> StmtContext<'a> {
     pub(super) fn last_valof_type(&self) -> Option<&RefCell<Option<Option<TypeIndex>>>> {
         match self {
             Self::ValOf(typ, _) => Some(typ),
-            _ => self.get_outer().map(|ctx| ctx.last_valof_type()).flatten()
+            _ => self.get_outer().and_then(|ctx| ctx.last_valof_type())
         }
     }
 
     pub(super) fn in_function(&self) -> Option<&'a Vec<Param>> {
         match self {
             Self::Function(params) => Some(params),
-            _ => self.get_outer().map(|ctx| ctx.in_function()).flatten()
+            _ => self.get_outer().and_then(|ctx| ctx.in_function())
         }
     }
 
@@ -68,7 +72,7 @@ impl<'a> StmtContext<'a> {
     fn in_switchon(&self) -> Option<(&'a RefCell<Option<Location>>, &'a Option<TypeIndex>)> {
         match self {
             Self::SwitchOn(default_case, cond_typ, _) => Some((default_case, cond_typ)),
-            _ => self.get_outer().map(|ctx| ctx.in_switchon()).flatten()
+            _ => self.get_outer().and_then(|ctx| ctx.in_switchon())
         }
     }
 
@@ -78,11 +82,38 @@ impl<'a> StmtContext<'a> {
             _ => self.get_outer().map(|ctx| ctx.in_match()).unwrap_or(false)
         }
     }
+
+    // whether a statement is part of a `match` or `every` branch outside of a block,
+    // where `ident :` starts the next branch instead of a label
+    fn in_branch_body(&self) -> bool {
+        match self {
+            Self::Match(_) => true,
+            Self::Block(_) | Self::ValOf(..) | Self::Function(_) | Self::Empty => false,
+            _ => self.get_outer().map(|ctx| ctx.in_branch_body()).unwrap_or(false)
+        }
+    }
+}
+
+fn compound_assign_operator(kind: &TokenKind) -> Option<BinaryExprInit> {
+    match kind {
+        TokenKind::PlusAssign => Some(ExprKind::Add),
+        TokenKind::MinusAssign => Some(ExprKind::Sub),
+        TokenKind::StarAssign => Some(ExprKind::Mul),
+        TokenKind::SlashAssign => Some(ExprKind::Div),
+        TokenKind::ModAssign => Some(ExprKind::Mod),
+        TokenKind::LogAndAssign => Some(ExprKind::And),
+        TokenKind::LogOrAssign => Some(ExprKind::Or),
+        TokenKind::XOrAssign => Some(ExprKind::XOr),
+        TokenKind::LShiftAssign => Some(ExprKind::LShift),
+        TokenKind::RShiftAssign => Some(ExprKind::RShift),
+        _ => None
+    }
 }
 
 impl<'a> Parser<'a> {
     pub(super) fn parse_stmt(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
-        let stmt = match self.current().kind() {
+        let loop_jumps = self.loop_jumps.len();
+        let mut stmt = match self.current().kind() {
             TokenKind::LBrace => self.parse_block(context),
             TokenKind::ResultIs => self.parse_resultis(context),
             TokenKind::Return => self.parse_return(context),
@@ -90,6 +121,7 @@ impl<'a> Parser<'a> {
             TokenKind::Unless => self.parse_unless(context),
             TokenKind::While => self.parse_while(context, false),
             TokenKind::Until => self.parse_while(context, true),
+            TokenKind::Loop => self.parse_loop(context),
             TokenKind::For => self.parse_for(context),
             TokenKind::SwitchOn => self.parse_switchon(context),
             TokenKind::Case => self.parse_case(context),
@@ -98,14 +130,25 @@ impl<'a> Parser<'a> {
             TokenKind::Every => self.parse_match_stmt(context, StmtKind::Every),
             TokenKind::Next => self.parse_next_break(context, false),
             TokenKind::Break => self.parse_next_break(context, true),
+            TokenKind::Finish => self.parse_finish(context),
+            TokenKind::Goto => self.parse_goto(context),
             TokenKind::Let => self.parse_let_binding(context),
             TokenKind::Semicolon => {
                 let loc = self.advance()?.location().clone();
                 Ok(Stmt::new(loc, StmtKind::Nop))
             }
+            TokenKind::Skip => {
+                let loc = self.advance()?.location().clone();
+                self.semicolon_if_required(context)?;
+                Ok(Stmt::new(loc, StmtKind::Nop))
+            }
             _ => self.parse_expr_stmt(context),
         }?;
 
+        while let TokenKind::Repeat | TokenKind::RepeatWhile | TokenKind::RepeatUntil = self.current().kind() {
+            stmt = self.parse_repeat(context, stmt, loop_jumps)?;
+        }
+
         if let TokenKind::Compound = self.current().kind() {
             self.parse_compound(context, stmt)
         }
@@ -134,7 +177,17 @@ impl<'a> Parser<'a> {
         let mut stmts = vec![];
 
         while self.current().kind() != &TokenKind::RBrace {
-            stmts.push(self.parse_stmt(&StmtContext::Block(context))?)
+            // a missing `}` is reported at the next declaration instead of parsing it as statements
+            match self.current().kind() {
+                TokenKind::Eof => return Err(ParseError::UnexpectedEof(vec![TokenKind::RBrace]).with_location(self.current().location().clone())),
+                TokenKind::And | TokenKind::Section => return self.unexpected(&[TokenKind::RBrace]),
+                _ => ()
+            }
+
+            match self.parse_stmt(&StmtContext::Block(context)) {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => self.recover_stmt(err)?
+            }
         }
 
         self.advance()?;
@@ -142,6 +195,16 @@ impl<'a> Parser<'a> {
         Ok(Stmt::new(loc, StmtKind::Block(stmts)))
     }
 
+    // reports `err` and continues after the end of the broken statement
+    fn recover_stmt(&mut self, err: Located<ParseError<'a>>) -> ParseResult<'a, ()> {
+        self.report(err)?;
+        self.synchronize(&[TokenKind::Semicolon, TokenKind::RBrace, TokenKind::Let, TokenKind::And, TokenKind::Section])?;
+        if let Err(err) = self.advance_if(&[TokenKind::Semicolon]) {
+            self.report(err)?;
+        }
+        Ok(())
+    }
+
     fn parse_resultis(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
         let loc = self.expect(&[TokenKind::ResultIs])?.location().clone();
         
@@ -152,11 +215,11 @@ impl<'a> Parser<'a> {
                     .with_location(loc.clone())
             )?; 
 
-        let vt = valof_typ.borrow().clone();
+        let vt = *valof_typ.borrow();
         let expr = match vt {
             Some(vt) if &vt != expr.typ() => Expr::new(loc.clone(), vt, ExprKind::ImplicitCast(Box::new(expr))),
             None => {
-                *valof_typ.borrow_mut() = Some(expr.typ().clone());
+                *valof_typ.borrow_mut() = Some(*expr.typ());
                 expr
             }
             _ => expr
@@ -231,6 +294,46 @@ impl<'a> Parser<'a> {
         Ok(Stmt::new(loc, kind))
     }
 
+    // `loop C` runs `C` until it breaks out
+    fn parse_loop(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
+        let loc = self.expect(&[TokenKind::Loop])?.location().clone();
+        let body = self.parse_stmt(&StmtContext::Loop(context))?;
+        Ok(Stmt::new(loc, StmtKind::Loop(Box::new(body))))
+    }
+
+    // `C repeat`, `C repeatwhile E` or `C repeatuntil E`, binding to the statement directly before it
+    fn parse_repeat(&mut self, context: &StmtContext, body: Stmt, loop_jumps: usize) -> ParseResult<'a, Stmt> {
+        let loc = body.location().clone();
+        let (is_repeat, is_while) = match self.advance()?.kind() {
+            TokenKind::Repeat => (true, false),
+            kind => (false, kind == &TokenKind::RepeatWhile)
+        };
+
+        // `break` and `next` in the body were parsed before it turned out to be a loop
+        self.loop_jumps.truncate(loop_jumps);
+
+        let kind = if is_repeat {
+            StmtKind::Loop(Box::new(body))
+        }
+        else {
+            let mut condition = self.parse_expr(context)?;
+            let bool_typ = self.get_type(TypeKind::Bool);
+            if condition.typ() != &Some(bool_typ) {
+                condition = condition.implicit_cast(bool_typ);
+            }
+
+            if is_while {
+                StmtKind::RepeatWhile(Box::new(body), Box::new(condition))
+            }
+            else {
+                StmtKind::RepeatUntil(Box::new(body), Box::new(condition))
+            }
+        };
+
+        self.semicolon_if_required(context)?;
+        Ok(Stmt::new(loc, kind))
+    }
+
     fn parse_for(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
         let loc = self.expect(&[TokenKind::For])?.location().clone();
 
@@ -328,7 +431,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_match_stmt(&mut self, context: &StmtContext, init: fn(Vec<Expr>, Vec<(Vec<Located<Pattern>>, Box<Stmt>)>) -> StmtKind) -> ParseResult<'a, Stmt> {
+    fn parse_match_stmt(&mut self, context: &StmtContext, init: MatchStmtInit) -> ParseResult<'a, Stmt> {
         let loc = self.advance()?.location().clone();
 
         let args = if self.current().kind() != &TokenKind::LParen {
@@ -358,6 +461,18 @@ impl<'a> Parser<'a> {
     fn parse_expr_stmt(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
         let loc = self.current().location().clone();
         let expr = self.parse_expr(context)?;
+
+        if let ExprKind::Ident(label) = expr.kind() && self.current().kind() == &TokenKind::Colon && !context.in_branch_body() {
+            let label = label.clone();
+            return self.parse_label(context, loc, label)
+        }
+        if let TokenKind::Assign | TokenKind::Comma = self.current().kind() {
+            return self.parse_assignment(context, loc, expr)
+        }
+        if let Some(op_init) = compound_assign_operator(self.current().kind()) {
+            return self.parse_compound_assignment(context, expr, op_init)
+        }
+
         if !expr.has_sideeffect() {
             self.push_warning(ParseError::ExprWithoutSideEffect.with_location(loc.clone()))
         }
@@ -367,8 +482,60 @@ impl<'a> Parser<'a> {
         Ok(Stmt::new(loc, StmtKind::Expr(Box::new(expr))))
     }
 
+    // `L: C`, the labelled statement may be left out at the end of a block
+    fn parse_label(&mut self, context: &StmtContext, loc: Location, label: String) -> ParseResult<'a, Stmt> {
+        self.expect(&[TokenKind::Colon])?;
+        let body = if self.current().kind() == &TokenKind::RBrace {
+            Stmt::new(self.current().location().clone(), StmtKind::Nop)
+        }
+        else {
+            self.parse_stmt(context)?
+        };
+        Ok(Stmt::new(loc, StmtKind::Label(label, Box::new(body))))
+    }
+
+    fn parse_assignment(&mut self, context: &StmtContext, loc: Location, target: Expr) -> ParseResult<'a, Stmt> {
+        let mut targets = vec![target];
+        while self.advance_if(&[TokenKind::Comma])?.is_some() {
+            targets.push(self.parse_expr(context)?);
+        }
+
+        self.expect(&[TokenKind::Assign])?;
+
+        let mut values = vec![self.parse_expr(context)?];
+        while self.advance_if(&[TokenKind::Comma])?.is_some() {
+            values.push(self.parse_expr(context)?);
+        }
+
+        self.semicolon_if_required(context)?;
+
+        if values.len() != targets.len() {
+            Err(ParseError::WrongNumOfValues(targets.len()).with_location(loc))
+        }
+        else {
+            Ok(Stmt::new(loc, StmtKind::Assign(targets, values)))
+        }
+    }
+
+    fn parse_compound_assignment(&mut self, context: &StmtContext, target: Expr, op_init: fn(Box<Expr>, Box<Expr>) -> ExprKind) -> ParseResult<'a, Stmt> {
+        let loc = self.advance()?.location().clone();
+        let mut value = self.parse_expr(context)?;
+
+        let typ = *target.typ();
+        if let Some(typ) = typ && Some(typ) != *value.typ() {
+            value = value.implicit_cast(typ);
+        }
+
+        self.semicolon_if_required(context)?;
+
+        let operation = Expr::new(loc.clone(), typ, op_init(Box::new(target), Box::new(value)));
+        Ok(Stmt::new(loc, StmtKind::CompoundAssign(Box::new(operation))))
+    }
+
+    // the semicolon after a statement in a block comes after a `repeat` following it
     fn semicolon_if_required(&mut self, context: &StmtContext) -> ParseResult<'a, ()> {
-        if context.require_semicolon() {
+        let repeats = [TokenKind::Repeat, TokenKind::RepeatWhile, TokenKind::RepeatUntil];
+        if context.require_semicolon() && !repeats.contains(self.current().kind()) {
             self.expect(&[TokenKind::Semicolon])?;
         }
         Ok(())
@@ -377,15 +544,30 @@ impl<'a> Parser<'a> {
     fn parse_next_break(&mut self, context: &StmtContext, is_break: bool) -> ParseResult<'a, Stmt> {
         let loc = self.advance()?.location().clone();
         self.semicolon_if_required(context)?;
-        if !context.in_loop() || !context.in_match() || context.in_switchon().is_none() {
-            Err(
+        if !context.in_loop() && !context.in_match() && context.in_switchon().is_none() {
+            self.loop_jumps.push(
                 ParseError::InvalidStmt(if is_break { "break" } else { "next" }.into(), "loop, `match`, `every` or `switchon`".into())
-                    .with_location(loc)
-            )
-        }
-        else {
-            Ok(Stmt::new(loc, if is_break { StmtKind::Break } else { StmtKind::Next }))
+                    .with_location(loc.clone())
+            );
         }
+        Ok(Stmt::new(loc, if is_break { StmtKind::Break } else { StmtKind::Next }))
+    }
+
+    fn parse_goto(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
+        let loc = self.expect(&[TokenKind::Goto])?.location().clone();
+        let label = match self.current().kind() {
+            TokenKind::Ident(label) => label.to_string(),
+            _ => return self.unexpected(&[TokenKind::Ident("label")])
+        };
+        self.advance()?;
+        self.semicolon_if_required(context)?;
+        Ok(Stmt::new(loc, StmtKind::Goto(label)))
+    }
+
+    fn parse_finish(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
+        let loc = self.expect(&[TokenKind::Finish])?.location().clone();
+        self.semicolon_if_required(context)?;
+        Ok(Stmt::new(loc, StmtKind::Finish))
     }
 
     fn parse_let_binding(&mut self, context: &StmtContext) -> ParseResult<'a, Stmt> {
@@ -407,7 +589,7 @@ impl<'a> Parser<'a> {
         else {
             Ok(Stmt::new(loc, StmtKind::Binding(
                         patterns.into_iter()
-                                .zip(exprs.into_iter())
+                                .zip(exprs)
                                 .collect()
             )))
         }
@@ -1,20 +1,39 @@
 use crate::{
-    ast::types::{TypeIndex, TypeKind, Type, SumVariant},
-    token::TokenKind, source_file::WithLocation
+    ast::types::{TypeIndex, TypeKind, Type, SumVariant, RecordField},
+    token::TokenKind, source_file::{WithLocation, Location}
 };
 
-use super::{Parser, ParseResult, ParseError, stmt::StmtContext};
+use super::{Parser, ParseResult, ParseError, stmt::StmtContext, sort_fields};
 
 impl<'a> Parser<'a> {
     pub(super) fn parse_type_alias(&mut self) -> ParseResult<'a, ()> {
         self.expect(&[TokenKind::Type])?;
         let loc = self.current().location().clone();
         let alias = self.expect_ident()?; 
+        self.type_params = self.parse_optional_list(TokenKind::LParen, TokenKind::RParen, TokenKind::Comma, Self::parse_type_param_ident, &())?;
 
         self.expect(&[TokenKind::Eq])?;
 
-        let typ = self.parse_type()?;
+        let typ = match self.current().kind().clone() {
+            TokenKind::Ident(ident) => {
+                let (ident, loc) = (ident.to_string(), self.current().location().clone());
+                self.advance()?;
+                self.parse_named_type(ident, loc, true)
+            }
+            _ => self.parse_type()
+        };
+        let type_params = std::mem::take(&mut self.type_params);
+        let typ = typ?;
+
         let mut ast = self.ast.lock().unwrap();
+        // generic type aliases are bound to their body together with the type parameters
+        let typ = if type_params.is_empty() {
+            typ
+        }
+        else {
+            ast.types_mut().define(Type::new(Some(loc.clone()), TypeKind::Generic(type_params, typ)))
+        };
+
         if let Some(id) = ast.types().find_alias(&alias) {
             let existing = ast.types_mut().get_mut(id).unwrap();
             if let Some(existing_loc) = existing.location() {
@@ -35,22 +54,13 @@ impl<'a> Parser<'a> {
     pub(super) fn parse_type(&mut self) -> ParseResult<'a, TypeIndex> {
         match self.current().kind().clone() {
             TokenKind::Ident(ident) => {
-                let ident = ident.to_string();
+                let (ident, loc) = (ident.to_string(), self.current().location().clone());
                 self.advance()?;
-                if [TokenKind::LParen, TokenKind::Colon].contains(self.current().kind()) {
-                    self.parse_sum_type(ident)
-                }
-                else {
-                    Ok(self.type_ident(ident))
-                }
+                self.parse_named_type(ident, loc, false)
             }
-            TokenKind::LParen => {
-                self.advance()?;
-                let typ = self.parse_type()?;
-                self.expect(&[TokenKind::RParen])?;
-                Ok(typ)
-            },
+            TokenKind::LParen => self.parse_parenthesized_type(),
             TokenKind::LBracket => self.parse_array_type(),
+            TokenKind::LBrace => self.parse_record_type(),
             TokenKind::LogAnd => {
                 self.advance()?;
                 let inner_typ = self.parse_type()?;
@@ -64,6 +74,62 @@ impl<'a> Parser<'a> {
         self.parse_type()
     }
 
+    pub(super) fn parse_type_param_ident(&mut self, _: &()) -> ParseResult<'a, String> {
+        let loc = self.current().location().clone();
+        let ident = self.expect_ident()?;
+        if self.type_params.contains(&ident) {
+            return Err(ParseError::Generic(format!("Duplicate type parameter `{ident}`.")).with_location(loc))
+        }
+        self.type_params.push(ident.clone());
+        Ok(ident)
+    }
+
+    // `T`, the sum type `A(T) : B` or the instance `Option(T)` of a generic type alias;
+    // a single variant with fields is only a sum type directly in a type alias
+    fn parse_named_type(&mut self, ident: String, loc: Location, is_alias: bool) -> ParseResult<'a, TypeIndex> {
+        match self.current().kind() {
+            TokenKind::LParen => {
+                let args = self.parse_optional_list(TokenKind::LParen, TokenKind::RParen, TokenKind::Comma, Self::parse_type_param, &())?;
+                let is_generic = self.ast.lock().unwrap().types().is_generic(&ident);
+                if self.current().kind() == &TokenKind::Colon || (is_alias && !is_generic) {
+                    self.parse_sum_type(SumVariant::Basic(ident, args))
+                }
+                else {
+                    Ok(self.instance(ident, args, loc))
+                }
+            }
+            TokenKind::Colon => self.parse_sum_type(SumVariant::Basic(ident, vec![])),
+            _ => Ok(self.type_ident(ident))
+        }
+    }
+
+    // instances are located at their first use, for diagnostics about their type arguments
+    fn instance(&self, ident: String, args: Vec<TypeIndex>, loc: Location) -> TypeIndex {
+        let typ = self.get_type(TypeKind::Instance(ident, args, None));
+        let mut ast = self.ast.lock().unwrap();
+        let instance = ast.types_mut().get_mut(typ).unwrap();
+        if instance.location().is_none() {
+            instance.set_location(loc);
+        }
+        typ
+    }
+
+    // `(T)` or the function type `(A, B) -> R`
+    fn parse_parenthesized_type(&mut self) -> ParseResult<'a, TypeIndex> {
+        self.expect(&[TokenKind::LParen])?;
+        let params = self.parse_list(TokenKind::RParen, TokenKind::Comma, Self::parse_type_param, &())?;
+
+        if self.advance_if(&[TokenKind::Condition])?.is_some() {
+            let return_type = self.parse_type()?;
+            return Ok(self.get_type(TypeKind::Function(params, return_type)))
+        }
+
+        match params[..] {
+            [typ] => Ok(typ),
+            _ => self.unexpected(&[TokenKind::Condition])
+        }
+    }
+
     fn parse_sum_variant(&mut self, ident: String) -> ParseResult<'a, SumVariant> {
         Ok(SumVariant::Basic(
             ident,
@@ -71,8 +137,8 @@ impl<'a> Parser<'a> {
         ))
     }
 
-    fn parse_sum_type(&mut self, first: String) -> ParseResult<'a, TypeIndex> {
-        let mut variants = vec![self.parse_sum_variant(first)?];
+    fn parse_sum_type(&mut self, first: SumVariant) -> ParseResult<'a, TypeIndex> {
+        let mut variants = vec![first];
 
         while let TokenKind::Colon = self.current().kind() {
             self.advance()?;
@@ -84,10 +150,31 @@ impl<'a> Parser<'a> {
         Ok(self.get_type(TypeKind::Sum(variants)))
     }
 
+    fn parse_record_field_type(&mut self, _: &()) -> ParseResult<'a, (Location, String, TypeIndex)> {
+        let loc = self.current().location().clone();
+        let ident = self.expect_ident()?;
+        self.expect(&[TokenKind::Of])?;
+        Ok((loc, ident, self.parse_type()?))
+    }
+
+    // `{ x :: Int32, y :: Int32 }`
+    fn parse_record_type(&mut self) -> ParseResult<'a, TypeIndex> {
+        self.expect(&[TokenKind::LBrace])?;
+        let fields = self.parse_list(TokenKind::RBrace, TokenKind::Comma, Self::parse_record_field_type, &())?;
+
+        let fields = sort_fields(fields)?.into_iter()
+            .map(|(ident, typ)| RecordField::new(ident, typ))
+            .collect();
+        Ok(self.get_type(TypeKind::Record(fields)))
+    }
+
     fn type_ident(&self, ident: String) -> TypeIndex {
         let mut ast = self.ast.lock().unwrap(); 
         let types = ast.types_mut();
-        if let Some(typ) = types.builtin_by_ident(&ident) {
+        if self.type_params.contains(&ident) {
+            types.get_or_define(TypeKind::Param(ident))
+        }
+        else if let Some(typ) = types.builtin_by_ident(&ident) {
             typ
         }
         else if let Some(typ) = types.find_alias(&ident) {
@@ -23,12 +23,16 @@ impl SourceFile {
 
         file.read_to_string(&mut contents)?;
 
-        Ok(Self {
+        Ok(Self::new(path, contents, id))
+    }
+
+    pub fn new(path: String, contents: String, id: SourceFileId) -> Self {
+        Self {
             id,
             path,
             lines: contents.split('\n').map(|e| e.to_string()).collect(),
             contents
-        })
+        }
     }
 
     pub fn contents(&self) -> &String {
@@ -48,7 +52,8 @@ impl SourceFile {
     }
 }
 
-#[derive(Clone, PartialEq)]
+// ordered by file, then by position in the file
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Location {
     source_file_id: SourceFileId,
     line: u32,
@@ -66,6 +71,15 @@ impl Location {
         }
     }
 
+    // `width` characters starting `offset` characters after the start of this location
+    pub fn sub_location(&self, offset: usize, width: usize) -> Self {
+        Self {
+            column: self.column + offset as u32,
+            width: width as u32,
+            ..self.clone()
+        }
+    }
+
     pub fn set_width(&mut self, width: usize) {
         self.width = width as u32;
     }
@@ -6,7 +6,8 @@ use std::{
 
 use crate::{source_file::{SourceFile, Location}, token::{Token, TokenKind}};
 
-const ESCAPE_CHAR: char = '\\';
+// both `\n` and the BCPL style `*n` escape the next character
+const ESCAPE_CHARS: &[char] = &['\\', '*'];
 
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq)]
@@ -42,9 +43,13 @@ impl<'a> Lexer<'a> {
     }
 
     fn next_char(&mut self) {
+        let Some(ch) = self.iter.next()
+        else {
+            return
+        };
         self.offset += 1;
 
-        if self.iter.next().unwrap() == '\n' {
+        if ch == '\n' {
             self.line += 1;
             self.column = 0;
         }
@@ -116,8 +121,10 @@ impl<'a> Lexer<'a> {
         }
 
         self.skip_digits(base);
-        
-        let mut is_float = self.iter.peek().copied().unwrap_or('\0') == '.';
+
+        // `1..9` is a range, not a float literal
+        let mut lookahead = self.iter.clone();
+        let mut is_float = lookahead.next() == Some('.') && lookahead.next().is_some_and(|ch| ch.is_ascii_digit());
         if is_float {
             self.next_char();
             self.skip_digits(base);
@@ -134,15 +141,15 @@ impl<'a> Lexer<'a> {
 
             if "+-".contains(self.iter.peek().copied().unwrap_or('\0')) {
                 self.next_char();
-                self.skip_digits(base);
             }
+            self.skip_digits(base);
         }
 
         let exponent_end = self.offset;
 
         (
             if is_float {
-                if base != Base::Decimal {
+                if base == Base::Decimal {
                     TokenKind::FloatLit(
                         &self.source_file.contents()[start..end], 
                         has_exponent.then(|| &self.source_file.contents()[exponent_start..exponent_end])
@@ -158,12 +165,13 @@ impl<'a> Lexer<'a> {
         )
     }
 
-    fn parse_string_lit(&mut self, quote: char) -> &'a str {
+    // `None` if the literal isn't closed before the end of the file
+    fn parse_string_lit(&mut self, quote: char) -> Option<&'a str> {
         let start = self.offset;
 
         while let Some(&ch) = self.iter.peek() && ch != quote {
             self.next_char();
-            if ch == ESCAPE_CHAR {
+            if ESCAPE_CHARS.contains(&ch) {
                 self.next_char();
             }
             else if ch == '\n' {
@@ -172,8 +180,22 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        self.iter.peek()?;
         self.next_char(); // expect `"`
-        &self.source_file.contents()[start..self.offset - 1]
+        Some(&self.source_file.contents()[start..self.offset - 1])
+    }
+
+    // binary operators directly followed by `:=` form compound assignments
+    fn operator(&mut self, loc: Location, width: usize, kind: TokenKind<'a>) -> Token<'a> {
+        let mut lookahead = self.iter.clone();
+        if let Some(assign) = kind.with_assign() && lookahead.next() == Some(':') && lookahead.next() == Some('=') {
+            self.next_char();
+            self.next_char();
+            Token::with_width(loc, width + 2, assign)
+        }
+        else {
+            Token::with_width(loc, width, kind)
+        }
     }
 }
 
@@ -193,7 +215,13 @@ impl<'a> Iterator for Lexer<'a> {
         let ch = next.unwrap();
         match ch {
             _ if ch.is_alphabetic() || ch == '_' => {
-                Some(Token::ident(loc, self.parse_ident()))
+                let token = Token::ident(loc.clone(), self.parse_ident());
+                if token.kind() == &TokenKind::Mod {
+                    Some(self.operator(loc, 3, TokenKind::Mod))
+                }
+                else {
+                    Some(token)
+                }
             }
             _ if ch.is_numeric() => {
                 let (kind, width) = self.parse_number();
@@ -201,25 +229,31 @@ impl<'a> Iterator for Lexer<'a> {
             }
             '"' => {
                 self.next_char();
-                Some(Token::string_lit(loc, self.parse_string_lit('"')))
+                Some(match self.parse_string_lit('"') {
+                    Some(lit) => Token::string_lit(loc, lit),
+                    None => Token::error(loc, Some("unterminated string literal".into()))
+                })
             }
             '\'' => {
                 self.next_char();
-                Some(Token::char_lit(loc, self.parse_string_lit('"')))
+                Some(match self.parse_string_lit('\'') {
+                    Some(lit) => Token::char_lit(loc, lit),
+                    None => Token::error(loc, Some("unterminated character literal".into()))
+                })
             }
             '#' => {
                 self.next_char();
                 let atom = self.parse_ident();
-                if atom.len() == 0 {
+                if atom.is_empty() {
                     Some(Token::error(self.current_loc(), Some("expect atom identifier after `#`".into())))
                 }
                 else {
                     Some(Token::with_width(loc, atom.len() + 1, TokenKind::Atom(atom)))
                 }
             }
-            '(' | ')' | '{' | '}' | '[' | ']' | ';' | '+' | '*' | '!' | '?' | ',' | '@' | '|' | '&' | '^' => {
+            '(' | ')' | '{' | '}' | '[' | ']' | ';' | '+' | '*' | '!' | '%' | '?' | ',' | '@' | '|' | '&' | '^' => {
                 self.next_char();
-                Some(Token::new(loc, TokenKind::try_from(ch).expect("invalid character")))
+                Some(self.operator(loc, 1, TokenKind::try_from(ch).expect("invalid character")))
             }
             '-' => {
                 self.next_char();
@@ -228,7 +262,7 @@ impl<'a> Iterator for Lexer<'a> {
                     Some(Token::with_width(loc, 2, TokenKind::Condition))
                 }
                 else {
-                    Some(Token::new(loc, TokenKind::Minus))
+                    Some(self.operator(loc, 1, TokenKind::Minus))
                 }
             }
             '=' => {
@@ -254,7 +288,7 @@ impl<'a> Iterator for Lexer<'a> {
                     }
                 }
                 
-                Some(Token::new(loc, TokenKind::Slash))
+                Some(self.operator(loc, 1, TokenKind::Slash))
             }
             ':' => {
                 self.next_char();
@@ -275,10 +309,10 @@ impl<'a> Iterator for Lexer<'a> {
                 self.next_char();
                 if let Some(&ch) = self.iter.peek() && ch == '.' {
                     self.next_char();
-                    return Some(Token::with_width(loc, 2, TokenKind::Range))
+                    Some(Token::with_width(loc, 2, TokenKind::Range))
                 }
                 else {
-                    return Some(Token::new(loc, TokenKind::Period))
+                    Some(Token::new(loc, TokenKind::Period))
                 }
             }
             '<' => {
@@ -294,7 +328,7 @@ impl<'a> Iterator for Lexer<'a> {
                     }
                     else if ch == '<' {
                         self.next_char();
-                        return Some(Token::with_width(loc, 2, TokenKind::LShift))
+                        return Some(self.operator(loc, 2, TokenKind::LShift))
                     }
                 }
                 
@@ -309,7 +343,7 @@ impl<'a> Iterator for Lexer<'a> {
                     }
                     else if ch == '>' {
                         self.next_char();
-                        return Some(Token::with_width(loc, 2, TokenKind::RShift))
+                        return Some(self.operator(loc, 2, TokenKind::RShift))
                     }
                 }
 
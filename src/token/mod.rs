@@ -43,6 +43,7 @@ pub enum TokenKind<'a> {
     Arrow, // `=>`
     QuestionMark, // `?`
     Bang, // `!`
+    Percent, // `%`
     At, // `@`
     Compound, // `<>`
     Range, // `..`
@@ -66,6 +67,18 @@ pub enum TokenKind<'a> {
     LShift,
     RShift,
 
+    // Compound assignments, e.g. `+:=`
+    PlusAssign,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
+    ModAssign,
+    LogAndAssign,
+    LogOrAssign,
+    XOrAssign,
+    LShiftAssign,
+    RShiftAssign,
+
     // Keywords
     True,
     False,
@@ -75,6 +88,7 @@ pub enum TokenKind<'a> {
     ResultIs,
     Return,
     Break,
+    Goto,
     Next,
     If,
     Else,
@@ -82,6 +96,12 @@ pub enum TokenKind<'a> {
     While,
     For,
     Until,
+    Repeat,
+    RepeatWhile,
+    RepeatUntil,
+    Loop,
+    Finish,
+    Skip,
     SwitchOn,
     Match,
     Every,
@@ -93,6 +113,7 @@ pub enum TokenKind<'a> {
     By,
     Of,
     Be,
+    Fn,
     Section,
     Require,
     Global,
@@ -100,7 +121,29 @@ pub enum TokenKind<'a> {
     Static,
     Mod,
     Abs,
-    Type
+    Type,
+    Vec,
+    Table
+}
+
+impl<'a> TokenKind<'a> {
+    // compound assignment of a binary operator, e.g. `+:=` for `+`
+    pub fn with_assign(&self) -> Option<Self> {
+        use TokenKind as TK;
+        match self {
+            TK::Plus => Some(TK::PlusAssign),
+            TK::Minus => Some(TK::MinusAssign),
+            TK::Star => Some(TK::StarAssign),
+            TK::Slash => Some(TK::SlashAssign),
+            TK::Mod => Some(TK::ModAssign),
+            TK::LogAnd => Some(TK::LogAndAssign),
+            TK::LogOr => Some(TK::LogOrAssign),
+            TK::XOr => Some(TK::XOrAssign),
+            TK::LShift => Some(TK::LShiftAssign),
+            TK::RShift => Some(TK::RShiftAssign),
+            _ => None
+        }
+    }
 }
 
 impl<'a> Display for TokenKind<'a> {
@@ -112,6 +155,7 @@ impl<'a> Display for TokenKind<'a> {
             TK::IntegerLit(int) => return write!(f, "{int}"),
             TK::StringLit(s) => return write!(f, "\"{s}\""),
             TK::CharLit(ch) => return write!(f, "'{ch}'"),
+            TK::FloatLit(mantissa, Some(exponent)) => return write!(f, "{mantissa}e{exponent}"),
             _ => ()
         }
 
@@ -121,7 +165,7 @@ impl<'a> Display for TokenKind<'a> {
             
             TK::Ident(ident) => ident,
 
-            TK::FloatLit(a, _) => a, // TODO: print correctly
+            TK::FloatLit(mantissa, None) => mantissa,
 
             TK::LParen => "(",
             TK::RParen => ")",
@@ -139,6 +183,7 @@ impl<'a> Display for TokenKind<'a> {
             TK::Arrow => "=>",
             TK::QuestionMark => "?",
             TK::Bang => "!",
+            TK::Percent => "%",
             TK::At => "@",
             TK::Compound => "<>",
             TK::Range => "..",
@@ -162,6 +207,17 @@ impl<'a> Display for TokenKind<'a> {
             TK::LShift => "<<",
             TK::RShift => ">>",
 
+            TK::PlusAssign => "+:=",
+            TK::MinusAssign => "-:=",
+            TK::StarAssign => "*:=",
+            TK::SlashAssign => "/:=",
+            TK::ModAssign => "mod:=",
+            TK::LogAndAssign => "&:=",
+            TK::LogOrAssign => "|:=",
+            TK::XOrAssign => "^:=",
+            TK::LShiftAssign => "<<:=",
+            TK::RShiftAssign => ">>:=",
+
             TK::True => "true",
             TK::False => "false",
             TK::Let => "let",
@@ -171,12 +227,19 @@ impl<'a> Display for TokenKind<'a> {
             TK::Return => "return",
             TK::Next => "next",
             TK::Break => "break",
+            TK::Goto => "goto",
             TK::If => "if",
             TK::Else => "else",
             TK::Unless => "unless",
             TK::While => "while",
             TK::For => "for",
             TK::Until => "until",
+            TK::Repeat => "repeat",
+            TK::RepeatWhile => "repeatwhile",
+            TK::RepeatUntil => "repeatuntil",
+            TK::Loop => "loop",
+            TK::Finish => "finish",
+            TK::Skip => "skip",
             TK::SwitchOn => "switchon",
             TK::Match => "match",
             TK::Every => "every",
@@ -188,6 +251,7 @@ impl<'a> Display for TokenKind<'a> {
             TK::By => "by",
             TK::Of => "::",
             TK::Be => "be",
+            TK::Fn => "fn",
             TK::Section => "section",
             TK::Require => "require",
             TK::Global => "global",
@@ -195,6 +259,8 @@ impl<'a> Display for TokenKind<'a> {
             TK::Static => "static",
             TK::Mod => "mod",
             TK::Abs => "abs",
+            TK::Vec => "vec",
+            TK::Table => "table",
             _ => "<unexpected>"
         };
 
@@ -218,6 +284,7 @@ impl<'a> TryFrom<char> for TokenKind<'a> {
             '+' => Ok(TK::Plus),
             '*' => Ok(TK::Star),
             '!' => Ok(TK::Bang),
+            '%' => Ok(TK::Percent),
             '?' => Ok(TK::QuestionMark),
             ',' => Ok(TK::Comma),
             '@' => Ok(TK::At),
@@ -242,10 +309,17 @@ impl<'a> From<&'a str> for TokenKind<'a> {
             "return" => TK::Return,
             "next" => TK::Next,
             "break" => TK::Break,
+            "goto" => TK::Goto,
             "if" => TK::If,
             "else" => TK::Else,
             "unless" => TK::Unless,
             "until" => TK::Until,
+            "repeat" => TK::Repeat,
+            "repeatwhile" => TK::RepeatWhile,
+            "repeatuntil" => TK::RepeatUntil,
+            "loop" => TK::Loop,
+            "finish" => TK::Finish,
+            "skip" => TK::Skip,
             "switchon" => TK::SwitchOn,
             "match" => TK::Match,
             "every" => TK::Every,
@@ -258,6 +332,7 @@ impl<'a> From<&'a str> for TokenKind<'a> {
             "by" => TK::By,
             "of" => TK::Of,
             "be" => TK::Be,
+            "fn" => TK::Fn,
             "section" => TK::Section,
             "require" => TK::Require,
             "global" => TK::Global,
@@ -267,7 +342,9 @@ impl<'a> From<&'a str> for TokenKind<'a> {
             "abs" => TK::Abs,
             "for" => TK::For,
             "type" => TK::Type,
-            _ => TK::Ident(value.into())
+            "vec" => TK::Vec,
+            "table" => TK::Table,
+            _ => TK::Ident(value)
         } 
     }
 }
@@ -296,10 +373,7 @@ impl<'a> Token<'a> {
     }
 
     pub fn is_eof(&self) -> bool {
-        match self.kind {
-            TokenKind::Eof => true,
-            _ => false
-        }
+        matches!(self.kind, TokenKind::Eof)
     }
 
     pub fn error(loc: Location, msg: Option<String>) -> Self {
@@ -333,7 +407,7 @@ impl<'a> Token<'a> {
         }
     }
 
-    pub fn kind(&self) -> &TokenKind {
+    pub fn kind(&self) -> &TokenKind<'_> {
         &self.kind
     }
     
@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        self,
+        expr::{AtomIndex, Expr, ExprKind},
+        pattern::Pattern,
+        stmt::Stmt,
+        types::{TypeIndex, TypeKind, TypeList},
+        visitor::{Action, ScopeVisitor, Traversable, Visitor},
+        Decl, Function, GlobalDecl, ManifestDecl, StaticDecl, GLOBAL_VECTOR_SIZE
+    },
+    match_decl,
+    source_file::{Located, Location, WithLocation}
+};
+
+use super::{get_ref, modules::EXTERNAL_SECTIONS, Error, TypeCheckError};
+
+#[derive(Clone, Copy)]
+enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    Atom(AtomIndex)
+}
+
+impl Constant {
+    fn as_int(self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(value),
+            Self::Bool(value) => Some(value as i64),
+            Self::Char(ch) => Some(ch as i64),
+            Self::Atom(atom) => Some(atom as i64),
+            Self::Float(_) => None
+        }
+    }
+
+    fn into_expr(self, loc: Location, typ: Option<TypeIndex>) -> Expr {
+        let kind = match self {
+            Self::Int(value) => ExprKind::IntLit(value as u64),
+            Self::Float(value) => ExprKind::FloatLit(value),
+            Self::Bool(true) => ExprKind::True,
+            Self::Bool(false) => ExprKind::False,
+            Self::Char(ch) => ExprKind::CharLit(ch),
+            Self::Atom(atom) => ExprKind::Atom(atom)
+        };
+        Expr::new(loc, typ, kind)
+    }
+}
+
+// Manifest constants, static initializers, global numbers, `vec` sizes and table elements are
+// evaluated at compile time and replaced by literals, so code generation can use them directly.
+pub(super) fn fold_constants(ast: &mut ast::Program) -> Vec<Error> {
+    let manifests = ast.sections().values()
+        .flat_map(|section| section.declarations().values())
+        .filter_map(|decl| decl.as_any().downcast_ref::<ManifestDecl>())
+        .map(|manifest| (manifest.ident().clone(), manifest.value().clone()))
+        .collect();
+
+    // table elements may name functions, which are stored as their closures
+    let functions = ast.sections().values()
+        .flat_map(|section| section.declarations().values())
+        .filter(|decl| decl.as_any().is::<Function>())
+        .map(|decl| decl.ident().clone())
+        .chain(EXTERNAL_SECTIONS.iter().flat_map(|(_, natives)| natives.iter().map(|native| native.to_string())))
+        .collect();
+
+    let mut folder = ConstantFolder {
+        types: unsafe { get_ref(ast.types()) },
+        manifests,
+        functions,
+        locals: vec![],
+        values: HashMap::new(),
+        evaluating: HashSet::new(),
+        errors: vec![]
+    };
+
+    // fold in source order, so errors are reported in a stable order
+    let mut decls = ast.sections().values()
+        .flat_map(|section| section.declarations().values())
+        .collect::<Vec<_>>();
+    decls.sort_by_key(|decl| {
+        let loc = decl.location();
+        (loc.file_id(), loc.line(), loc.column())
+    });
+
+    let mut folded = vec![];
+    for decl in decls {
+        let any_decl = decl.as_any();
+        let value = match_decl!(
+            any_decl,
+            manifest as ManifestDecl => folder.manifest(manifest.ident(), manifest.location()),
+            global as GlobalDecl => folder.global_index(global.index()),
+            static_decl as StaticDecl => folder.static_initializer(static_decl.value()),
+            _ => None
+        );
+        if let Some(value) = value {
+            folded.push((decl.ident().clone(), value));
+        }
+    }
+
+    // `vec` sizes and table elements are folded in place, after all manifest constants are known
+    let _ = ast.traverse(&mut folder);
+
+    let errors = folder.errors;
+    for (ident, value) in folded {
+        let Some(decl) = ast.sections_mut().values_mut().find_map(|section| section.declarations_mut().get_mut(&ident)) else {
+            continue
+        };
+
+        match_decl!{
+            mut decl;
+            manifest as ManifestDecl => replace(manifest.value_mut(), value),
+            global as GlobalDecl => replace(global.index_mut(), value),
+            static_decl as StaticDecl => replace(static_decl.value_mut(), value),
+            _ => ()
+        }
+    }
+
+    errors
+}
+
+fn replace(expr: &mut Expr, value: Constant) {
+    *expr = value.into_expr(expr.location().clone(), *expr.typ());
+}
+
+struct ConstantFolder<'a> {
+    types: &'a TypeList,
+    manifests: HashMap<String, Expr>,
+    functions: HashSet<String>,
+    // variables declared in each scope currently visited, which may shadow functions
+    locals: Vec<HashSet<String>>,
+
+    values: HashMap<String, Option<Constant>>,
+    // manifest constants currently being evaluated, to detect cycles
+    evaluating: HashSet<String>,
+    errors: Vec<Error>
+}
+
+impl<'a> ConstantFolder<'a> {
+    fn error(&mut self, err: TypeCheckError, loc: &Location) -> Option<Constant> {
+        self.errors.push(err.with_location(loc.clone()));
+        None
+    }
+
+    fn manifest(&mut self, ident: &str, loc: &Location) -> Option<Constant> {
+        if let Some(value) = self.values.get(ident) {
+            return *value
+        }
+
+        if !self.evaluating.insert(ident.to_string()) {
+            return self.error(TypeCheckError::CyclicConstant(ident.to_string()), loc)
+        }
+
+        let expr = self.manifests[ident].clone();
+        let value = self.eval(&expr);
+        self.evaluating.remove(ident);
+        self.values.insert(ident.to_string(), value);
+        value
+    }
+
+    fn global_index(&mut self, index: &Expr) -> Option<Constant> {
+        match self.eval(index)? {
+            Constant::Int(value) if (0..GLOBAL_VECTOR_SIZE).contains(&value) => Some(Constant::Int(value)),
+            Constant::Int(value) => self.error(TypeCheckError::InvalidGlobal(value), index.location()),
+            _ => self.error(TypeCheckError::NotConstant, index.location())
+        }
+    }
+
+    // tables are static data, their elements are folded in place
+    fn static_initializer(&mut self, value: &Expr) -> Option<Constant> {
+        match value.kind() {
+            ExprKind::Array(_) => None,
+            _ => self.eval(value)
+        }
+    }
+
+    fn is_function(&self, ident: &str) -> bool {
+        self.functions.contains(ident) && !self.locals.iter().any(|scope| scope.contains(ident))
+    }
+
+    // strings, nested tables and functions are stored as their addresses
+    fn table_elem(&mut self, elem: &mut Expr) {
+        match elem.kind() {
+            ExprKind::Array(_) | ExprKind::StringLit(_) => return,
+            ExprKind::Ident(ident) if self.is_function(ident) => return,
+            _ => ()
+        }
+
+        if let Some(value) = self.eval(elem) {
+            replace(elem, value);
+        }
+    }
+
+    fn vector_size(&mut self, size: &mut Expr) {
+        match self.eval(size) {
+            Some(Constant::Int(value)) if value >= 0 => replace(size, Constant::Int(value)),
+            Some(Constant::Int(value)) => { self.error(TypeCheckError::InvalidVecSize(value), size.location()); }
+            Some(_) => { self.error(TypeCheckError::NotConstant, size.location()); }
+            None => ()
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Option<Constant> {
+        let loc = expr.location();
+        match expr.kind() {
+            ExprKind::IntLit(value) => Some(Constant::Int(*value as i64)),
+            ExprKind::FloatLit(value) => Some(Constant::Float(*value)),
+            ExprKind::CharLit(ch) => Some(Constant::Char(*ch)),
+            ExprKind::Atom(atom) => Some(Constant::Atom(*atom)),
+            ExprKind::True => Some(Constant::Bool(true)),
+            ExprKind::False => Some(Constant::Bool(false)),
+            ExprKind::Ident(ident) if self.manifests.contains_key(ident) => self.manifest(ident, loc),
+
+            ExprKind::Add(lhs, rhs) => self.arithmetic(lhs, rhs, i64::wrapping_add, |a, b| a + b),
+            ExprKind::Sub(lhs, rhs) => self.arithmetic(lhs, rhs, i64::wrapping_sub, |a, b| a - b),
+            ExprKind::Mul(lhs, rhs) => self.arithmetic(lhs, rhs, i64::wrapping_mul, |a, b| a * b),
+            ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs) => {
+                let remainder = matches!(expr.kind(), ExprKind::Mod(..));
+                match (self.eval(lhs)?, self.eval(rhs)?) {
+                    (Constant::Float(a), Constant::Float(b)) => Some(Constant::Float(if remainder { a % b } else { a / b })),
+                    (_, Constant::Int(0)) => self.error(TypeCheckError::ConstantDivisionByZero, loc),
+                    (Constant::Int(a), Constant::Int(b)) => Some(Constant::Int(if remainder { a.wrapping_rem(b) } else { a.wrapping_div(b) })),
+                    _ => self.error(TypeCheckError::NotConstant, loc)
+                }
+            }
+            ExprKind::Abs(inner) => match self.eval(inner)? {
+                Constant::Int(value) => Some(Constant::Int(value.wrapping_abs())),
+                Constant::Float(value) => Some(Constant::Float(value.abs())),
+                _ => self.error(TypeCheckError::NotConstant, loc)
+            },
+            ExprKind::Neg(inner) => match self.eval(inner)? {
+                Constant::Int(value) => Some(Constant::Int(value.wrapping_neg())),
+                Constant::Float(value) => Some(Constant::Float(-value)),
+                _ => self.error(TypeCheckError::NotConstant, loc)
+            },
+
+            ExprKind::Not(inner) => match self.eval(inner)? {
+                Constant::Bool(value) => Some(Constant::Bool(!value)),
+                Constant::Int(value) => Some(Constant::Int(!value)),
+                _ => self.error(TypeCheckError::NotConstant, loc)
+            },
+            ExprKind::And(lhs, rhs) => self.bitwise(lhs, rhs, |a, b| a & b),
+            ExprKind::Or(lhs, rhs) => self.bitwise(lhs, rhs, |a, b| a | b),
+            ExprKind::XOr(lhs, rhs) => self.bitwise(lhs, rhs, |a, b| a ^ b),
+            ExprKind::LShift(lhs, rhs) => self.bitwise(lhs, rhs, |a, b| (a as u64).wrapping_shl(b as u32) as i64),
+            ExprKind::RShift(lhs, rhs) => self.bitwise(lhs, rhs, |a, b| (a as u64).wrapping_shr(b as u32) as i64),
+
+            ExprKind::Eq(lhs, rhs) => self.comparison(lhs, rhs, |ordering| ordering.is_eq()),
+            ExprKind::Ne(lhs, rhs) => self.comparison(lhs, rhs, |ordering| ordering.is_ne()),
+            ExprKind::Gt(lhs, rhs) => self.comparison(lhs, rhs, |ordering| ordering.is_gt()),
+            ExprKind::Ge(lhs, rhs) => self.comparison(lhs, rhs, |ordering| ordering.is_ge()),
+            ExprKind::Lt(lhs, rhs) => self.comparison(lhs, rhs, |ordering| ordering.is_lt()),
+            ExprKind::Le(lhs, rhs) => self.comparison(lhs, rhs, |ordering| ordering.is_le()),
+
+            ExprKind::Cast(inner) | ExprKind::ImplicitCast(inner) => {
+                let value = self.eval(inner)?;
+                self.cast(value, expr.typ())
+            }
+            ExprKind::Conditional(condition, if_branch, else_branch) => match self.eval(condition)? {
+                Constant::Bool(true) => self.eval(if_branch),
+                Constant::Bool(false) => self.eval(else_branch),
+                _ => self.error(TypeCheckError::NotConstant, loc)
+            },
+            _ => self.error(TypeCheckError::NotConstant, loc)
+        }
+    }
+
+    fn arithmetic(&mut self, lhs: &Expr, rhs: &Expr, int_op: fn(i64, i64) -> i64, float_op: fn(f64, f64) -> f64) -> Option<Constant> {
+        match (self.eval(lhs)?, self.eval(rhs)?) {
+            (Constant::Float(a), Constant::Float(b)) => Some(Constant::Float(float_op(a, b))),
+            (Constant::Int(a), Constant::Int(b)) => Some(Constant::Int(int_op(a, b))),
+            _ => self.error(TypeCheckError::NotConstant, lhs.location())
+        }
+    }
+
+    fn bitwise(&mut self, lhs: &Expr, rhs: &Expr, op: fn(i64, i64) -> i64) -> Option<Constant> {
+        match (self.eval(lhs)?, self.eval(rhs)?) {
+            (Constant::Bool(a), Constant::Bool(b)) => Some(Constant::Bool(op(a as i64, b as i64) != 0)),
+            (Constant::Int(a), Constant::Int(b)) => Some(Constant::Int(op(a, b))),
+            _ => self.error(TypeCheckError::NotConstant, lhs.location())
+        }
+    }
+
+    fn comparison(&mut self, lhs: &Expr, rhs: &Expr, holds: fn(std::cmp::Ordering) -> bool) -> Option<Constant> {
+        let ordering = match (self.eval(lhs)?, self.eval(rhs)?) {
+            (Constant::Float(a), Constant::Float(b)) => a.partial_cmp(&b),
+            (a, b) => a.as_int().zip(b.as_int()).map(|(a, b)| a.cmp(&b))
+        };
+        Some(Constant::Bool(ordering.is_some_and(holds)))
+    }
+
+    // converts like the cast instructions of the generated code
+    fn cast(&mut self, value: Constant, target: &Option<TypeIndex>) -> Option<Constant> {
+        let Some(kind) = target.and_then(|typ| self.types.resolve(typ)) else {
+            return Some(value)
+        };
+
+        if kind.is_float() {
+            return match value {
+                Constant::Float(value) => Some(Constant::Float(value)),
+                value => value.as_int().map(|value| Constant::Float(value as f64))
+            }
+        }
+
+        let value = match value {
+            Constant::Float(value) => value as i64,
+            value => value.as_int()?
+        };
+        Some(match kind {
+            TypeKind::Bool => Constant::Bool(value != 0),
+            TypeKind::Char => Constant::Char(char::from(value as u8)),
+            TypeKind::Int8 => Constant::Int(value as i8 as i64),
+            TypeKind::Int16 => Constant::Int(value as i16 as i64),
+            TypeKind::Int32 => Constant::Int(value as i32 as i64),
+            TypeKind::UInt8 => Constant::Int(value as u8 as i64),
+            TypeKind::UInt16 => Constant::Int(value as u16 as i64),
+            TypeKind::UInt32 => Constant::Int(value as u32 as i64),
+            TypeKind::Atom => Constant::Atom(value as AtomIndex),
+            _ => Constant::Int(value)
+        })
+    }
+}
+
+impl<'a> ScopeVisitor for ConstantFolder<'a> {
+    fn enter_scope(&mut self) {
+        self.locals.push(HashSet::new());
+    }
+
+    fn leave_scope(&mut self) {
+        self.locals.pop();
+    }
+}
+
+impl<'a> Visitor<ast::Program, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, _node: &mut ast::Program) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::Section, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, _node: &mut ast::Section) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::Function, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, _node: &mut ast::Function) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::Param, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, _node: &mut ast::Param) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<Stmt, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, _node: &mut Stmt) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<Expr, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, node: &mut Expr) -> Result<Action, Error> {
+        match node.kind_mut() {
+            ExprKind::Vector(size) => self.vector_size(size),
+            ExprKind::Array(elems) => elems.iter_mut().for_each(|elem| self.table_elem(elem)),
+            _ => ()
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<Located<Pattern>, Error> for ConstantFolder<'a> {
+    fn visit(&mut self, node: &mut Located<Pattern>) -> Result<Action, Error> {
+        if let Pattern::Query(ident) = &**node && let Some(scope) = self.locals.last_mut() {
+            scope.insert(ident.clone());
+        }
+        Ok(Action::Continue)
+    }
+}
@@ -0,0 +1,588 @@
+use crate::{
+    ast::{
+        self,
+        expr::{Expr, ExprKind},
+        pattern::{Pattern, PatternTerm},
+        stmt::StmtKind,
+        types::{TypeIndex, TypeKind, TypeList},
+        visitor::{Action, ScopeVisitor, Visitor},
+        FunctionBody
+    },
+    source_file::{Located, Location, WithLocation}
+};
+
+use super::{Error, TypeCheckError};
+
+// maximum number of missing cases listed in a diagnostic
+const MAX_MISSING_CASES: usize = 5;
+
+// pattern as seen by the exhaustiveness and redundancy checks
+#[derive(Clone, Debug)]
+enum Pat {
+    Wild,
+    // matches no value the checker can reason about, like non-constant terms
+    Never,
+    Variant(TypeIndex, u32, Vec<Pat>), // sum type, tag, fields
+    Bool(bool),
+    Int(i128, i128), // inclusive range, empty if the bounds are reversed
+    List(Vec<Pat>, Option<Vec<Pat>>), // elements, elements after `..`
+    Record(TypeIndex, Vec<Pat>), // record type, one pattern per field of the type
+    Or(Vec<Pat>)
+}
+
+// set of values a scrutinee can take
+enum Domain {
+    Sum(TypeIndex, Vec<Vec<TypeIndex>>), // sum type, fields of each variant
+    Bool,
+    Int(i128, i128),
+    List(Option<TypeIndex>),
+    Record(TypeIndex, Vec<TypeIndex>), // record type, field types
+    // infinitely many values without constructors, like strings; only wildcards cover them
+    Opaque
+}
+
+// a single constructor of a domain; integer domains are split into ranges
+#[derive(Clone, Copy, Debug)]
+enum Ctor {
+    Variant(TypeIndex, u32),
+    Bool(bool),
+    Int(i128, i128),
+    FixedLen(usize),
+    VarLen(usize, usize), // lists longer than all fixed-length patterns: elements before and after `..`
+    Record(TypeIndex)
+}
+
+fn int_bounds(kind: &TypeKind) -> Option<(i128, i128)> {
+    match kind {
+        TypeKind::UInt8 | TypeKind::Char => Some((0, u8::MAX as i128)),
+        TypeKind::UInt16 => Some((0, u16::MAX as i128)),
+        TypeKind::UInt32 => Some((0, u32::MAX as i128)),
+        TypeKind::UInt64 => Some((0, u64::MAX as i128)),
+        TypeKind::Int8 => Some((i8::MIN as i128, i8::MAX as i128)),
+        TypeKind::Int16 => Some((i16::MIN as i128, i16::MAX as i128)),
+        TypeKind::Int32 => Some((i32::MIN as i128, i32::MAX as i128)),
+        TypeKind::Int64 => Some((i64::MIN as i128, i64::MAX as i128)),
+        TypeKind::Atom => Some((0, u32::MAX as i128)),
+        _ => None
+    }
+}
+
+fn const_int(expr: &Expr) -> Option<i128> {
+    match expr.kind() {
+        ExprKind::IntLit(value) => Some(*value as i128),
+        ExprKind::CharLit(ch) => Some(*ch as i128),
+        ExprKind::Atom(atom) => Some(*atom as i128),
+        ExprKind::Neg(inner) => const_int(inner).map(|value| -value),
+        ExprKind::Cast(inner) | ExprKind::ImplicitCast(inner) => const_int(inner),
+        _ => None
+    }
+}
+
+// whether `pat` matches all values of constructor `ctor`
+fn covers(pat: &Pat, ctor: &Ctor) -> bool {
+    match (pat, ctor) {
+        (Pat::Wild, _) => true,
+        (Pat::Or(alternatives), _) => alternatives.iter().any(|alt| covers(alt, ctor)),
+        (Pat::Variant(_, tag, _), Ctor::Variant(_, ctor_tag)) => tag == ctor_tag,
+        (Pat::Bool(value), Ctor::Bool(ctor_value)) => value == ctor_value,
+        (Pat::Int(lo, hi), Ctor::Int(ctor_lo, ctor_hi)) => lo <= ctor_lo && ctor_hi <= hi,
+        (Pat::List(elems, None), Ctor::FixedLen(len)) => elems.len() == *len,
+        (Pat::List(prefix, Some(suffix)), Ctor::FixedLen(len)) => prefix.len() + suffix.len() <= *len,
+        (Pat::List(_, Some(_)), Ctor::VarLen(..)) => true,
+        (Pat::Record(..), Ctor::Record(_)) => true,
+        _ => false
+    }
+}
+
+// fields of `row`s first pattern when it matches `ctor`, followed by the remaining patterns
+fn specialize(row: &[Pat], ctor: &Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let (head, tail) = row.split_first()?;
+    let mut fields = match head {
+        Pat::Wild => vec![Pat::Wild; arity],
+        _ if !covers(head, ctor) => return None,
+        Pat::Variant(_, _, fields) | Pat::Record(_, fields) => fields.clone(),
+        Pat::List(elems, None) => elems.clone(),
+        Pat::List(prefix, Some(suffix)) => {
+            let middle = arity - prefix.len() - suffix.len();
+            [prefix.clone(), vec![Pat::Wild; middle], suffix.clone()].concat()
+        }
+        _ => vec![]
+    };
+
+    // wrong numbers of variant fields are reported by the type inference
+    fields.resize(arity, Pat::Wild);
+    fields.extend_from_slice(tail);
+    Some(fields)
+}
+
+// splits rows starting with or-patterns into one row per alternative
+fn expand_or(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    let mut expanded = vec![];
+    for row in rows {
+        match row.first() {
+            Some(Pat::Or(alternatives)) => expanded.extend(expand_or(
+                &alternatives.iter()
+                    .map(|alt| [std::slice::from_ref(alt), &row[1..]].concat())
+                    .collect::<Vec<_>>()
+            )),
+            _ => expanded.push(row.clone())
+        }
+    }
+    expanded
+}
+
+// Checks `match` expressions, statements and pattern-matched function bodies for branches which
+// can never be reached and for values which are not matched by any branch.
+// `every` runs all matching branches and doesn't fail when none matches, so it isn't checked.
+pub(super) struct PatternChecker<'a> {
+    types: &'a TypeList,
+    diagnostics: Vec<Error>
+}
+
+impl<'a> PatternChecker<'a> {
+    pub(super) fn new(types: &'a TypeList) -> Self {
+        Self {
+            types,
+            diagnostics: vec![]
+        }
+    }
+
+    pub(super) fn diagnostics(self) -> Vec<Error> {
+        self.diagnostics
+    }
+
+    fn check<'p>(&mut self, loc: &Location, typs: &[Option<TypeIndex>], branches: impl Iterator<Item = &'p Vec<Located<Pattern>>>) {
+        let mut rows = vec![];
+        for patterns in branches {
+            let query = self.row(patterns, typs, &Pat::Wild);
+            if let Some(first) = patterns.first() && self.usefulness(&rows, &query, typs).is_empty() {
+                self.diagnostics.push(TypeCheckError::UnreachableBranch.with_location(first.location().clone()));
+            }
+            rows.push(self.row(patterns, typs, &Pat::Never));
+        }
+
+        let missing = self.usefulness(&rows, &vec![Pat::Wild; typs.len()], typs);
+        if !missing.is_empty() {
+            let cases = missing.iter()
+                .take(MAX_MISSING_CASES)
+                .map(|witness| witness.iter().map(|pat| self.display(pat)).collect::<Vec<_>>().join(", "))
+                .collect();
+            let err = TypeCheckError::NonExhaustive(cases, missing.len() > MAX_MISSING_CASES);
+            self.diagnostics.push(err.with_location(loc.clone()));
+        }
+    }
+
+    // `opaque` stands in for patterns the checker can't analyze
+    fn row(&self, patterns: &[Located<Pattern>], typs: &[Option<TypeIndex>], opaque: &Pat) -> Vec<Pat> {
+        patterns.iter()
+            .enumerate()
+            .map(|(i, pattern)| self.pat(pattern, typs.get(i).copied().flatten(), opaque))
+            .collect()
+    }
+
+    fn strip_pointers(&self, typ: Option<TypeIndex>) -> Option<TypeIndex> {
+        match self.types.resolve(typ?) {
+            Some(TypeKind::Pointer(inner)) => self.strip_pointers(Some(*inner)),
+            _ => typ
+        }
+    }
+
+    fn kind_of(&self, typ: Option<TypeIndex>) -> Option<&'a TypeKind> {
+        self.strip_pointers(typ).and_then(|typ| self.types.resolve(typ))
+    }
+
+    fn pat(&self, pattern: &Pattern, typ: Option<TypeIndex>, opaque: &Pat) -> Pat {
+        match pattern {
+            Pattern::Any | Pattern::Remaining => Pat::Wild,
+            Pattern::Query(ident) => match self.types.find_variant(ident) {
+                Some((sum, tag, variant)) if variant.fields().is_empty() => Pat::Variant(sum, tag, vec![]),
+                _ => Pat::Wild
+            },
+            Pattern::Term(term) => self.term(term, typ).unwrap_or_else(|| opaque.clone()),
+            Pattern::Or(lhs, rhs) => Pat::Or(vec![self.pat(lhs, typ, opaque), self.pat(rhs, typ, opaque)]),
+            Pattern::And(lhs, rhs) => match (self.pat(lhs, typ, opaque), self.pat(rhs, typ, opaque)) {
+                (Pat::Wild, pat) | (pat, Pat::Wild) => pat,
+                (Pat::Int(lo, hi), Pat::Int(other_lo, other_hi)) => Pat::Int(lo.max(other_lo), hi.min(other_hi)),
+                _ => opaque.clone()
+            },
+            Pattern::Variant(ident, args) => match (self.types.find_variant(ident), self.types.find_variant_of(self.strip_pointers(typ), ident)) {
+                // the field types of instances of generic sum types are those of the scrutinee
+                (Some((sum, tag, _)), Some((_, _, variant))) => Pat::Variant(
+                    sum, tag,
+                    args.iter()
+                        .enumerate()
+                        .map(|(i, arg)| self.pat(arg, variant.fields().get(i).copied(), opaque))
+                        .collect()
+                ),
+                _ => opaque.clone()
+            },
+            Pattern::List(elems) => {
+                let elem_typ = match self.kind_of(typ) {
+                    Some(TypeKind::Slice(elem) | TypeKind::Array(elem, _)) => Some(*elem),
+                    _ => None
+                };
+                let pats = elems.iter().map(|elem| self.pat(elem, elem_typ, opaque)).collect::<Vec<_>>();
+                match elems.iter().position(|elem| matches!(**elem, Pattern::Remaining)) {
+                    Some(pos) => Pat::List(pats[..pos].to_vec(), Some(pats[pos + 1..].to_vec())),
+                    None => Pat::List(pats, None)
+                }
+            }
+            Pattern::Record(fields) => match self.kind_of(typ) {
+                Some(TypeKind::Record(decl_fields)) => Pat::Record(
+                    self.strip_pointers(typ).map(|typ| self.representative(typ)).unwrap(),
+                    decl_fields.iter()
+                        .map(|decl_field| fields.iter()
+                            .find(|(ident, _)| ident == decl_field.ident())
+                            .map(|(_, field)| self.pat(field, Some(decl_field.typ()), opaque))
+                            .unwrap_or(Pat::Wild)
+                        )
+                        .collect()
+                ),
+                _ => opaque.clone()
+            }
+        }
+    }
+
+    fn term(&self, term: &PatternTerm, typ: Option<TypeIndex>) -> Option<Pat> {
+        let (lo, hi) = self.kind_of(typ)
+            .and_then(int_bounds)
+            .unwrap_or((i64::MIN as i128, i64::MAX as i128));
+
+        let pat = match term {
+            PatternTerm::Basic(expr) | PatternTerm::Eq(expr) => match expr.kind() {
+                ExprKind::True => Pat::Bool(true),
+                ExprKind::False => Pat::Bool(false),
+                _ => {
+                    let value = const_int(expr)?;
+                    Pat::Int(value, value)
+                }
+            },
+            PatternTerm::Ne(expr) => match expr.kind() {
+                ExprKind::True => Pat::Bool(false),
+                ExprKind::False => Pat::Bool(true),
+                _ => {
+                    let value = const_int(expr)?;
+                    Pat::Or(vec![Pat::Int(lo, value - 1), Pat::Int(value + 1, hi)])
+                }
+            },
+            PatternTerm::Lt(expr) => Pat::Int(lo, const_int(expr)? - 1),
+            PatternTerm::Le(expr) => Pat::Int(lo, const_int(expr)?),
+            PatternTerm::Gt(expr) => Pat::Int(const_int(expr)? + 1, hi),
+            PatternTerm::Ge(expr) => Pat::Int(const_int(expr)?, hi),
+            PatternTerm::Range(from, to) => Pat::Int(const_int(from)?, const_int(to)?)
+        };
+        Some(pat)
+    }
+
+    fn domain(&self, typ: Option<TypeIndex>, heads: &[&Pat]) -> Domain {
+        let kind = self.kind_of(typ);
+        if let Some(kind) = kind && let Some((lo, hi)) = int_bounds(kind) {
+            return Domain::Int(lo, hi)
+        }
+
+        match kind {
+            Some(TypeKind::Sum(variants)) => Domain::Sum(
+                self.strip_pointers(typ).map(|typ| self.representative(typ)).unwrap(),
+                variants.iter().map(|variant| variant.fields().clone()).collect()
+            ),
+            Some(TypeKind::Bool) => Domain::Bool,
+            Some(TypeKind::Record(fields)) => Domain::Record(
+                self.strip_pointers(typ).map(|typ| self.representative(typ)).unwrap(),
+                fields.iter().map(|field| field.typ()).collect()
+            ),
+            Some(TypeKind::Slice(elem) | TypeKind::Array(elem, _)) => Domain::List(Some(*elem)),
+            Some(_) => Domain::Opaque,
+            // the scrutinee's type is unknown, guess it from the patterns
+            None => heads.iter()
+                .find_map(|head| match head {
+                    Pat::Variant(sum, _, _) => match self.types.get(*sum).map(|typ| typ.kind()) {
+                        Some(TypeKind::Sum(variants)) => Some(Domain::Sum(*sum, variants.iter().map(|variant| variant.fields().clone()).collect())),
+                        _ => None
+                    },
+                    Pat::Bool(_) => Some(Domain::Bool),
+                    Pat::Int(..) => Some(Domain::Int(i64::MIN as i128, i64::MAX as i128)),
+                    Pat::List(..) => Some(Domain::List(None)),
+                    Pat::Record(record, _) => match self.types.get(*record).map(|typ| typ.kind()) {
+                        Some(TypeKind::Record(fields)) => Some(Domain::Record(*record, fields.iter().map(|field| field.typ()).collect())),
+                        _ => None
+                    },
+                    _ => None
+                })
+                .unwrap_or(Domain::Opaque)
+        }
+    }
+
+    // the sum or record type itself instead of aliases naming it
+    fn representative(&self, typ: TypeIndex) -> TypeIndex {
+        match self.types.get(typ).map(|typ| typ.kind()) {
+            Some(TypeKind::Alias(_, Some(inner)) | TypeKind::Var(Some(inner))) => self.representative(*inner),
+            _ => typ
+        }
+    }
+
+    // constructors of `domain`, split so that each is either fully covered by a pattern in `heads` or not at all
+    fn constructors(&self, domain: &Domain, heads: &[&Pat]) -> Vec<Ctor> {
+        match domain {
+            Domain::Sum(sum, variants) => (0..variants.len() as u32).map(|tag| Ctor::Variant(*sum, tag)).collect(),
+            Domain::Bool => vec![Ctor::Bool(false), Ctor::Bool(true)],
+            Domain::Int(lo, hi) => {
+                let mut bounds = vec![*lo, *hi + 1];
+                for head in heads {
+                    if let Pat::Int(from, to) = head && from <= to {
+                        bounds.push((*from).clamp(*lo, *hi + 1));
+                        bounds.push((*to + 1).clamp(*lo, *hi + 1));
+                    }
+                }
+                bounds.sort();
+                bounds.dedup();
+                bounds.windows(2).map(|range| Ctor::Int(range[0], range[1] - 1)).collect()
+            }
+            Domain::List(_) => {
+                let (mut max_len, mut max_prefix, mut max_suffix) = (0, 0, 0);
+                for head in heads {
+                    match head {
+                        Pat::List(elems, None) => max_len = max_len.max(elems.len()),
+                        Pat::List(prefix, Some(suffix)) => {
+                            max_len = max_len.max(prefix.len() + suffix.len());
+                            max_prefix = max_prefix.max(prefix.len());
+                            max_suffix = max_suffix.max(suffix.len());
+                        }
+                        _ => ()
+                    }
+                }
+                // the longer lists have at least `max_len + 1` elements, which the prefix spells out
+                // so that missing ones are shown like `[_, _, ..]`
+                let prefix = max_prefix.max(max_len + 1 - max_suffix);
+                (0..=max_len).map(Ctor::FixedLen)
+                    .chain([Ctor::VarLen(prefix, max_suffix)])
+                    .collect()
+            }
+            Domain::Record(record, _) => vec![Ctor::Record(*record)],
+            Domain::Opaque => vec![]
+        }
+    }
+
+    fn field_types(&self, domain: &Domain, ctor: &Ctor) -> Vec<Option<TypeIndex>> {
+        match (domain, ctor) {
+            (Domain::Sum(_, variants), Ctor::Variant(_, tag)) => variants[*tag as usize].iter().copied().map(Some).collect(),
+            (Domain::List(elem), Ctor::FixedLen(len)) => vec![*elem; *len],
+            (Domain::List(elem), Ctor::VarLen(prefix, suffix)) => vec![*elem; prefix + suffix],
+            (Domain::Record(_, fields), Ctor::Record(_)) => fields.iter().copied().map(Some).collect(),
+            _ => vec![]
+        }
+    }
+
+    // Returns example rows of values matched by `query` but by none of the `rows`.
+    // If there are none, `query` is useless after `rows`.
+    fn usefulness(&self, rows: &[Vec<Pat>], query: &[Pat], typs: &[Option<TypeIndex>]) -> Vec<Vec<Pat>> {
+        let Some((head, tail)) = query.split_first()
+        else {
+            return if rows.is_empty() { vec![vec![]] } else { vec![] }
+        };
+
+        if let Pat::Or(alternatives) = head {
+            return alternatives.iter()
+                .flat_map(|alt| self.usefulness(rows, &[std::slice::from_ref(alt), tail].concat(), typs))
+                .take(MAX_MISSING_CASES + 1)
+                .collect()
+        }
+
+        let rows = expand_or(rows);
+        let heads = rows.iter().map(|row| &row[0]).chain([head]).collect::<Vec<_>>();
+        let typ = typs.first().copied().flatten();
+        let domain = self.domain(typ, &heads);
+        let ctors = self.constructors(&domain, &heads);
+
+        let covered = ctors.iter()
+            .map(|ctor| rows.iter().any(|row| !matches!(row[0], Pat::Wild) && covers(&row[0], ctor)))
+            .collect::<Vec<_>>();
+        let complete = !ctors.is_empty() && covered.iter().all(|covered| *covered);
+
+        if matches!(head, Pat::Wild) && !complete {
+            // only rows starting with a wildcard match the constructors missing in this column
+            let default_rows = rows.iter()
+                .filter(|row| matches!(row[0], Pat::Wild))
+                .map(|row| row[1..].to_vec())
+                .collect::<Vec<_>>();
+            let default_witnesses = self.usefulness(&default_rows, tail, typs.get(1..).unwrap_or_default());
+            if default_witnesses.is_empty() {
+                return vec![]
+            }
+
+            // values of the covered constructors may still be missing in their fields or the later columns
+            let covered_ctors = ctors.iter().zip(&covered).filter(|(_, covered)| **covered).map(|(ctor, _)| ctor);
+            let mut witnesses = self.specialized_witnesses(&rows, query, typs, &domain, covered_ctors);
+
+            let missing = if covered.iter().any(|covered| *covered) {
+                self.missing_patterns(&ctors, &covered, &domain)
+            }
+            else {
+                vec![Pat::Wild]
+            };
+            witnesses.extend(missing.iter()
+                .flat_map(|pat| default_witnesses.iter().map(move |witness| [std::slice::from_ref(pat), witness.as_slice()].concat()))
+            );
+            witnesses.truncate(MAX_MISSING_CASES + 1);
+            return witnesses
+        }
+
+        self.specialized_witnesses(&rows, query, typs, &domain, ctors.iter().filter(|ctor| covers(head, ctor)))
+    }
+
+    // witnesses of `usefulness` starting with one of `ctors`, found by specializing the rows and the query to each
+    fn specialized_witnesses<'c>(&self, rows: &[Vec<Pat>], query: &[Pat], typs: &[Option<TypeIndex>], domain: &Domain, ctors: impl Iterator<Item = &'c Ctor>) -> Vec<Vec<Pat>> {
+        let typs_tail = typs.get(1..).unwrap_or_default();
+        let mut witnesses = vec![];
+        for ctor in ctors {
+            let field_types = self.field_types(domain, ctor);
+            let arity = field_types.len();
+            let spec_rows = rows.iter()
+                .filter_map(|row| specialize(row, ctor, arity))
+                .collect::<Vec<_>>();
+            let spec_query = specialize(query, ctor, arity).expect("query doesn't cover constructor");
+
+            for mut witness in self.usefulness(&spec_rows, &spec_query, &[field_types.as_slice(), typs_tail].concat()) {
+                let rest = witness.split_off(arity);
+                let pat = match *ctor {
+                    Ctor::Variant(sum, tag) => Pat::Variant(sum, tag, witness),
+                    Ctor::Bool(value) => Pat::Bool(value),
+                    Ctor::Int(lo, hi) => Pat::Int(lo, hi),
+                    Ctor::FixedLen(_) => Pat::List(witness, None),
+                    Ctor::VarLen(prefix, _) => {
+                        let suffix = witness.split_off(prefix);
+                        Pat::List(witness, Some(suffix))
+                    }
+                    Ctor::Record(record) => Pat::Record(record, witness)
+                };
+                witnesses.push([vec![pat], rest].concat());
+            }
+
+            if witnesses.len() > MAX_MISSING_CASES {
+                break
+            }
+        }
+        witnesses
+    }
+
+    // patterns for the constructors not covered by any row, with adjacent integer ranges merged
+    fn missing_patterns(&self, ctors: &[Ctor], covered: &[bool], domain: &Domain) -> Vec<Pat> {
+        let mut missing: Vec<Pat> = vec![];
+        for (ctor, _) in ctors.iter().zip(covered).filter(|(_, covered)| !**covered) {
+            let pat = match *ctor {
+                Ctor::Variant(sum, tag) => {
+                    let arity = self.field_types(domain, ctor).len();
+                    Pat::Variant(sum, tag, vec![Pat::Wild; arity])
+                }
+                Ctor::Bool(value) => Pat::Bool(value),
+                Ctor::Int(lo, hi) => {
+                    if let Some(Pat::Int(_, prev_hi)) = missing.last_mut() && *prev_hi + 1 == lo {
+                        *prev_hi = hi;
+                        continue
+                    }
+                    Pat::Int(lo, hi)
+                }
+                Ctor::FixedLen(len) => Pat::List(vec![Pat::Wild; len], None),
+                Ctor::VarLen(prefix, suffix) => Pat::List(vec![Pat::Wild; prefix], Some(vec![Pat::Wild; suffix])),
+                Ctor::Record(_) => Pat::Wild
+            };
+            missing.push(pat);
+        }
+        missing
+    }
+
+    fn display(&self, pat: &Pat) -> String {
+        let list = |pats: &[Pat]| pats.iter().map(|pat| self.display(pat)).collect::<Vec<_>>();
+        match pat {
+            Pat::Wild | Pat::Never => "?".into(),
+            Pat::Variant(sum, tag, fields) => {
+                let ident = match self.types.get(*sum).map(|typ| typ.kind()) {
+                    Some(TypeKind::Sum(variants)) => variants[*tag as usize].ident().clone(),
+                    _ => "?".into()
+                };
+                if fields.is_empty() {
+                    ident
+                }
+                else {
+                    format!("{ident}({})", list(fields).join(", "))
+                }
+            }
+            Pat::Bool(value) => value.to_string(),
+            Pat::Int(lo, hi) if lo == hi => lo.to_string(),
+            Pat::Int(lo, hi) => format!("{lo} .. {hi}"),
+            Pat::List(elems, None) => format!("[{}]", list(elems).join(", ")),
+            Pat::List(prefix, Some(suffix)) => format!("[{}]", [list(prefix), vec!["..".into()], list(suffix)].concat().join(", ")),
+            // fields matching anything are left out
+            Pat::Record(record, fields) => {
+                let Some(TypeKind::Record(decl_fields)) = self.types.get(*record).map(|typ| typ.kind()) else {
+                    return "?".into()
+                };
+                let fields = decl_fields.iter()
+                    .zip(fields)
+                    .filter(|(_, field)| !matches!(field, Pat::Wild | Pat::Never))
+                    .map(|(decl_field, field)| format!("{} = {}", decl_field.ident(), self.display(field)))
+                    .collect::<Vec<_>>();
+                if fields.is_empty() { "?".into() } else { format!("{{ {} }}", fields.join(", ")) }
+            }
+            Pat::Or(alternatives) => list(alternatives).join(" | ")
+        }
+    }
+}
+
+impl<'a> ScopeVisitor for PatternChecker<'a> {}
+
+impl<'a> Visitor<ast::Program, Error> for PatternChecker<'a> {
+    fn visit(&mut self, _node: &mut ast::Program) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::Section, Error> for PatternChecker<'a> {
+    fn visit(&mut self, _node: &mut ast::Section) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::Function, Error> for PatternChecker<'a> {
+    fn visit(&mut self, node: &mut ast::Function) -> Result<Action, Error> {
+        let typs = node.params().iter().map(|param| *param.typ()).collect::<Vec<_>>();
+        let loc = ast::Decl::location(node).clone();
+        match node.body() {
+            FunctionBody::PatternMatchedExpr(branches) => self.check(&loc, &typs, branches.iter().map(|(patterns, _)| patterns)),
+            FunctionBody::PatternMatchedStmt(branches) => self.check(&loc, &typs, branches.iter().map(|(patterns, _)| patterns)),
+            FunctionBody::Expr(_) | FunctionBody::Stmt(_) => ()
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::Param, Error> for PatternChecker<'a> {
+    fn visit(&mut self, _node: &mut ast::Param) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<ast::stmt::Stmt, Error> for PatternChecker<'a> {
+    fn visit(&mut self, node: &mut ast::stmt::Stmt) -> Result<Action, Error> {
+        if let StmtKind::Match(args, branches) = node.kind() {
+            let typs = args.iter().map(|arg| *arg.typ()).collect::<Vec<_>>();
+            self.check(node.location(), &typs, branches.iter().map(|(patterns, _)| patterns));
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<Expr, Error> for PatternChecker<'a> {
+    fn visit(&mut self, node: &mut Expr) -> Result<Action, Error> {
+        if let ExprKind::Match(args, branches) = node.kind() {
+            let typs = args.iter().map(|arg| *arg.typ()).collect::<Vec<_>>();
+            self.check(node.location(), &typs, branches.iter().map(|(patterns, _)| patterns));
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl<'a> Visitor<Located<Pattern>, Error> for PatternChecker<'a> {
+    fn visit(&mut self, _node: &mut Located<Pattern>) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
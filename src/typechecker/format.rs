@@ -0,0 +1,157 @@
+use crate::{
+    ast::{
+        self,
+        expr::{Expr, ExprKind},
+        pattern::Pattern,
+        stmt::Stmt,
+        types::{TypeIndex, TypeKind, TypeList},
+        visitor::{Action, ScopeVisitor, Traversable, Visitor},
+        Function
+    },
+    source_file::{Located, WithLocation},
+    writef::{parse_format, DirectiveKind, FormatPart}
+};
+
+use super::{get_ref, Error, TypeCheckError};
+
+fn accepts(directive: DirectiveKind, kind: &TypeKind) -> bool {
+    let is_integer = matches!(kind,
+        TypeKind::UInt8 | TypeKind::UInt16 | TypeKind::UInt32 | TypeKind::UInt64 |
+        TypeKind::Int8 | TypeKind::Int16 | TypeKind::Int32 | TypeKind::Int64 |
+        TypeKind::Char | TypeKind::Bool
+    );
+    match directive {
+        DirectiveKind::Number | DirectiveKind::Integer | DirectiveKind::Hex | DirectiveKind::Octal | DirectiveKind::Binary | DirectiveKind::Char => is_integer,
+        DirectiveKind::String => matches!(kind, TypeKind::Pointer(_)),
+        DirectiveKind::Float => kind.is_float()
+    }
+}
+
+fn expected(directive: DirectiveKind) -> &'static str {
+    match directive {
+        DirectiveKind::Number | DirectiveKind::Integer | DirectiveKind::Hex | DirectiveKind::Octal | DirectiveKind::Binary => "an integer",
+        DirectiveKind::String => "a string",
+        DirectiveKind::Char => "a character",
+        DirectiveKind::Float => "a floating point number"
+    }
+}
+
+// Checks calls of `writef` with a literal format string against the number and types of their arguments.
+// Arguments of unknown types, like those of generic functions, are not checked.
+pub(super) fn check_formats(ast: &mut ast::Program) -> Vec<Error> {
+    let mut checker = FormatChecker {
+        types: unsafe { get_ref(ast.types()) },
+        errors: vec![]
+    };
+    let _ = ast.traverse(&mut checker);
+    checker.errors
+}
+
+struct FormatChecker<'a> {
+    types: &'a TypeList,
+    errors: Vec<Error>
+}
+
+impl<'a> FormatChecker<'a> {
+    fn check_call(&mut self, call: &Expr, format: &Expr, format_string: &str, args: &[Expr]) {
+        // the location of a part inside of the literal is only known if it is on a single line and has no
+        // escape sequences, which make the decoded string shorter
+        let literal_loc = format.location();
+        let part_loc = |offset: usize, len: usize| if literal_loc.width() == format_string.len() + 2 {
+            literal_loc.sub_location(offset + 1, len)
+        }
+        else {
+            literal_loc.clone()
+        };
+
+        let mut directives = vec![];
+        for (offset, len, part) in parse_format(format_string) {
+            match part {
+                FormatPart::Arg(directive) => directives.push((directive, &format_string[offset..offset + len])),
+                FormatPart::Unknown(ch) => {
+                    let err = TypeCheckError::UnknownDirective(ch);
+                    self.errors.push(err.with_location(part_loc(offset, len)));
+                }
+                FormatPart::Text(_) => ()
+            }
+        }
+
+        if directives.len() != args.len() {
+            let err = TypeCheckError::FormatArgCount(directives.len(), args.len());
+            self.errors.push(err.with_location(call.location().clone()));
+        }
+
+        for ((directive, text), arg) in directives.into_iter().zip(args) {
+            let Some(typ) = self.known_type(arg.typ())
+            else {
+                continue
+            };
+
+            let kind = self.types.resolve(typ).expect("known type without kind");
+            if !accepts(directive.kind, kind) {
+                let err = TypeCheckError::FormatArgType(text.to_string(), expected(directive.kind), self.types.name_of(typ));
+                self.errors.push(err.with_location(arg.location().clone()));
+            }
+        }
+    }
+
+    fn known_type(&self, typ: &Option<TypeIndex>) -> Option<TypeIndex> {
+        let typ = (*typ)?;
+        match self.types.resolve(typ)? {
+            TypeKind::Param(_) | TypeKind::Generic(..) => None,
+            _ => Some(typ)
+        }
+    }
+}
+
+impl ScopeVisitor for FormatChecker<'_> {}
+
+impl Visitor<ast::Program, Error> for FormatChecker<'_> {
+    fn visit(&mut self, _node: &mut ast::Program) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<ast::Section, Error> for FormatChecker<'_> {
+    fn visit(&mut self, _node: &mut ast::Section) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Function, Error> for FormatChecker<'_> {
+    fn visit(&mut self, _node: &mut Function) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<ast::Param, Error> for FormatChecker<'_> {
+    fn visit(&mut self, _node: &mut ast::Param) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Stmt, Error> for FormatChecker<'_> {
+    fn visit(&mut self, _node: &mut Stmt) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Expr, Error> for FormatChecker<'_> {
+    fn visit(&mut self, node: &mut Expr) -> Result<Action, Error> {
+        // `writef` is provided by the runtime, so it stays untyped unless a local variable shadows it
+        if let ExprKind::FuncCall(callee, args) = node.kind()
+            && let ExprKind::Ident(ident) = callee.kind()
+            && ident == "writef" && callee.typ().is_none()
+            && let Some((format, args)) = args.split_first()
+            && let ExprKind::StringLit(format_string) = format.kind() {
+            self.check_call(node, format, format_string, args);
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Located<Pattern>, Error> for FormatChecker<'_> {
+    fn visit(&mut self, _node: &mut Located<Pattern>) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
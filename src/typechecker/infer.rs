@@ -0,0 +1,1131 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        self,
+        expr::{Expr, ExprKind, Lambda},
+        pattern::{Pattern, PatternTerm},
+        stmt::{Stmt, StmtKind},
+        types::{TypeIndex, TypeKind, TypeList, RecordField},
+        BasicFunctionBody, Decl, Function, FunctionBody, GlobalDecl, ManifestDecl, StaticDecl
+    },
+    match_decl,
+    source_file::{Located, Location, WithLocation}
+};
+
+use super::{Error, TypeCheckError};
+
+// parameter and return types of a function
+struct Signature {
+    loc: Location,
+    type_params: Vec<String>,
+    params: Vec<(TypeIndex, Location)>,
+    required_params: usize,
+    return_type: TypeIndex
+}
+
+// Fills in the types the parser couldn't know by unifying type variables.
+// Every expression, untyped parameter and return type starts with an unbound type variable,
+// which gets bound by the first concrete type it is used as.
+pub(super) struct Inferer {
+    types: TypeList,
+    signatures: HashMap<String, Signature>,
+    // types of manifest constants, globals and statics
+    variables: HashMap<String, TypeIndex>,
+    errors: Vec<Error>,
+
+    scopes: Vec<HashMap<String, TypeIndex>>,
+    valofs: Vec<TypeIndex>,
+    switches: Vec<Option<TypeIndex>>
+}
+
+pub(super) fn infer_types(ast: &mut ast::Program) -> Vec<Error> {
+    let mut inferer = Inferer {
+        types: std::mem::take(ast.types_mut()),
+        signatures: HashMap::new(),
+        variables: HashMap::new(),
+        errors: vec![],
+        scopes: vec![],
+        valofs: vec![],
+        switches: vec![]
+    };
+
+    inferer.instances();
+
+    let (mut functions, mut variables): (Vec<_>, Vec<_>) = ast.sections_mut().values_mut()
+        .flat_map(|section| section.declarations_mut().values_mut())
+        .partition(|decl| decl.as_any().is::<Function>());
+    // infer in source order, so the first use of a type variable is the one binding it
+    let source_order = |decl: &&mut Box<dyn Decl>| {
+        let loc = decl.location();
+        (loc.file_id(), loc.line(), loc.column())
+    };
+    functions.sort_by_key(source_order);
+    variables.sort_by_key(source_order);
+
+    let mut functions = functions.into_iter()
+        .filter_map(|decl| decl.as_mut_any().downcast_mut::<Function>())
+        .collect::<Vec<_>>();
+    for func in functions.iter_mut() {
+        inferer.signature(func);
+    }
+    // variables may be initialized with functions, so they need the signatures
+    for decl in variables {
+        inferer.variable(&mut **decl);
+    }
+    for func in functions.iter_mut() {
+        inferer.default_values(func);
+    }
+    // generic functions are inferred first, so their instantiations know their return types
+    functions.sort_by_key(|func| !func.is_generic());
+    for func in functions.iter_mut() {
+        inferer.function(func);
+    }
+
+    inferer.types.compute_layouts();
+    *ast.types_mut() = inferer.types;
+    inferer.errors
+}
+
+fn take(expr: &mut Expr) -> Expr {
+    let placeholder = Expr::new(expr.location().clone(), None, ExprKind::True);
+    std::mem::replace(expr, placeholder)
+}
+
+fn is_integer(kind: &TypeKind) -> bool {
+    matches!(kind,
+        TypeKind::UInt8 | TypeKind::UInt16 | TypeKind::UInt32 | TypeKind::UInt64 |
+        TypeKind::Int8 | TypeKind::Int16 | TypeKind::Int32 | TypeKind::Int64 | TypeKind::Char
+    )
+}
+
+// types which can be converted into each other implicitly
+fn is_scalar(kind: &TypeKind) -> bool {
+    is_integer(kind) || kind.is_float()
+}
+
+fn is_bitwise(kind: &TypeKind) -> bool {
+    is_integer(kind) || matches!(kind, TypeKind::Bool)
+}
+
+fn is_ordered(kind: &TypeKind) -> bool {
+    is_scalar(kind) || matches!(kind, TypeKind::Pointer(_))
+}
+
+fn is_any(_: &TypeKind) -> bool {
+    true
+}
+
+fn operator(kind: &ExprKind) -> &'static str {
+    match kind {
+        ExprKind::Add(..) => "+",
+        ExprKind::Sub(..) => "-",
+        ExprKind::Mul(..) => "*",
+        ExprKind::Div(..) => "/",
+        ExprKind::Mod(..) => "mod",
+        ExprKind::Abs(..) => "abs",
+        ExprKind::Neg(..) => "-",
+        ExprKind::Not(..) => "~",
+        ExprKind::And(..) => "&",
+        ExprKind::Or(..) => "|",
+        ExprKind::XOr(..) => "^",
+        ExprKind::Eq(..) => "=",
+        ExprKind::Ne(..) => "~=",
+        ExprKind::Gt(..) => ">",
+        ExprKind::Ge(..) => ">=",
+        ExprKind::Lt(..) => "<",
+        ExprKind::Le(..) => "<=",
+        ExprKind::LShift(..) => "<<",
+        ExprKind::RShift(..) => ">>",
+        _ => ""
+    }
+}
+
+impl Inferer {
+    // instances of generic type aliases written in the source get their bodies once all aliases are known
+    fn instances(&mut self) {
+        let instances = self.types.iter()
+            .enumerate()
+            .filter_map(|(i, typ)| match (typ.kind(), typ.location()) {
+                (TypeKind::Instance(ident, args, None), Some(loc)) => Some((i as TypeIndex, ident.clone(), args.len(), loc.clone())),
+                _ => None
+            })
+            .collect::<Vec<_>>();
+
+        for (index, ident, num_args, loc) in instances {
+            let alias = self.types.find_alias(&ident);
+            match alias.and_then(|alias| self.types.resolve(alias)) {
+                Some(TypeKind::Generic(params, _)) if params.len() != num_args => {
+                    let decl_loc = alias.and_then(|alias| self.types.get(alias)).and_then(|typ| typ.location().clone());
+                    self.error(TypeCheckError::WrongArity(ident, params.len(), params.len(), num_args, decl_loc), &loc);
+                }
+                Some(TypeKind::Generic(..)) => self.types.instantiate_body(index),
+                _ => self.error(TypeCheckError::NotGeneric(ident), &loc)
+            }
+        }
+    }
+
+    fn variable(&mut self, decl: &mut dyn Decl) {
+        let ident = decl.ident().clone();
+        let loc = decl.location().clone();
+        match_decl!{
+            mut decl;
+            manifest as ManifestDecl => {
+                self.expr(manifest.value_mut());
+                if let Some(typ) = *manifest.value().typ() {
+                    self.variables.insert(ident, typ);
+                }
+            },
+            global as GlobalDecl => {
+                self.expr(global.index_mut());
+                // globals are typed by their first use
+                let typ = global.typ().unwrap_or_else(|| self.types.fresh_var());
+                global.set_typ(typ);
+                self.variables.insert(ident, typ);
+            },
+            static_decl as StaticDecl => {
+                self.expr(static_decl.value_mut());
+                let typ = match *static_decl.typ() {
+                    Some(typ) => {
+                        self.coerce_declared(static_decl.value_mut(), typ, Some(loc));
+                        typ
+                    }
+                    None => static_decl.value().typ().unwrap_or_else(|| self.types.fresh_var())
+                };
+                static_decl.set_typ(typ);
+                self.variables.insert(ident, typ);
+            },
+            _ => ()
+        }
+    }
+
+    fn signature(&mut self, func: &mut Function) {
+        let params = func.params_mut().iter_mut()
+            .map(|param| match param.typ() {
+                Some(typ) => (*typ, param.location().clone()),
+                None => {
+                    let typ = self.types.fresh_var();
+                    param.set_typ(typ);
+                    (typ, param.location().clone())
+                }
+            })
+            .collect();
+
+        let return_type = match func.return_type() {
+            Some(typ) => *typ,
+            None => {
+                let typ = self.types.fresh_var();
+                func.set_return_type(typ);
+                typ
+            }
+        };
+
+        let signature = Signature {
+            loc: func.location().clone(),
+            type_params: func.type_params().clone(),
+            params,
+            required_params: func.required_params() as usize,
+            return_type
+        };
+        self.signatures.insert(func.ident().clone(), signature);
+    }
+
+    // untyped parameters get the type of their default value
+    fn default_values(&mut self, func: &mut Function) {
+        self.scopes.push(HashMap::new());
+        for param in func.params_mut() {
+            let typ = param.typ().expect("parameter without type variable");
+            let loc = param.location().clone();
+            if let Some(value) = param.default_value_mut() {
+                self.expr(value);
+                self.coerce_declared(value, typ, Some(loc));
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn function(&mut self, func: &mut Function) {
+        let return_type = func.return_type().expect("function without return type");
+        let param_types = func.params().iter()
+            .map(|param| *param.typ())
+            .collect::<Vec<_>>();
+
+        self.scopes.push(HashMap::new());
+        for param in func.params_mut() {
+            let typ = *param.typ();
+            self.pattern(param.pattern_mut(), typ);
+        }
+
+        match func.body_mut() {
+            FunctionBody::Expr(expr) => {
+                self.expr(expr);
+                self.coerce(expr, return_type);
+            }
+            FunctionBody::Stmt(stmt) => self.stmt(stmt),
+            FunctionBody::PatternMatchedExpr(branches) => for (patterns, expr) in branches {
+                self.scopes.push(HashMap::new());
+                for (pattern, typ) in patterns.iter_mut().zip(&param_types) {
+                    self.pattern(pattern, *typ);
+                }
+                self.expr(expr);
+                self.coerce(expr, return_type);
+                self.scopes.pop();
+            }
+            FunctionBody::PatternMatchedStmt(branches) => for (patterns, stmt) in branches {
+                self.scopes.push(HashMap::new());
+                for (pattern, typ) in patterns.iter_mut().zip(&param_types) {
+                    self.pattern(pattern, *typ);
+                }
+                self.stmt(stmt);
+                self.scopes.pop();
+            }
+        }
+
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, ident: &str, typ: TypeIndex) {
+        self.scopes.last_mut()
+            .expect("declaration outside of scope")
+            .insert(ident.to_string(), typ);
+    }
+
+    fn lookup_local(&self, ident: &str) -> Option<TypeIndex> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident).copied())
+    }
+
+    // follows aliases and bound type variables to the type representing `typ`
+    fn representative(&self, typ: TypeIndex) -> TypeIndex {
+        match self.types.get(typ).map(|typ| typ.kind()) {
+            Some(TypeKind::Alias(_, Some(inner)) | TypeKind::Var(Some(inner))) => self.representative(*inner),
+            _ => typ
+        }
+    }
+
+    fn is_nullary_variant(&self, ident: &str) -> bool {
+        self.types.find_variant(ident)
+            .map(|(_, _, variant)| variant.fields().is_empty())
+            .unwrap_or(false)
+    }
+
+    fn is_unbound(&self, typ: TypeIndex) -> bool {
+        matches!(self.types.get(self.representative(typ)).map(|typ| typ.kind()), Some(TypeKind::Var(None)))
+    }
+
+    // whether the unbound type variable `var` occurs in `typ`
+    fn occurs(&self, var: TypeIndex, typ: TypeIndex) -> bool {
+        let typ = self.representative(typ);
+        match self.types.get(typ).map(|typ| typ.kind()) {
+            _ if typ == var => true,
+            Some(TypeKind::Pointer(inner) | TypeKind::Slice(inner) | TypeKind::Array(inner, _)) => self.occurs(var, *inner),
+            Some(TypeKind::Record(fields)) => fields.iter().any(|field| self.occurs(var, field.typ())),
+            Some(TypeKind::Function(params, return_type)) => params.iter().chain([return_type]).any(|typ| self.occurs(var, *typ)),
+            Some(TypeKind::Instance(_, args, _)) => args.iter().any(|arg| self.occurs(var, *arg)),
+            _ => false
+        }
+    }
+
+    fn bind(&mut self, var: TypeIndex, typ: TypeIndex) -> bool {
+        if self.occurs(var, typ) {
+            return false
+        }
+
+        self.types.get_mut(var).unwrap().set_kind(TypeKind::Var(Some(typ)));
+        true
+    }
+
+    // makes `a` and `b` the same type, returns false if they are incompatible
+    fn unify(&mut self, a: TypeIndex, b: TypeIndex) -> bool {
+        let (a, b) = (self.representative(a), self.representative(b));
+        if a == b {
+            return true
+        }
+
+        let kinds = (self.types.get(a).unwrap().kind().clone(), self.types.get(b).unwrap().kind().clone());
+        match kinds {
+            (TypeKind::Var(None), _) => self.bind(a, b),
+            (_, TypeKind::Var(None)) => self.bind(b, a),
+            (TypeKind::Pointer(a), TypeKind::Pointer(b))
+                | (TypeKind::Slice(a), TypeKind::Slice(b))
+                | (TypeKind::Array(a, _), TypeKind::Array(b, _)) => self.unify(a, b),
+            // records are equal if their fields have the same names and types
+            (TypeKind::Record(a), TypeKind::Record(b)) => a.len() == b.len()
+                && a.iter().zip(&b).all(|(a, b)| a.ident() == b.ident())
+                && a.iter().zip(&b).all(|(a, b)| self.unify(a.typ(), b.typ())),
+            (TypeKind::Function(a_params, a_return), TypeKind::Function(b_params, b_return)) => a_params.len() == b_params.len()
+                && a_params.iter().zip(&b_params).all(|(a, b)| self.unify(*a, *b))
+                && self.unify(a_return, b_return),
+            // instances of the same generic type are equal if their type arguments are
+            (TypeKind::Instance(a, a_args, _), TypeKind::Instance(b, b_args, _)) if a == b => a_args.len() == b_args.len()
+                && a_args.iter().zip(&b_args).all(|(a, b)| self.unify(*a, *b)),
+            (TypeKind::Instance(_, _, Some(body)), _) => self.unify(body, b),
+            (_, TypeKind::Instance(_, _, Some(body))) => self.unify(a, body),
+            // sum types are only equal to themselves
+            (TypeKind::Sum(_), _) | (_, TypeKind::Sum(_)) => false,
+            (a, b) => a == b
+        }
+    }
+
+    fn coerce(&mut self, expr: &mut Expr, typ: TypeIndex) {
+        self.coerce_declared(expr, typ, None)
+    }
+
+    // makes `expr` of type `typ`, inserting an implicit cast between different scalar types;
+    // `decl_loc` points to where `typ` was required, if known
+    fn coerce_declared(&mut self, expr: &mut Expr, typ: TypeIndex, decl_loc: Option<Location>) {
+        let Some(found) = *expr.typ()
+        else {
+            expr.set_typ(typ);
+            return
+        };
+
+        // the fields of record literals are coerced one by one
+        if let Some(TypeKind::Record(decl_fields)) = self.types.resolve(typ).cloned()
+            && let ExprKind::Record(fields) = expr.kind_mut()
+            && fields.len() == decl_fields.len()
+            && fields.iter().zip(&decl_fields).all(|((ident, _), field)| ident == field.ident()) {
+            for ((_, value), field) in fields.iter_mut().zip(&decl_fields) {
+                self.coerce(value, field.typ());
+            }
+            expr.set_typ(typ);
+            return
+        }
+
+        if self.unify(found, typ) {
+            return
+        }
+
+        match (self.types.resolve(found), self.types.resolve(typ)) {
+            (Some(from), Some(to)) if is_scalar(from) && is_scalar(to) => {
+                *expr = take(expr).implicit_cast(typ);
+            }
+            // arrays are stored like vectors, so they can be used as pointers and slices
+            (Some(TypeKind::Array(elem, _)), Some(TypeKind::Pointer(inner) | TypeKind::Slice(inner))) => {
+                let (elem, inner) = (*elem, *inner);
+                if !self.unify(elem, inner) {
+                    self.errors.push(
+                        TypeCheckError::Mismatch(self.types.name_of(typ), self.types.name_of(found), decl_loc)
+                            .with_location(expr.location().clone())
+                    )
+                }
+            }
+            _ => self.errors.push(
+                TypeCheckError::Mismatch(self.types.name_of(typ), self.types.name_of(found), decl_loc)
+                    .with_location(expr.location().clone())
+            )
+        }
+    }
+
+    // type all of `typs` get coerced to: the widest one if all concrete ones are numbers, floating point
+    // numbers being wider than integers, the first concrete one otherwise
+    fn common_type(&self, typs: impl Iterator<Item = Option<TypeIndex>>) -> Option<TypeIndex> {
+        let typs = typs.flatten().collect::<Vec<_>>();
+        let concrete = typs.iter()
+            .filter_map(|typ| self.types.resolve(*typ).map(|kind| (*typ, kind)))
+            .collect::<Vec<_>>();
+
+        if !concrete.is_empty() && concrete.iter().all(|(_, kind)| is_scalar(kind)) {
+            let rank = |kind: &TypeKind| (kind.is_float(), kind.try_get_size().unwrap_or(0));
+            return concrete.into_iter()
+                .reduce(|widest, next| if rank(next.1) > rank(widest.1) { next } else { widest })
+                .map(|(typ, _)| typ)
+        }
+
+        concrete.first().map(|(typ, _)| typ).or(typs.first()).copied()
+    }
+
+    // record literals cast to a record type are laid out like it; other records can only be cast to
+    // records storing the same fields at the same offsets
+    fn record_cast(&mut self, inner: &mut Expr, typ: TypeIndex) {
+        let Some(TypeKind::Record(to)) = self.types.resolve(typ).cloned()
+        else {
+            return
+        };
+
+        if let ExprKind::Record(_) = inner.kind() {
+            self.coerce(inner, typ);
+            return
+        }
+
+        let Some(found) = *inner.typ()
+        else {
+            return
+        };
+        if let Some(TypeKind::Record(from)) = self.types.resolve(found).cloned()
+            && !self.unify(found, typ) && !self.same_layout(&from, &to) {
+            self.error(TypeCheckError::InvalidCast(self.types.name_of(found), self.types.name_of(typ)), inner.location());
+        }
+    }
+
+    fn is_scalar_conversion(&self, from: TypeIndex, to: TypeIndex) -> bool {
+        matches!((self.types.resolve(from), self.types.resolve(to)), (Some(from), Some(to)) if is_scalar(from) && is_scalar(to))
+    }
+
+    fn same_layout(&self, a: &[RecordField], b: &[RecordField]) -> bool {
+        let is_byte = |field: &RecordField| self.types.resolve(field.typ()).is_some_and(TypeKind::is_byte_sized);
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.ident() == b.ident() && is_byte(a) == is_byte(b))
+    }
+
+    fn unify_all<'e>(&mut self, exprs: impl IntoIterator<Item = &'e mut Expr>) -> Option<TypeIndex> {
+        let mut exprs = exprs.into_iter().collect::<Vec<_>>();
+        let typ = self.common_type(exprs.iter().map(|expr| *expr.typ()))?;
+        for expr in exprs.iter_mut() {
+            self.coerce(expr, typ);
+        }
+        Some(typ)
+    }
+
+    fn error(&mut self, err: TypeCheckError, loc: &Location) {
+        self.errors.push(err.with_location(loc.clone()))
+    }
+
+    // reports operands whose type doesn't satisfy `accepts`, unifies the operands otherwise
+    fn operands<'e>(&mut self, op: &'static str, accepts: fn(&TypeKind) -> bool, exprs: impl IntoIterator<Item = &'e mut Expr>) -> Option<TypeIndex> {
+        let exprs = exprs.into_iter().collect::<Vec<_>>();
+        let mut valid = true;
+        for expr in exprs.iter() {
+            if let Some(typ) = *expr.typ() && let Some(kind) = self.types.resolve(typ) && !accepts(kind) {
+                self.error(TypeCheckError::BadOperand(op, self.types.name_of(typ)), expr.location());
+                valid = false;
+            }
+        }
+
+        if valid { self.unify_all(exprs) } else { None }
+    }
+
+    // conditions have to be booleans, the parser casts them to `Bool` implicitly
+    fn condition(&mut self, condition: &mut Expr) {
+        let value = match condition.kind_mut() {
+            ExprKind::ImplicitCast(inner) => &mut **inner,
+            _ => &mut *condition
+        };
+        self.expr(value);
+
+        let bool_typ = self.types.get_or_define(TypeKind::Bool);
+        if let Some(typ) = *value.typ() && self.is_unbound(typ) {
+            self.unify(typ, bool_typ);
+        }
+        else if let Some(typ) = *value.typ() && let Some(kind) = self.types.resolve(typ) && kind != &TypeKind::Bool {
+            self.error(TypeCheckError::NonBoolCondition(self.types.name_of(typ)), value.location());
+        }
+    }
+
+    // element type of `base` when dereferenced or indexed
+    fn dereference(&mut self, base: &Expr, err: fn(String) -> TypeCheckError) -> Option<TypeIndex> {
+        let typ = (*base.typ())?;
+        let elem = self.element_type(Some(typ));
+        if elem.is_none() && self.types.resolve(typ).is_some() {
+            self.error(err(self.types.name_of(typ)), base.location());
+        }
+        elem
+    }
+
+    // element type of pointers, slices and arrays; unbound type variables become pointers
+    fn element_type(&mut self, typ: Option<TypeIndex>) -> Option<TypeIndex> {
+        let typ = typ?;
+        match self.types.resolve(typ) {
+            Some(TypeKind::Pointer(inner) | TypeKind::Slice(inner) | TypeKind::Array(inner, _)) => Some(*inner),
+            None if self.is_unbound(typ) => {
+                let elem = self.types.fresh_var();
+                let pointer = self.types.get_or_define(TypeKind::Pointer(elem));
+                self.unify(typ, pointer);
+                Some(elem)
+            }
+            _ => None
+        }
+    }
+
+    // sum types and lists are matched through pointers
+    fn strip_pointers(&self, typ: Option<TypeIndex>) -> Option<TypeIndex> {
+        match self.types.resolve(typ?) {
+            Some(TypeKind::Pointer(inner)) => self.strip_pointers(Some(*inner)),
+            _ => typ
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Located<Pattern>, typ: Option<TypeIndex>) {
+        let loc = pattern.location().clone();
+        match &mut **pattern {
+            Pattern::Any | Pattern::Remaining => (),
+            Pattern::Query(ident) if ident == "_" => (),
+            Pattern::Query(ident) if self.is_nullary_variant(ident) => {
+                let (sum, _, _) = self.variant(ident).unwrap();
+                if let Some(typ) = self.strip_pointers(typ) && !self.unify(typ, sum) {
+                    let err = TypeCheckError::Mismatch(self.types.name_of(typ), self.types.name_of(sum), None);
+                    self.error(err, &loc);
+                }
+            }
+            Pattern::Query(ident) => {
+                let typ = typ.unwrap_or_else(|| self.types.fresh_var());
+                let ident = ident.clone();
+                self.declare(&ident, typ);
+            }
+            Pattern::Term(term) => match term {
+                PatternTerm::Range(from, to) => {
+                    self.expr(from);
+                    self.expr(to);
+                    if let Some(typ) = typ.or_else(|| self.unify_all([&mut *from, &mut *to])) {
+                        self.coerce(from, typ);
+                        self.coerce(to, typ);
+                    }
+                }
+                PatternTerm::Basic(expr) | PatternTerm::Eq(expr) | PatternTerm::Ne(expr)
+                    | PatternTerm::Lt(expr) | PatternTerm::Le(expr) | PatternTerm::Gt(expr) | PatternTerm::Ge(expr) => {
+                    self.expr(expr);
+                    if let Some(typ) = typ {
+                        self.coerce(expr, typ);
+                    }
+                }
+            }
+            Pattern::Or(lhs, rhs) | Pattern::And(lhs, rhs) => {
+                self.pattern(lhs, typ);
+                self.pattern(rhs, typ);
+            }
+            Pattern::Variant(ident, args) => {
+                let Some((sum, fields, decl_loc)) = self.variant(ident)
+                else {
+                    return
+                };
+
+                if let Some(typ) = self.strip_pointers(typ) && !self.unify(typ, sum) {
+                    let err = TypeCheckError::Mismatch(self.types.name_of(typ), self.types.name_of(sum), None);
+                    self.error(err, &loc);
+                    return
+                }
+
+                if args.len() != fields.len() {
+                    let err = TypeCheckError::WrongArity(ident.clone(), fields.len(), fields.len(), args.len(), decl_loc);
+                    self.error(err, &loc);
+                    return
+                }
+
+                for (i, arg) in args.iter_mut().enumerate() {
+                    self.pattern(arg, fields.get(i).copied());
+                }
+            }
+            Pattern::Record(fields) => {
+                // without a known record type, the pattern determines the fields
+                let typ = match typ {
+                    Some(typ) if !self.is_unbound(typ) => typ,
+                    _ => {
+                        let record = fields.iter()
+                            .map(|(ident, _)| RecordField::new(ident.clone(), self.types.fresh_var()))
+                            .collect();
+                        let record = self.types.get_or_define(TypeKind::Record(record));
+                        if let Some(typ) = typ {
+                            self.unify(typ, record);
+                        }
+                        record
+                    }
+                };
+
+                for (ident, field) in fields.iter_mut() {
+                    let field_typ = self.types.find_field(typ, ident).map(|(typ, _)| typ);
+                    if field_typ.is_none() {
+                        let err = TypeCheckError::NoField(ident.clone(), self.types.name_of(typ));
+                        self.error(err, field.location());
+                    }
+                    self.pattern(field, field_typ);
+                }
+            }
+            Pattern::List(elems) => {
+                let typ = match self.strip_pointers(typ) {
+                    Some(typ) if self.is_unbound(typ) => {
+                        let elem = self.types.fresh_var();
+                        let slice = self.types.get_or_define(TypeKind::Slice(elem));
+                        self.unify(typ, slice);
+                        Some(typ)
+                    }
+                    typ => typ
+                };
+
+                let elem_typ = self.element_type(typ);
+                for elem in elems.iter_mut() {
+                    self.pattern(elem, elem_typ);
+                }
+            }
+        }
+    }
+
+    // patterns of `match` and `every` branches are matched against the values of `args`
+    fn branch_patterns(&mut self, patterns: &mut [Located<Pattern>], args: &[Expr]) {
+        for (pattern, arg) in patterns.iter_mut().zip(args) {
+            self.pattern(pattern, *arg.typ());
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        // casts to the type of an untyped expression are no-ops
+        if expr.typ().is_none() && let ExprKind::ImplicitCast(inner) = expr.kind_mut() {
+            let inner = take(inner);
+            *expr = inner;
+        }
+
+        if let ExprKind::Ident(ident) = expr.kind() && self.is_function(ident) && self.instantiate(expr).is_some() {
+            return
+        }
+
+        let current = *expr.typ();
+        let op = operator(expr.kind());
+        let loc = expr.location().clone();
+        let typ = match expr.kind_mut() {
+            ExprKind::Ident(ident) => match self.lookup_local(ident).or_else(|| self.variables.get(ident).copied()) {
+                Some(typ) => Some(typ),
+                None => self.variant(ident)
+                    .filter(|(_, fields, _)| fields.is_empty())
+                    .map(|(sum, _, _)| sum)
+                    .or_else(|| self.function_type(ident))
+            },
+            ExprKind::Instance(..) => current,
+            ExprKind::Atom(_) => Some(self.types.get_or_define(TypeKind::Atom)),
+            ExprKind::IntLit(_) | ExprKind::StringLit(_) => current,
+            ExprKind::FloatLit(_) => Some(current.unwrap_or_else(|| self.types.get_or_define(TypeKind::Float64))),
+            ExprKind::CharLit(_) => Some(self.types.get_or_define(TypeKind::Char)),
+            ExprKind::True | ExprKind::False => Some(self.types.get_or_define(TypeKind::Bool)),
+
+            ExprKind::Add(lhs, rhs) | ExprKind::Sub(lhs, rhs) | ExprKind::Mul(lhs, rhs)
+                | ExprKind::Div(lhs, rhs) | ExprKind::Mod(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+                self.operands(op, is_scalar, [&mut **lhs, &mut **rhs])
+            }
+            ExprKind::And(lhs, rhs) | ExprKind::Or(lhs, rhs) | ExprKind::XOr(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+                self.operands(op, is_bitwise, [&mut **lhs, &mut **rhs])
+            }
+            ExprKind::Eq(lhs, rhs) | ExprKind::Ne(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+                self.operands(op, is_any, [&mut **lhs, &mut **rhs]);
+                Some(self.types.get_or_define(TypeKind::Bool))
+            }
+            ExprKind::Gt(lhs, rhs) | ExprKind::Ge(lhs, rhs) | ExprKind::Lt(lhs, rhs) | ExprKind::Le(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+                self.operands(op, is_ordered, [&mut **lhs, &mut **rhs]);
+                Some(self.types.get_or_define(TypeKind::Bool))
+            }
+            ExprKind::LShift(lhs, rhs) | ExprKind::RShift(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+                self.operands(op, is_integer, [&mut **rhs]);
+                self.operands(op, is_integer, [&mut **lhs])
+            }
+            ExprKind::Abs(inner) | ExprKind::Neg(inner) => {
+                self.expr(inner);
+                self.operands(op, is_scalar, [&mut **inner])
+            }
+            ExprKind::Not(inner) => {
+                self.expr(inner);
+                self.operands(op, is_bitwise, [&mut **inner])
+            }
+
+            ExprKind::Ref(inner) => {
+                self.expr(inner);
+                inner.typ().map(|typ| self.types.get_or_define(TypeKind::Pointer(typ)))
+            }
+            ExprKind::Deref(inner) => {
+                self.expr(inner);
+                self.dereference(inner, TypeCheckError::InvalidDeref)
+            }
+            ExprKind::Index(base, index) => {
+                self.expr(base);
+                self.expr(index);
+                self.operands("[]", is_integer, [&mut **index]);
+                self.dereference(base, TypeCheckError::InvalidIndex)
+            }
+            // vectors of `vec` declarations consist of words
+            ExprKind::Vector(size) => {
+                self.expr(size);
+                self.operands("vec", is_integer, [&mut **size]);
+                let word = self.types.get_or_define(TypeKind::Int64);
+                Some(self.types.get_or_define(TypeKind::Pointer(word)))
+            }
+            ExprKind::Array(elems) => {
+                elems.iter_mut().for_each(|elem| self.expr(elem));
+                let elem = self.unify_all(elems.iter_mut()).unwrap_or_else(|| self.types.fresh_var());
+                let len_typ = self.types.get_or_define(TypeKind::UInt64);
+                let len = Expr::new(loc, Some(len_typ), ExprKind::IntLit(elems.len() as u64));
+                Some(self.types.get_or_define(TypeKind::Array(elem, Box::new(len))))
+            }
+            ExprKind::Record(fields) => {
+                let fields = fields.iter_mut()
+                    .map(|(ident, value)| {
+                        self.expr(value);
+                        let typ = value.typ().unwrap_or_else(|| self.types.fresh_var());
+                        RecordField::new(ident.clone(), typ)
+                    })
+                    .collect();
+                Some(self.types.get_or_define(TypeKind::Record(fields)))
+            }
+            ExprKind::Field(base, field) => {
+                self.expr(base);
+                let typ = base.typ().unwrap_or_else(|| self.types.fresh_var());
+                let field_typ = self.types.find_field(typ, field).map(|(typ, _)| typ);
+                if field_typ.is_none() {
+                    let name = if self.types.resolve(typ).is_some() { self.types.name_of(typ) } else { "?".into() };
+                    self.error(TypeCheckError::NoField(field.clone(), name), &loc);
+                }
+                field_typ
+            }
+            ExprKind::Slice(base, from, to) => {
+                self.expr(base);
+                self.expr(from);
+                self.expr(to);
+                self.operands("[]", is_integer, [&mut **from, &mut **to]);
+                self.dereference(base, TypeCheckError::InvalidIndex)
+                    .map(|elem| self.types.get_or_define(TypeKind::Slice(elem)))
+            }
+
+            ExprKind::Cast(inner) => {
+                self.expr(inner);
+                if let Some(typ) = current {
+                    self.record_cast(inner, typ);
+                }
+                current
+            }
+            ExprKind::ImplicitCast(inner) => {
+                self.expr(inner);
+                let typ = current.expect("untyped implicit cast");
+                // an untyped value is inferred to be of the type it is cast to, other values have to be
+                // of it already or be converted from one scalar type to another
+                match *inner.typ() {
+                    Some(inner_typ) if self.is_unbound(inner_typ) => {
+                        self.unify(inner_typ, typ);
+                    }
+                    Some(_) if matches!(inner.kind(), ExprKind::Record(_)) => self.coerce(inner, typ),
+                    Some(inner_typ) if !self.unify(inner_typ, typ) && !self.is_scalar_conversion(inner_typ, typ) => {
+                        self.error(TypeCheckError::Mismatch(self.types.name_of(typ), self.types.name_of(inner_typ), None), inner.location());
+                    }
+                    _ => ()
+                }
+                Some(typ)
+            }
+            ExprKind::ValOf(stmt) => {
+                let typ = current.unwrap_or_else(|| self.types.fresh_var());
+                self.valofs.push(typ);
+                self.stmt(stmt);
+                self.valofs.pop();
+                Some(typ)
+            }
+            ExprKind::FuncCall(callee, args) => {
+                for arg in args.iter_mut() {
+                    self.expr(arg);
+                }
+                self.call(callee, args)
+            }
+            ExprKind::Lambda(lambda) => Some(self.lambda(lambda)),
+
+            ExprKind::Conditional(condition, if_branch, else_branch) => {
+                self.condition(condition);
+                self.expr(if_branch);
+                self.expr(else_branch);
+                self.unify_all([&mut **if_branch, &mut **else_branch])
+            }
+            ExprKind::Match(args, branches) | ExprKind::Every(args, branches) => {
+                for arg in args.iter_mut() {
+                    self.expr(arg);
+                }
+                for (patterns, body) in branches.iter_mut() {
+                    self.scopes.push(HashMap::new());
+                    self.branch_patterns(patterns, args);
+                    self.expr(body);
+                    self.scopes.pop();
+                }
+                self.unify_all(branches.iter_mut().map(|(_, body)| &mut **body))
+            }
+        };
+
+        if let Some(typ) = typ {
+            expr.set_typ(typ);
+        }
+    }
+
+    // the result of a compound assignment is stored in its target, so the value gets the target's type
+    // instead of the common type of both
+    fn compound_assign(&mut self, operation: &mut Expr) {
+        let op = operator(operation.kind());
+        let accepts = match operation.kind() {
+            ExprKind::LShift(..) | ExprKind::RShift(..) => is_integer,
+            ExprKind::And(..) | ExprKind::Or(..) | ExprKind::XOr(..) => is_bitwise,
+            _ => is_scalar
+        };
+        let Some((target, value)) = operation.binary_operands_mut()
+        else {
+            return self.expr(operation)
+        };
+
+        self.expr(target);
+        self.expr(value);
+        if let Some(typ) = *target.typ() {
+            self.coerce(value, typ);
+        }
+        if let Some(typ) = self.operands(op, accepts, [target, value]) {
+            operation.set_typ(typ);
+        }
+    }
+
+    fn call(&mut self, callee: &mut Expr, args: &mut [Expr]) -> Option<TypeIndex> {
+        let ident = match callee.kind() {
+            ExprKind::Ident(ident) if self.is_function(ident) => ident.clone(),
+            _ => {
+                self.expr(callee);
+                return self.indirect_call(callee, args)
+            }
+        };
+
+        if let Some((sum, fields, decl_loc)) = self.variant(&ident) {
+            if args.len() != fields.len() {
+                let err = TypeCheckError::WrongArity(ident, fields.len(), fields.len(), args.len(), decl_loc);
+                self.error(err, callee.location());
+                return Some(sum)
+            }
+
+            for (arg, field) in args.iter_mut().zip(fields) {
+                self.coerce_declared(arg, field, decl_loc.clone());
+            }
+            return Some(sum)
+        }
+
+        // calls to external functions stay untyped
+        let signature = self.signatures.get(&ident)?;
+        let (mut params, required_params, mut return_type) = (signature.params.clone(), signature.required_params, signature.return_type);
+        let decl_loc = Some(signature.loc.clone());
+        if self.instantiate(callee).is_some() {
+            let Some(TypeKind::Function(instance_params, instance_return)) = callee.typ().and_then(|typ| self.types.resolve(typ)).cloned()
+            else {
+                unreachable!("generic function instantiated with a non-function type")
+            };
+            params = instance_params.into_iter().zip(params).map(|(typ, (_, loc))| (typ, loc)).collect();
+            return_type = instance_return;
+        }
+
+        if args.len() < required_params || args.len() > params.len() {
+            let err = TypeCheckError::WrongArity(ident, required_params, params.len(), args.len(), decl_loc);
+            self.error(err, callee.location());
+            return Some(return_type)
+        }
+
+        for (arg, (param, param_loc)) in args.iter_mut().zip(params) {
+            self.coerce_declared(arg, param, Some(param_loc));
+        }
+        Some(return_type)
+    }
+
+    // whether `ident` can refer to a function or variant, instead of a local variable or a global declaration
+    fn is_function(&self, ident: &str) -> bool {
+        self.lookup_local(ident).is_none() && !self.variables.contains_key(ident)
+    }
+
+    // replaces a reference to a generic function by an instance with fresh type variables as type arguments,
+    // returns the type of the instance
+    fn instantiate(&mut self, expr: &mut Expr) -> Option<TypeIndex> {
+        let ExprKind::Ident(ident) = expr.kind() else {
+            return None
+        };
+        let signature = self.signatures.get(ident).filter(|signature| !signature.type_params.is_empty())?;
+        let (type_params, params, return_type) = (signature.type_params.clone(), signature.params.clone(), signature.return_type);
+
+        let type_args = type_params.iter().map(|_| self.types.fresh_var()).collect::<Vec<_>>();
+        let substitution = type_params.into_iter().zip(type_args.iter().copied()).collect();
+        let params = params.into_iter()
+            .map(|(typ, _)| self.types.substitute(typ, &substitution))
+            .collect();
+        let return_type = self.types.substitute(return_type, &substitution);
+        let typ = self.types.get_or_define(TypeKind::Function(params, return_type));
+
+        *expr.kind_mut() = ExprKind::Instance(ident.clone(), type_args);
+        expr.set_typ(typ);
+        Some(typ)
+    }
+
+    // sum type and field types of the variant `ident` and where the sum type is declared;
+    // variants of generic sum types instantiate them with fresh type variables
+    fn variant(&mut self, ident: &str) -> Option<(TypeIndex, Vec<TypeIndex>, Option<Location>)> {
+        let (sum, tag, variant) = self.types.find_variant(ident)?;
+        let fields = variant.fields().clone();
+        let decl_loc = self.types.get(sum).and_then(|typ| typ.location().clone());
+        let Some((alias, type_params)) = self.types.generic_of(sum)
+        else {
+            return Some((sum, fields, decl_loc))
+        };
+
+        let type_args = type_params.iter().map(|_| self.types.fresh_var()).collect();
+        let instance = self.types.instantiate(&alias, type_args);
+        let fields = match self.types.resolve(instance) {
+            Some(TypeKind::Sum(variants)) => variants[tag as usize].fields().clone(),
+            _ => fields
+        };
+        Some((instance, fields, decl_loc))
+    }
+
+    // type of the address of the function `ident`
+    fn function_type(&mut self, ident: &str) -> Option<TypeIndex> {
+        let signature = self.signatures.get(ident)?;
+        let params = signature.params.iter().map(|(typ, _)| *typ).collect();
+        let return_type = signature.return_type;
+        Some(self.types.get_or_define(TypeKind::Function(params, return_type)))
+    }
+
+    // calls through function pointers pass all parameters, since default values are not known
+    fn indirect_call(&mut self, callee: &Expr, args: &mut [Expr]) -> Option<TypeIndex> {
+        let typ = (*callee.typ())?;
+        if self.is_unbound(typ) {
+            let params = args.iter()
+                .map(|arg| arg.typ().unwrap_or_else(|| self.types.fresh_var()))
+                .collect();
+            let return_type = self.types.fresh_var();
+            let function = self.types.get_or_define(TypeKind::Function(params, return_type));
+            self.unify(typ, function);
+            return Some(return_type)
+        }
+
+        let Some(TypeKind::Function(params, return_type)) = self.types.resolve(typ).cloned()
+        else {
+            if self.types.resolve(typ).is_some() {
+                self.error(TypeCheckError::NotCallable(self.types.name_of(typ)), callee.location());
+            }
+            return None
+        };
+
+        if args.len() != params.len() {
+            let ident = match callee.kind() {
+                ExprKind::Ident(ident) => ident.clone(),
+                _ => self.types.name_of(typ)
+            };
+            let err = TypeCheckError::WrongArity(ident, params.len(), params.len(), args.len(), None);
+            self.error(err, callee.location());
+            return Some(return_type)
+        }
+
+        for (arg, param) in args.iter_mut().zip(params) {
+            self.coerce(arg, param);
+        }
+        Some(return_type)
+    }
+
+    // lambda bodies see the variables of the enclosing scopes, so they are inferred in place
+    fn lambda(&mut self, lambda: &mut Lambda) -> TypeIndex {
+        let params = lambda.params().iter()
+            .map(|param| param.typ().expect("lambda parameter without type"))
+            .collect::<Vec<_>>();
+        let return_type = match lambda.return_type() {
+            Some(typ) => *typ,
+            None => {
+                let typ = self.types.fresh_var();
+                lambda.set_return_type(typ);
+                typ
+            }
+        };
+
+        self.scopes.push(HashMap::new());
+        for (param, typ) in lambda.params_mut().iter_mut().zip(&params) {
+            self.pattern(param.pattern_mut(), Some(*typ));
+        }
+
+        match lambda.body_mut() {
+            BasicFunctionBody::Expr(expr) => {
+                self.expr(expr);
+                self.coerce(expr, return_type);
+            }
+            BasicFunctionBody::Stmt(stmt) => self.stmt(stmt)
+        }
+        self.scopes.pop();
+
+        self.types.get_or_define(TypeKind::Function(params, return_type))
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt.kind_mut() {
+            StmtKind::Nop | StmtKind::Return | StmtKind::Break | StmtKind::Next | StmtKind::DefaultCase
+                | StmtKind::Finish | StmtKind::Goto(_) => (),
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Block(stmts) => {
+                self.scopes.push(HashMap::new());
+                for stmt in stmts {
+                    self.stmt(stmt);
+                }
+                self.scopes.pop();
+            }
+            StmtKind::ResultIs(expr) => {
+                self.expr(expr);
+                if let Some(typ) = self.valofs.last().copied() {
+                    self.coerce(expr, typ);
+                }
+            }
+            StmtKind::If(condition, if_branch, else_branch) => {
+                self.condition(condition);
+                self.stmt(if_branch);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            StmtKind::Unless(condition, body) | StmtKind::While(condition, body) | StmtKind::Until(condition, body) => {
+                self.condition(condition);
+                self.stmt(body);
+            }
+            StmtKind::Loop(body) | StmtKind::Label(_, body) => self.stmt(body),
+            StmtKind::RepeatWhile(body, condition) | StmtKind::RepeatUntil(body, condition) => {
+                self.stmt(body);
+                self.condition(condition);
+            }
+            StmtKind::For(iterator, init, limit, step, body) => {
+                self.expr(init);
+                for expr in [limit, step].into_iter().flatten() {
+                    self.expr(expr);
+                    if let Some(typ) = *init.typ() {
+                        self.coerce(expr, typ);
+                    }
+                }
+
+                self.scopes.push(HashMap::new());
+                self.pattern(iterator, *init.typ());
+                self.stmt(body);
+                self.scopes.pop();
+            }
+            StmtKind::SwitchOn(condition, body) => {
+                self.expr(condition);
+                self.switches.push(*condition.typ());
+                self.stmt(body);
+                self.switches.pop();
+            }
+            StmtKind::Case(value) => {
+                self.expr(value);
+                if let Some(Some(typ)) = self.switches.last().copied() {
+                    self.coerce(value, typ);
+                }
+            }
+            StmtKind::Match(args, branches) | StmtKind::Every(args, branches) => {
+                for arg in args.iter_mut() {
+                    self.expr(arg);
+                }
+                for (patterns, body) in branches.iter_mut() {
+                    self.scopes.push(HashMap::new());
+                    self.branch_patterns(patterns, args);
+                    self.stmt(body);
+                    self.scopes.pop();
+                }
+            }
+            StmtKind::Binding(bindings) => {
+                for (_, expr) in bindings.iter_mut() {
+                    self.expr(expr);
+                }
+                for (pattern, expr) in bindings.iter_mut() {
+                    self.pattern(pattern, *expr.typ());
+                }
+            }
+            StmtKind::Assign(targets, values) => {
+                for expr in targets.iter_mut().chain(values.iter_mut()) {
+                    self.expr(expr);
+                }
+                for (target, value) in targets.iter().zip(values.iter_mut()) {
+                    if let Some(typ) = *target.typ() {
+                        self.coerce(value, typ);
+                    }
+                }
+            }
+            StmtKind::CompoundAssign(operation) => self.compound_assign(operation),
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        self,
+        expr::{Expr, ExprKind},
+        pattern::Pattern,
+        stmt::{Stmt, StmtKind},
+        visitor::{Action, ScopeVisitor, Traversable, Visitor},
+        Function
+    },
+    source_file::{Located, Location, WithLocation}
+};
+
+use super::{scope, Error, TypeCheckError};
+
+// Functions, lambdas and `valof` bodies each have their own labels. A `goto` can only jump to
+// labels of its own body, since leaving a function or `valof` any other way than `return` or
+// `resultis` would skip its result. Within a body, labels are only visible in the block whose
+// statements they label and the blocks nested in it, and a jump forward can't skip a `let`
+// declaration of that block.
+pub(super) fn resolve_labels(ast: &mut ast::Program) -> Vec<Error> {
+    let mut resolver = LabelResolver {
+        bodies: vec![],
+        scopes: vec![],
+        open: vec![],
+        gotos: vec![],
+        errors: vec![]
+    };
+    let _ = ast.traverse(&mut resolver);
+
+    for (label, body, path, loc) in std::mem::take(&mut resolver.gotos) {
+        if let Some(err) = resolver.resolve(&label, body, &path) {
+            resolver.errors.push(err.with_location(loc));
+        }
+    }
+    resolver.errors
+}
+
+struct LabelBody {
+    parent: Option<usize>,
+    is_valof: bool,
+    labels: HashMap<String, Location>,
+    // block labels are visible in and the position of the statement they label, `None` for labels
+    // nested in other statements than blocks
+    positions: HashMap<String, Option<(usize, usize)>>,
+    // statements currently visited, innermost last
+    stmts: Vec<OpenStmt>,
+    // scope of the statement the body consists of, labels of it are visible in the whole body
+    root: usize
+}
+
+#[derive(Clone, Copy)]
+enum OpenKind {
+    Block(usize), // scope of the block
+    Label,
+    Other
+}
+
+// scopes enclosing a statement, outermost first, each with the position of the statement containing it
+type ScopePath = Vec<(usize, usize)>;
+
+#[derive(Clone, Copy)]
+struct OpenStmt {
+    kind: OpenKind,
+    // position in the statements of the innermost block, the same as the labelled statement for labels
+    index: usize
+}
+
+#[derive(Default)]
+struct LabelScope {
+    stmts: usize,
+    // positions and locations of the `let` declarations among the statements of the block
+    bindings: Vec<(usize, Location)>
+}
+
+struct LabelResolver {
+    bodies: Vec<LabelBody>,
+    scopes: Vec<LabelScope>,
+    // bodies currently visited, innermost last
+    open: Vec<usize>,
+    // label, body, scopes enclosing the `goto` with the position of the statement containing it
+    gotos: Vec<(String, usize, ScopePath, Location)>,
+    errors: Vec<Error>
+}
+
+impl LabelResolver {
+    fn enter_body(&mut self, is_valof: bool) {
+        self.bodies.push(LabelBody {
+            parent: self.open.last().copied(),
+            is_valof,
+            labels: HashMap::new(),
+            positions: HashMap::new(),
+            stmts: vec![],
+            root: 0
+        });
+        self.open.push(self.bodies.len() - 1);
+    }
+
+    fn new_scope(&mut self) -> usize {
+        self.scopes.push(LabelScope::default());
+        self.scopes.len() - 1
+    }
+
+    // scope a statement of `body` is directly part of, looking through the labels in front of it
+    fn direct_scope(&self, body: usize) -> Option<usize> {
+        let body = &self.bodies[body];
+        match body.stmts.iter().rev().find(|stmt| !matches!(stmt.kind, OpenKind::Label)) {
+            Some(OpenStmt { kind: OpenKind::Block(scope), .. }) => Some(*scope),
+            Some(_) => None,
+            None => Some(body.root)
+        }
+    }
+
+    // scopes enclosing the next statement of `body`, each with the position of the statement containing it
+    fn enclosing_scopes(&self, body: usize, index: usize) -> ScopePath {
+        let stmts = &self.bodies[body].stmts;
+        let mut scopes = vec![(self.bodies[body].root, stmts.first().map(|stmt| stmt.index).unwrap_or(index))];
+        for (i, stmt) in stmts.iter().enumerate() {
+            if let OpenKind::Block(scope) = stmt.kind {
+                scopes.push((scope, stmts.get(i + 1).map(|stmt| stmt.index).unwrap_or(index)));
+            }
+        }
+        scopes
+    }
+
+    // whether `inner` is `outer` or only separated from it by `valof` bodies
+    fn in_valof_of(&self, mut inner: usize, outer: usize) -> bool {
+        while inner != outer {
+            match self.bodies[inner] {
+                LabelBody { parent: Some(parent), is_valof: true, .. } => inner = parent,
+                _ => return false
+            }
+        }
+        true
+    }
+
+    fn resolve(&self, label: &str, body: usize, path: &[(usize, usize)]) -> Option<TypeCheckError> {
+        if let Some(label_loc) = self.bodies[body].labels.get(label) {
+            let position = self.bodies[body].positions[label]
+                .and_then(|(scope, index)| path.iter().find(|(enclosing, _)| *enclosing == scope).map(|(_, from)| (scope, *from, index)));
+            let Some((scope, from, to)) = position
+            else {
+                return Some(TypeCheckError::JumpIntoBlock(label.to_string(), label_loc.clone()))
+            };
+
+            return self.scopes[scope].bindings.iter()
+                .find(|(index, _)| from < *index && *index < to)
+                .map(|(_, binding_loc)| TypeCheckError::JumpOverDecl(label.to_string(), binding_loc.clone()))
+        }
+
+        // labels of enclosing bodies are out of reach, but get a more helpful error than undefined ones
+        let mut enclosing = self.bodies[body].parent;
+        while let Some(outer) = enclosing {
+            if let Some(loc) = self.bodies[outer].labels.get(label) {
+                return Some(if self.in_valof_of(body, outer) {
+                    TypeCheckError::JumpOutOfValof(label.to_string(), loc.clone())
+                }
+                else {
+                    TypeCheckError::JumpAcrossFunctions(label.to_string(), loc.clone())
+                })
+            }
+            enclosing = self.bodies[outer].parent;
+        }
+
+        // as are labels of `valof` bodies nested in this one, which are visited in source order
+        let into_valof = self.bodies.iter().enumerate()
+            .find(|(i, other)| *i != body && other.labels.contains_key(label) && self.in_valof_of(*i, body));
+        Some(match into_valof {
+            Some((_, other)) => TypeCheckError::JumpIntoValof(label.to_string(), other.labels[label].clone()),
+            None => self.undefined(label, body)
+        })
+    }
+
+    fn undefined(&self, label: &str, body: usize) -> TypeCheckError {
+        let similar = scope::most_similar(label, self.bodies[body].labels.keys()).cloned();
+        TypeCheckError::UndefinedLabel(label.to_string(), similar)
+    }
+}
+
+impl ScopeVisitor for LabelResolver {}
+
+impl Visitor<ast::Program, Error> for LabelResolver {
+    fn visit(&mut self, _node: &mut ast::Program) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<ast::Section, Error> for LabelResolver {
+    fn visit(&mut self, _node: &mut ast::Section) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Function, Error> for LabelResolver {
+    fn visit_before(&mut self, _node: &mut Function) -> Result<Action, Error> {
+        self.enter_body(false);
+        Ok(Action::Continue)
+    }
+
+    fn visit(&mut self, _node: &mut Function) -> Result<Action, Error> {
+        self.open.pop();
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<ast::Param, Error> for LabelResolver {
+    fn visit(&mut self, _node: &mut ast::Param) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Stmt, Error> for LabelResolver {
+    fn visit_before(&mut self, node: &mut Stmt) -> Result<Action, Error> {
+        let Some(&body) = self.open.last()
+        else {
+            return Ok(Action::Continue)
+        };
+
+        // each statement a body consists of, like the branches of pattern-matched functions, is a scope of its own
+        if self.bodies[body].stmts.is_empty() {
+            self.bodies[body].root = self.new_scope();
+        }
+
+        let index = match self.bodies[body].stmts.last() {
+            Some(OpenStmt { kind: OpenKind::Block(scope), .. }) => {
+                let scope = &mut self.scopes[*scope];
+                scope.stmts += 1;
+                scope.stmts - 1
+            }
+            Some(parent) => parent.index,
+            None => 0
+        };
+        let scope = self.direct_scope(body);
+
+        let kind = match node.kind() {
+            StmtKind::Block(_) => OpenKind::Block(self.new_scope()),
+            StmtKind::Label(label, _) => {
+                let labels = &mut self.bodies[body].labels;
+                if let Some(first) = labels.get(label) {
+                    let err = TypeCheckError::DuplicateLabel(label.clone(), first.clone());
+                    self.errors.push(err.with_location(node.location().clone()));
+                }
+                else {
+                    labels.insert(label.clone(), node.location().clone());
+                    self.bodies[body].positions.insert(label.clone(), scope.map(|scope| (scope, index)));
+                }
+                OpenKind::Label
+            }
+            StmtKind::Goto(label) => {
+                let path = self.enclosing_scopes(body, index);
+                self.gotos.push((label.clone(), body, path, node.location().clone()));
+                OpenKind::Other
+            }
+            StmtKind::Binding(_) => {
+                if let Some(scope) = scope {
+                    self.scopes[scope].bindings.push((index, node.location().clone()));
+                }
+                OpenKind::Other
+            }
+            _ => OpenKind::Other
+        };
+        self.bodies[body].stmts.push(OpenStmt { kind, index });
+        Ok(Action::Continue)
+    }
+
+    fn visit(&mut self, _node: &mut Stmt) -> Result<Action, Error> {
+        if let Some(&body) = self.open.last() {
+            self.bodies[body].stmts.pop();
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Expr, Error> for LabelResolver {
+    fn visit_before(&mut self, node: &mut Expr) -> Result<Action, Error> {
+        match node.kind() {
+            ExprKind::ValOf(_) => self.enter_body(true),
+            ExprKind::Lambda(_) => self.enter_body(false),
+            _ => ()
+        }
+        Ok(Action::Continue)
+    }
+
+    fn visit(&mut self, node: &mut Expr) -> Result<Action, Error> {
+        if let ExprKind::ValOf(_) | ExprKind::Lambda(_) = node.kind() {
+            self.open.pop();
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Located<Pattern>, Error> for LabelResolver {
+    fn visit(&mut self, _node: &mut Located<Pattern>) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
@@ -1,23 +1,413 @@
 mod scope;
+mod infer;
+mod exhaustiveness;
+mod constant;
+mod monomorphise;
+mod labels;
+mod modules;
+mod format;
 
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{collections::HashSet, fmt::Display, sync::{Arc, Mutex}};
 
-use crate::{ast::{self, visitor::{ASTVisitor, Visitor, Traversable}}, source_file::Located};
+use crate::{
+    ast::{self, expr::{Expr, ExprKind}, pattern::Pattern, stmt::StmtKind, types::TypeKind, visitor::{ScopeVisitor, Visitor, Traversable}},
+    error::{CompilerError, Severity},
+    source_file::{Located, Location, WithLocation}
+};
 
-use self::scope::Scope;
+use self::scope::{Binding, Scope};
+
+pub(crate) use self::modules::{is_external, native_functions};
 
 pub enum TypeCheckError {
+    UndefinedIdent(String, Option<String>), // identifier, most similar defined identifier
+    Mismatch(String, String, Option<Location>), // expected type, found type, declaration of the expected type
+    BadOperand(&'static str, String), // operator, operand type
+    WrongArity(String, usize, usize, usize, Option<Location>), // function, required args, max. args, given args, declaration
+    NonBoolCondition(String),
+    InvalidDeref(String),
+    InvalidIndex(String),
+    NoField(String, String), // field, record type
+    NotCallable(String),
+    InvalidCast(String, String), // record type, record type it is cast to
+    NonExhaustive(Vec<String>, bool), // missing cases, whether there are more than listed
+    UnreachableBranch,
+    NotConstant,
+    CyclicConstant(String),
+    ConstantDivisionByZero,
+    InvalidGlobal(i64),
+    InvalidVecSize(i64),
+    NotAssignable,
+    AssignToCapture(String),
+    NotGeneric(String),
+    InstantiationLimit(String),
+    UndefinedLabel(String, Option<String>), // label, most similar label of the same body
+    DuplicateLabel(String, Location), // label, first definition
+    JumpIntoValof(String, Location), // label, definition
+    JumpOutOfValof(String, Location),
+    JumpAcrossFunctions(String, Location),
+    JumpIntoBlock(String, Location),
+    JumpOverDecl(String, Location), // label, skipped declaration
+    UnknownSection(String, Option<String>), // required section, most similar section
+    CyclicRequire(Vec<String>), // sections of the cycle, starting and ending with the same one
+    DuplicateDecl(String, String, Location), // identifier, section of the first declaration, first declaration
+    AmbiguousIdent(String, String, String), // identifier, two of the required sections declaring it
+    PrivateDecl(String, String, Location), // identifier, section, declaration
+    NotRequired(String, String), // identifier, section declaring it
+    NoSectionDecl(String, String, Option<String>), // section, identifier, most similar public declaration
+    ShadowedQualified(String, String, Location), // section, identifier, local variable
+    UnknownDirective(char),
+    FormatArgCount(usize, usize), // directives, given arguments
+    FormatArgType(String, &'static str, String) // directive, expected kind of value, found type
+}
+
+impl TypeCheckError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::UnreachableBranch => Severity::Warning,
+            _ => Severity::Error
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Self::UndefinedIdent(_, Some(similar)) | Self::UndefinedLabel(_, Some(similar))
+                | Self::UnknownSection(_, Some(similar)) | Self::NoSectionDecl(_, _, Some(similar)) => Some(format!("Did you mean `{similar}`?")),
+            Self::NonBoolCondition(_) => Some("Compare the value explicitly, e.g. with `~= 0`.".into()),
+            Self::WrongArity(_, required, max, given, _) if given < required && required < max =>
+                Some(format!("The last {} parameters have default values.", max - required)),
+            Self::NonExhaustive(..) => Some("Add branches for the missing cases or a `?` branch.".into()),
+            Self::UnreachableBranch => Some("All values it matches are matched by previous branches.".into()),
+            Self::NotConstant => Some("Manifest constants, static initializers, global numbers, `vec` sizes and table elements are evaluated at compile time; table elements may also be strings, tables and functions.".into()),
+            Self::NotAssignable => Some("Only variables, dereferenced pointers, indexed elements and record fields can be assigned to.".into()),
+            Self::AssignToCapture(_) => Some("Lambdas capture variables by value; capture a pointer to the variable to change it.".into()),
+            Self::InstantiationLimit(_) => Some("Each recursive call instantiates it with larger type arguments.".into()),
+            Self::JumpOutOfValof(..) => Some("`valof` expressions are left with `resultis`.".into()),
+            Self::JumpAcrossFunctions(..) => Some("Labels are local to the function or lambda they are defined in.".into()),
+            Self::JumpIntoBlock(..) => Some("Labels are only visible in the block they are defined in and the blocks nested in it.".into()),
+            Self::JumpOverDecl(..) => Some("Move the declaration before the `goto` or after the label.".into()),
+            Self::CyclicRequire(_) => Some("Move the declarations they share into a section of their own.".into()),
+            Self::DuplicateDecl(..) => Some("A program has a single entry point `main`.".into()),
+            Self::AmbiguousIdent(ident, section, _) => Some(format!("Qualify it like `{section}.{ident}`.")),
+            Self::PrivateDecl(..) => Some("Static variables can only be used in the section declaring them.".into()),
+            Self::NotRequired(_, section) => Some(format!("Add `require {section}` to use it.")),
+            Self::ShadowedQualified(..) => Some("Rename the local variable.".into()),
+            Self::UnknownDirective(_) => Some("Directives are `%n`, `%i`, `%x`, `%o`, `%b`, `%s`, `%c` and `%f`; `%%` prints a `%`.".into()),
+            Self::InvalidCast(..) => Some("Construct a new record from the fields instead.".into()),
+            Self::NoField(_, typ) if typ == "?" => Some("The type of a record must be known before its fields are accessed.".into()),
+            _ => None
+        }
+    }
+
+    fn additional(&self) -> Vec<Located<CompilerError>> {
+        match self {
+            Self::Mismatch(_, _, Some(decl_loc)) => vec![
+                CompilerError::new(Severity::Hint, "Expected type declared here.".into(), None, vec![])
+                    .with_location(decl_loc.clone())
+            ],
+            Self::WrongArity(ident, _, _, _, Some(decl_loc)) => vec![
+                CompilerError::new(Severity::Hint, format!("`{ident}` declared here."), None, vec![])
+                    .with_location(decl_loc.clone())
+            ],
+            Self::DuplicateLabel(_, first_loc) => vec![
+                CompilerError::new(Severity::Hint, "First defined here.".into(), None, vec![])
+                    .with_location(first_loc.clone())
+            ],
+            Self::JumpIntoValof(label, label_loc) | Self::JumpOutOfValof(label, label_loc)
+                | Self::JumpAcrossFunctions(label, label_loc) | Self::JumpIntoBlock(label, label_loc) => vec![
+                CompilerError::new(Severity::Hint, format!("`{label}` defined here."), None, vec![])
+                    .with_location(label_loc.clone())
+            ],
+            Self::JumpOverDecl(_, decl_loc) => vec![
+                CompilerError::new(Severity::Hint, "Declaration skipped here.".into(), None, vec![])
+                    .with_location(decl_loc.clone())
+            ],
+            Self::DuplicateDecl(_, _, first_loc) => vec![
+                CompilerError::new(Severity::Hint, "First declared here.".into(), None, vec![])
+                    .with_location(first_loc.clone())
+            ],
+            Self::PrivateDecl(ident, _, decl_loc) | Self::ShadowedQualified(_, ident, decl_loc) => vec![
+                CompilerError::new(Severity::Hint, format!("`{ident}` declared here."), None, vec![])
+                    .with_location(decl_loc.clone())
+            ],
+            _ => vec![]
+        }
+    }
+}
+
+fn num_args(required: usize, max: usize) -> String {
+    match (required, max) {
+        (1, 1) => "1 argument".into(),
+        _ if required == max => format!("{max} arguments"),
+        _ => format!("{required} to {max} arguments")
+    }
+}
 
+impl WithLocation for TypeCheckError {}
+
+impl Display for TypeCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message: String = match self {
+            Self::UndefinedIdent(ident, _) => format!("Undefined identifier `{ident}`."),
+            Self::Mismatch(expected, found, _) => format!("Mismatched types: expected `{expected}`, found `{found}`."),
+            Self::BadOperand(op, typ) => format!("Operator `{op}` cannot be applied to a value of type `{typ}`."),
+            Self::WrongArity(ident, required, max, given, _) => format!(
+                "`{ident}` expects {}, but {given} {} given.",
+                num_args(*required, *max), if *given == 1 { "was" } else { "were" }
+            ),
+            Self::NonBoolCondition(typ) => format!("Condition is of type `{typ}` instead of `Bool`."),
+            Self::InvalidDeref(typ) => format!("Cannot dereference a value of type `{typ}`."),
+            Self::InvalidIndex(typ) => format!("Cannot index into a value of type `{typ}`."),
+            Self::NoField(field, typ) => format!("Type `{typ}` has no field `{field}`."),
+            Self::NotCallable(typ) => format!("Cannot call a value of type `{typ}`."),
+            Self::InvalidCast(from, to) => format!("Cannot cast a record of type `{from}` to `{to}`, which is laid out differently."),
+            Self::NonExhaustive(cases, more) => format!(
+                "Non-exhaustive patterns: {}{} not covered.",
+                cases.iter().map(|case| format!("`{case}`")).collect::<Vec<_>>().join(", "),
+                if *more { " and more" } else { "" }
+            ),
+            Self::UnreachableBranch => "Unreachable branch.".into(),
+            Self::NotConstant => "Expression is not a compile-time constant.".into(),
+            Self::CyclicConstant(ident) => format!("Manifest constant `{ident}` depends on itself."),
+            Self::ConstantDivisionByZero => "Division by zero in constant expression.".into(),
+            Self::InvalidGlobal(index) => format!("Global number {index} is outside of the global vector (0 to {}).", ast::GLOBAL_VECTOR_SIZE - 1),
+            Self::InvalidVecSize(size) => format!("Vector size {size} is negative."),
+            Self::NotAssignable => "Expression cannot be assigned to.".into(),
+            Self::AssignToCapture(ident) => format!("Cannot assign to `{ident}`, which is captured by a lambda."),
+            Self::NotGeneric(ident) => format!("Type `{ident}` has no type parameters."),
+            Self::InstantiationLimit(ident) => format!("Generic function `{ident}` is instantiated with infinitely many type arguments."),
+            Self::UndefinedLabel(label, _) => format!("Undefined label `{label}`."),
+            Self::DuplicateLabel(label, _) => format!("Redefinition of label `{label}`."),
+            Self::JumpIntoValof(label, _) => format!("Cannot `goto` label `{label}` inside of a `valof` expression."),
+            Self::JumpOutOfValof(label, _) => format!("Cannot `goto` label `{label}` outside of the enclosing `valof` expression."),
+            Self::JumpAcrossFunctions(label, _) => format!("Cannot `goto` label `{label}` of another function."),
+            Self::JumpIntoBlock(label, _) => format!("Cannot `goto` label `{label}` inside of a nested statement."),
+            Self::JumpOverDecl(label, _) => format!("`goto` label `{label}` skips a `let` declaration."),
+            Self::UnknownSection(section, _) => format!("Required section `{section}` does not exist."),
+            Self::CyclicRequire(cycle) => format!(
+                "Sections require each other: {}.",
+                cycle.iter().map(|section| format!("`{section}`")).collect::<Vec<_>>().join(" -> ")
+            ),
+            Self::DuplicateDecl(ident, section, _) => format!("`{ident}` is already declared in section `{section}`."),
+            Self::AmbiguousIdent(ident, first, second) => format!("`{ident}` is declared in both sections `{first}` and `{second}`."),
+            Self::PrivateDecl(ident, section, _) => format!("`{ident}` is private to section `{section}`."),
+            Self::NotRequired(ident, section) => format!("`{ident}` is declared in section `{section}`, which is not required here."),
+            Self::NoSectionDecl(section, ident, _) => format!("Section `{section}` has no public declaration `{ident}`."),
+            Self::ShadowedQualified(section, ident, _) => format!("`{section}.{ident}` is shadowed by the local variable `{ident}`."),
+            Self::UnknownDirective(ch) => format!("Unknown `writef` directive `%{ch}`."),
+            Self::FormatArgCount(expected, given) => format!(
+                "Format string expects {}, but {given} {} given.",
+                num_args(*expected, *expected), if *given == 1 { "was" } else { "were" }
+            ),
+            Self::FormatArgType(directive, expected, found) => format!("Directive `{directive}` expects {expected}, found `{found}`.")
+        };
+        f.write_str(&message)
+    }
+}
+
+impl From<TypeCheckError> for CompilerError {
+    fn from(value: TypeCheckError) -> Self {
+        CompilerError::new(value.severity(), value.to_string(), value.hint(), value.additional())
+    }
 }
 
 pub struct TypeChecker<'a> {
-    scope: Scope<'a>
+    program: &'a ast::Program,
+    scope: Scope<'a>,
+    errors: Vec<Error>,
+
+    // section currently visited
+    section: Option<&'a ast::Section>,
+    // section of the qualified identifier visited next, like `Std` in `Std.writef`, and the location of both
+    qualifier: Option<(String, Location)>,
+
+    // scope depth outside of each lambda currently visited, and the variables it captures
+    lambdas: Vec<(usize, Vec<String>)>,
+    // identifiers declared by several sections, replaced by `Section.ident` once resolved
+    shared: HashSet<String>
 }
 
 impl<'a> TypeChecker<'a> {
-    fn new(scope: Scope<'a>) -> Self {
+    fn new(program: &'a ast::Program) -> Self {
         Self {
-            scope
+            program,
+            scope: Scope::new(None),
+            errors: vec![],
+            section: None,
+            qualifier: None,
+            lambdas: vec![],
+            shared: modules::shared_idents(program)
+        }
+    }
+
+    fn variant_idents(&self) -> impl Iterator<Item = &'a String> {
+        self.program.types().iter()
+            .filter_map(|typ| match typ.kind() {
+                TypeKind::Sum(variants) => Some(variants.iter().map(|variant| variant.ident())),
+                _ => None
+            })
+            .flatten()
+    }
+
+    fn is_nullary_variant(&self, ident: &str) -> bool {
+        self.program.types().find_variant(ident)
+            .map(|(_, _, variant)| variant.fields().is_empty())
+            .unwrap_or(false)
+    }
+
+    fn resolve(&mut self, ident: &str, loc: &Location) {
+        if self.scope.lookup(ident).is_some() || self.program.types().find_variant(ident).is_some() {
+            return
+        }
+
+        if let Some(err) = self.foreign_decl(ident) {
+            self.errors.push(err.with_location(loc.clone()));
+            return
+        }
+        let natives = self.native_functions();
+        if natives.iter().any(|native| native == ident) {
+            return
+        }
+
+        let similar = scope::most_similar(ident, self.scope.idents().chain(self.variant_idents()).chain(natives.iter())).cloned();
+        self.errors.push(TypeCheckError::UndefinedIdent(ident.to_string(), similar).with_location(loc.clone()));
+    }
+
+    // section declaring the shared identifier `ident` that resolved to a declaration; the own section's
+    // declaration hides those of required sections
+    fn declaring_section(&mut self, ident: &str, loc: &Location) -> Option<String> {
+        if !self.shared.contains(ident) || !matches!(self.scope.lookup(ident), Some(Binding::Decl(_))) {
+            return None
+        }
+
+        let current = self.section.expect("identifier outside of section");
+        if current.defines(&ident.to_string()).is_some() {
+            return Some(current.ident().clone())
+        }
+
+        let mut declaring = current.required().iter()
+            .filter_map(|required| self.program.sections().get(&**required))
+            .filter(|section| section.defines(&ident.to_string()).is_some_and(|decl| decl.is_public()))
+            .map(|section| section.ident().clone())
+            .collect::<Vec<_>>();
+        declaring.sort();
+        if let [first, second, ..] = declaring.as_slice() {
+            let err = TypeCheckError::AmbiguousIdent(ident.to_string(), first.clone(), second.clone());
+            self.errors.push(err.with_location(loc.clone()));
+        }
+        declaring.into_iter().next()
+    }
+
+    // functions provided natively for the current section and the external sections it requires
+    fn native_functions(&self) -> Vec<String> {
+        let section = self.section.expect("identifier outside of section");
+        [section.ident()].into_iter()
+            .chain(section.required().iter().map(|required| &**required))
+            .flat_map(|section| modules::native_functions(section))
+            .map(|native| native.to_string())
+            .collect()
+    }
+
+    // why `ident` is unresolved although another section of the program declares it
+    fn foreign_decl(&self, ident: &str) -> Option<TypeCheckError> {
+        let mut sections = self.program.sections().values().collect::<Vec<_>>();
+        sections.sort_by_key(|section| section.ident());
+
+        sections.into_iter()
+            .filter_map(|section| section.defines(&ident.to_string()).map(|decl| (section, decl)))
+            .map(|(section, decl)| if decl.is_public() {
+                TypeCheckError::NotRequired(ident.to_string(), section.ident().clone())
+            }
+            else {
+                TypeCheckError::PrivateDecl(ident.to_string(), section.ident().clone(), decl.location().clone())
+            })
+            .next()
+    }
+
+    // whether `base.field` refers to a declaration of the section `base` instead of a record field
+    fn is_qualifier(&self, base: &str) -> bool {
+        self.scope.lookup(base).is_none()
+            && self.program.types().find_variant(base).is_none()
+            && (self.program.sections().contains_key(base) || modules::is_external(base))
+    }
+
+    fn resolve_qualified(&mut self, section: &str, ident: &str, loc: &Location) {
+        let current = self.section.expect("identifier outside of section");
+        let own = current.ident() == section;
+
+        if let Some(declaring) = self.program.sections().get(section) {
+            let err = match declaring.defines(&ident.to_string()) {
+                Some(decl) if !decl.is_public() && !own =>
+                    Some(TypeCheckError::PrivateDecl(ident.to_string(), section.to_string(), decl.location().clone())),
+                Some(_) => None,
+                None if modules::native_functions(section).contains(&ident) => None,
+                None => {
+                    let natives = modules::native_functions(section).iter().map(|native| native.to_string()).collect::<Vec<_>>();
+                    let visible = declaring.declarations().values()
+                        .filter(|decl| decl.is_public() || own)
+                        .map(|decl| decl.ident())
+                        .chain(natives.iter());
+                    Some(TypeCheckError::NoSectionDecl(section.to_string(), ident.to_string(), scope::most_similar(ident, visible).cloned()))
+                }
+            };
+            if let Some(err) = err {
+                self.errors.push(err.with_location(loc.clone()));
+                return
+            }
+        }
+
+        if !own && !current.required().iter().any(|required| **required == section) {
+            self.errors.push(TypeCheckError::NotRequired(ident.to_string(), section.to_string()).with_location(loc.clone()));
+        }
+        else if let Some(Binding::Local(local_loc)) = self.scope.lookup(ident) {
+            let err = TypeCheckError::ShadowedQualified(section.to_string(), ident.to_string(), local_loc.clone());
+            self.errors.push(err.with_location(loc.clone()));
+        }
+    }
+
+    // local variables declared outside of a lambda are captured by it and all lambdas in between
+    fn capture(&mut self, ident: &str) {
+        let (Some(Binding::Local(..)), Some(depth)) = (self.scope.lookup(ident), self.scope.depth_of(ident))
+        else {
+            return
+        };
+
+        for (lambda_depth, captures) in self.lambdas.iter_mut().rev() {
+            if depth > *lambda_depth {
+                break
+            }
+            if !captures.iter().any(|capture| capture == ident) {
+                captures.push(ident.to_string());
+            }
+        }
+    }
+
+    fn is_captured(&self, ident: &str) -> bool {
+        matches!(
+            (self.lambdas.last(), self.scope.depth_of(ident)),
+            (Some((lambda_depth, _)), Some(depth)) if depth <= *lambda_depth
+        )
+    }
+
+    // only variables and memory locations can be assigned to
+    fn check_assignable(&mut self, target: &Expr) {
+        let assignable = match target.kind() {
+            ExprKind::Ident(ident) => match self.scope.lookup(ident) {
+                Some(Binding::Local(..)) if self.is_captured(ident) => {
+                    self.errors.push(TypeCheckError::AssignToCapture(ident.clone()).with_location(target.location().clone()));
+                    return
+                }
+                Some(Binding::Local(..)) => true,
+                Some(Binding::Decl(decl)) => decl.as_any().is::<ast::StaticDecl>() || decl.as_any().is::<ast::GlobalDecl>(),
+                // undefined identifiers are already reported
+                None => self.program.types().find_variant(ident).is_none()
+            },
+            ExprKind::Deref(_) | ExprKind::Index(..) | ExprKind::Field(..) => true,
+            _ => false
+        };
+
+        if !assignable {
+            self.errors.push(TypeCheckError::NotAssignable.with_location(target.location().clone()));
         }
     }
 }
@@ -25,55 +415,179 @@ impl<'a> TypeChecker<'a> {
 type Error = Located<TypeCheckError>;
 
 unsafe fn get_ref<'a, T>(r: &T) -> &'a T {
-    (r as *const T).as_ref().unwrap()
+    unsafe { (r as *const T).as_ref().unwrap() }
 }
 
-pub fn typecheck_ast(ast: Arc<Mutex<ast::Program>>) -> Result<(), Error> {
+// returns the warnings on success, or all errors and warnings
+pub fn typecheck_ast(ast: Arc<Mutex<ast::Program>>) -> Result<Vec<Error>, Vec<Error>> {
     let mut ast = ast.lock().unwrap();
-    let mut typechecker = TypeChecker::new(Scope::toplevel(unsafe { get_ref(&ast) }));
-    println!("{:#?}", typechecker.scope);
 
-    ast.traverse(&mut typechecker).map(|_| ())
+    // each section is checked with the declarations of the sections it requires
+    let errors = modules::resolve_requires(&mut ast);
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    let mut typechecker = TypeChecker::new(unsafe { get_ref(&*ast) });
+
+    // the typechecker collects all errors itself instead of aborting the traversal
+    let _ = ast.traverse(&mut typechecker);
+    if !typechecker.errors.is_empty() {
+        return Err(typechecker.errors)
+    }
+    modules::rename_shared(&mut ast, &typechecker.shared);
+
+    let errors = labels::resolve_labels(&mut ast);
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    // types can only be inferred once all identifiers are resolved
+    let errors = infer::infer_types(&mut ast);
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    let errors = constant::fold_constants(&mut ast);
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    let errors = format::check_formats(&mut ast);
+    if !errors.is_empty() {
+        return Err(errors)
+    }
+
+    let mut pattern_checker = exhaustiveness::PatternChecker::new(unsafe { get_ref(ast.types()) });
+    let _ = ast.traverse(&mut pattern_checker);
+    let mut diagnostics = pattern_checker.diagnostics();
+
+    // generic functions are checked once, their instances are only needed for code generation
+    if !diagnostics.iter().any(|diagnostic| matches!(diagnostic.severity(), Severity::Error)) {
+        diagnostics.extend(monomorphise::monomorphise(&mut ast));
+    }
+
+    if diagnostics.iter().any(|diagnostic| matches!(diagnostic.severity(), Severity::Error)) {
+        Err(diagnostics)
+    }
+    else {
+        Ok(diagnostics)
+    }
+}
+
+impl<'a> ScopeVisitor for TypeChecker<'a> {
+    fn enter_scope(&mut self) {
+        self.scope.push();
+    }
+
+    fn leave_scope(&mut self) {
+        self.scope.pop();
+    }
 }
 
 impl<'a> Visitor<ast::Program, Error> for TypeChecker<'a> {
-    fn visit(&mut self, node: &mut ast::Program) -> Result<ast::visitor::Action, Error> {
-        Ok(ast::visitor::Action::Continue) 
+    fn visit(&mut self, _node: &mut ast::Program) -> Result<ast::visitor::Action, Error> {
+        Ok(ast::visitor::Action::Continue)
     }
 }
 
 impl<'a> Visitor<ast::Section, Error> for TypeChecker<'a> {
-    fn visit(&mut self, node: &mut ast::Section) -> Result<ast::visitor::Action, Error> {
+    fn visit_before(&mut self, node: &mut ast::Section) -> Result<ast::visitor::Action, Error> {
+        let section = &self.program.sections()[node.ident()];
+        self.section = Some(section);
+        self.scope = Scope::toplevel(self.program, section);
+        Ok(ast::visitor::Action::Continue)
+    }
+
+    fn visit(&mut self, _node: &mut ast::Section) -> Result<ast::visitor::Action, Error> {
         Ok(ast::visitor::Action::Continue)
     }
 }
 
 impl<'a> Visitor<ast::Function, Error> for TypeChecker<'a> {
-    fn visit(&mut self, node: &mut ast::Function) -> Result<ast::visitor::Action, Error> {
+    fn visit(&mut self, _node: &mut ast::Function) -> Result<ast::visitor::Action, Error> {
         Ok(ast::visitor::Action::Continue)
     }
 }
 
 impl<'a> Visitor<ast::Param, Error> for TypeChecker<'a> {
-    fn visit(&mut self, node: &mut ast::Param) -> Result<ast::visitor::Action, Error> {
-        Ok(ast::visitor::Action::Continue) 
+    fn visit(&mut self, _node: &mut ast::Param) -> Result<ast::visitor::Action, Error> {
+        Ok(ast::visitor::Action::Continue)
     }
 }
 
 impl<'a> Visitor<ast::stmt::Stmt, Error> for TypeChecker<'a> {
     fn visit(&mut self, node: &mut ast::stmt::Stmt) -> Result<ast::visitor::Action, Error> {
-        Ok(ast::visitor::Action::Continue) 
-    } 
+        match node.kind() {
+            StmtKind::Assign(targets, _) => targets.iter().for_each(|target| self.check_assignable(target)),
+            StmtKind::CompoundAssign(operation) => {
+                let (target, _) = operation.binary_operands().expect("compound assignment without operator");
+                self.check_assignable(target);
+            }
+            _ => ()
+        }
+        Ok(ast::visitor::Action::Continue)
+    }
 }
 
 impl<'a> Visitor<ast::expr::Expr, Error> for TypeChecker<'a> {
+    fn visit_before(&mut self, node: &mut ast::expr::Expr) -> Result<ast::visitor::Action, Error> {
+        if let ExprKind::Lambda(_) = node.kind() {
+            self.lambdas.push((self.scope.depth(), vec![]));
+        }
+
+        // `Section.ident` is resolved like `ident`, so it needs no support in later stages
+        if let ExprKind::Field(base, field) = node.kind()
+            && let ExprKind::Ident(section) = base.kind()
+            && self.is_qualifier(section) {
+            // spans `Section.ident` instead of only the `.`
+            let mut loc = base.location().clone();
+            if loc.line() == node.location().line() {
+                loc.set_width(node.location().column() + 1 + field.len() - loc.column());
+            }
+            self.qualifier = Some((section.clone(), loc));
+            *node.kind_mut() = ExprKind::Ident(field.clone());
+        }
+        Ok(ast::visitor::Action::Continue)
+    }
+
     fn visit(&mut self, node: &mut ast::expr::Expr) -> Result<ast::visitor::Action, Error> {
+        match node.kind_mut() {
+            ExprKind::Ident(ident) => {
+                let ident = ident.clone();
+                let section = if let Some((section, loc)) = self.qualifier.take() {
+                    self.resolve_qualified(&section, &ident, &loc);
+                    Some(section)
+                }
+                else {
+                    self.resolve(&ident, node.location());
+                    self.capture(&ident);
+                    self.declaring_section(&ident, node.location())
+                };
+
+                // declarations of several sections are renamed after resolving all identifiers
+                if let Some(section) = section && self.shared.contains(&ident) {
+                    *node.kind_mut() = ExprKind::Ident(format!("{section}.{ident}"));
+                }
+            }
+            ExprKind::Lambda(lambda) => {
+                let (_, captures) = self.lambdas.pop().expect("lambda without capture list");
+                lambda.set_captures(captures);
+            }
+            _ => ()
+        }
         Ok(ast::visitor::Action::Continue)
     }
 }
 
-impl<'a> Visitor<ast::pattern::Pattern, Error> for TypeChecker<'a> {
-    fn visit(&mut self, node: &mut ast::pattern::Pattern) -> Result<ast::visitor::Action, Error> {
+impl<'a> Visitor<Located<Pattern>, Error> for TypeChecker<'a> {
+    fn visit(&mut self, node: &mut Located<Pattern>) -> Result<ast::visitor::Action, Error> {
+        match &**node {
+            Pattern::Query(ident) if ident == "_" || self.is_nullary_variant(ident) => (),
+            Pattern::Query(ident) => self.scope.declare(ident.clone(), Binding::Local(node.location().clone())),
+            Pattern::Variant(ident, _) => self.resolve(ident, node.location()),
+            _ => ()
+        }
         Ok(ast::visitor::Action::Continue)
     }
 }
@@ -0,0 +1,157 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast,
+    source_file::{Located, WithLocation}
+};
+
+use super::{scope, Error, TypeCheckError};
+
+// sections whose declarations are provided by the runtime and the interpreter, either completely or
+// in addition to the bundled source code of the section, with the functions implemented natively
+pub(super) const EXTERNAL_SECTIONS: &[(&str, &[&str])] = &[
+    ("Std", &["writef", "writes", "writen", "wrch", "newline", "rdch", "newvec", "freevec", "stop"])
+];
+
+pub(crate) fn is_external(section: &str) -> bool {
+    EXTERNAL_SECTIONS.iter().any(|(ident, _)| *ident == section)
+}
+
+// functions the runtime and the interpreter provide for `section`
+pub(crate) fn native_functions(section: &str) -> &'static [&'static str] {
+    EXTERNAL_SECTIONS.iter()
+        .find(|(ident, _)| *ident == section)
+        .map(|(_, functions)| *functions)
+        .unwrap_or_default()
+}
+
+const ENTRY_POINT: &str = "main";
+
+// identifiers declared by more than one section, which are kept apart by `rename_shared`
+pub(super) fn shared_idents(ast: &ast::Program) -> HashSet<String> {
+    let mut declared = HashSet::new();
+    ast.sections().values()
+        .flat_map(|section| section.declarations().keys())
+        .filter(|ident| !declared.insert(*ident))
+        .cloned()
+        .collect()
+}
+
+// Renames the declarations of identifiers shared by several sections to `Section.ident`, which the
+// typechecker already substituted for the identifiers referring to them. Later stages, generated
+// code and the interpreter look up declarations by name only.
+pub(super) fn rename_shared(ast: &mut ast::Program, shared: &HashSet<String>) {
+    for section in ast.sections_mut().values_mut() {
+        let prefix = section.ident().clone();
+        for ident in shared {
+            if let Some(mut decl) = section.declarations_mut().remove(ident) {
+                decl.rename(format!("{prefix}.{ident}"));
+                section.declare(decl);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Visiting,
+    Done
+}
+
+// Checks that every required section exists, that only one section declares `main` and that no
+// section requires itself through others, then orders the sections so each one comes after those it requires.
+pub(super) fn resolve_requires(ast: &mut ast::Program) -> Vec<Error> {
+    let mut idents = ast.sections().keys().cloned().collect::<Vec<_>>();
+    idents.sort();
+
+    let mut errors = vec![];
+    for ident in &idents {
+        for required in sorted_requires(&ast.sections()[ident]) {
+            if ast.sections().contains_key(&**required) || is_external(required) {
+                continue
+            }
+
+            let external = EXTERNAL_SECTIONS.iter().map(|(section, _)| section.to_string()).collect::<Vec<_>>();
+            let similar = scope::most_similar(required, idents.iter().chain(external.iter())).cloned();
+            errors.push(TypeCheckError::UnknownSection(required.to_string(), similar).with_location(required.location().clone()));
+        }
+    }
+
+    // the entry point is looked up by name, so only one section can declare it
+    let mut mains = idents.iter().filter_map(|ident| ast.sections()[ident].defines(&ENTRY_POINT.to_string()).map(|decl| (ident, decl)));
+    if let Some((first_section, first)) = mains.next() {
+        for (_, decl) in mains {
+            let err = TypeCheckError::DuplicateDecl(ENTRY_POINT.to_string(), first_section.to_string(), first.location().clone());
+            errors.push(err.with_location(decl.location().clone()));
+        }
+    }
+
+    if !errors.is_empty() {
+        return errors
+    }
+
+    let mut resolver = RequireResolver {
+        program: ast,
+        states: HashMap::new(),
+        path: vec![],
+        order: vec![],
+        errors: vec![]
+    };
+    for ident in &idents {
+        resolver.visit(ident);
+    }
+
+    let RequireResolver { order, errors, .. } = resolver;
+    if errors.is_empty() {
+        ast.set_section_order(order);
+    }
+    errors
+}
+
+fn sorted_requires(section: &ast::Section) -> Vec<&Located<String>> {
+    let mut required = section.required().iter().collect::<Vec<_>>();
+    required.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    required
+}
+
+struct RequireResolver<'a> {
+    program: &'a ast::Program,
+    states: HashMap<&'a str, State>,
+    // sections currently visited with the `require` leading to the next one
+    path: Vec<(&'a str, Option<&'a Located<String>>)>,
+    order: Vec<String>,
+    errors: Vec<Error>
+}
+
+impl<'a> RequireResolver<'a> {
+    fn visit(&mut self, ident: &'a str) {
+        match self.states.get(ident) {
+            Some(State::Done) => return,
+            Some(State::Visiting) => {
+                let start = self.path.iter().position(|(section, _)| *section == ident).expect("visited section outside of path");
+                let mut cycle = self.path[start..].iter().map(|(section, _)| section.to_string()).collect::<Vec<_>>();
+                cycle.push(ident.to_string());
+
+                let loc = self.path[start].1.expect("cycle without `require`").location().clone();
+                self.errors.push(TypeCheckError::CyclicRequire(cycle).with_location(loc));
+                return
+            }
+            None => ()
+        }
+
+        self.states.insert(ident, State::Visiting);
+        let section = &self.program.sections()[ident];
+        self.path.push((ident, None));
+        for required in sorted_requires(section) {
+            if !self.program.sections().contains_key(&**required) {
+                continue
+            }
+            self.path.last_mut().unwrap().1 = Some(required);
+            self.visit(required);
+        }
+        self.path.pop();
+
+        self.states.insert(ident, State::Done);
+        self.order.push(ident.to_string());
+    }
+}
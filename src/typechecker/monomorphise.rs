@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        self,
+        expr::{Expr, ExprKind},
+        pattern::Pattern,
+        stmt::Stmt,
+        types::{RecordField, TypeIndex, TypeKind, TypeList},
+        visitor::{Action, ScopeVisitor, Traversable, Visitor},
+        Decl, Function, IntoDecl
+    },
+    source_file::{Located, WithLocation}
+};
+
+use super::{Error, TypeCheckError};
+
+// more instances of a single generic function only come from recursion with growing type arguments
+const MAX_INSTANCES: usize = 64;
+
+// Generic functions are copied for every combination of type arguments they are used with, with the
+// type parameters substituted. The instances are named `ident.N`, which no identifier of the source can clash with,
+// and replace all references to the generic function, so code generation never sees type parameters.
+pub(super) fn monomorphise(ast: &mut ast::Program) -> Vec<Error> {
+    let generics = ast.sections().iter()
+        .flat_map(|(section, decls)| decls.declarations().values().map(move |decl| (section, decl)))
+        .filter_map(|(section, decl)| decl.as_any().downcast_ref::<Function>()
+            .filter(|func| func.is_generic())
+            .map(|func| (func.ident().clone(), (section.clone(), func.clone())))
+        )
+        .collect::<HashMap<_, _>>();
+    if generics.is_empty() {
+        return vec![]
+    }
+
+    let mut monomorphiser = Monomorphiser {
+        types: std::mem::take(ast.types_mut()),
+        substitution: HashMap::new(),
+        instances: HashMap::new(),
+        num_instances: HashMap::new(),
+        pending: vec![],
+        in_generic: false,
+        errors: vec![]
+    };
+    let _ = ast.traverse(&mut monomorphiser);
+
+    while let Some((ident, type_args, instance_ident)) = monomorphiser.pending.pop() {
+        let (section, generic) = &generics[&ident];
+        let mut instance = generic.instance(instance_ident);
+        monomorphiser.substitution = generic.type_params().iter().cloned().zip(type_args).collect();
+        let _ = instance.traverse(&mut monomorphiser);
+        ast.sections_mut().get_mut(section)
+            .expect("generic function outside of a section")
+            .declare(instance.into_decl());
+    }
+
+    // substituted record types need their layouts
+    monomorphiser.types.compute_layouts();
+    *ast.types_mut() = monomorphiser.types;
+    monomorphiser.errors
+}
+
+struct Monomorphiser {
+    types: TypeList,
+    // type arguments of the instance currently visited
+    substitution: HashMap<String, TypeIndex>,
+    // identifiers of the instances by generic function and type arguments
+    instances: HashMap<(String, Vec<TypeIndex>), String>,
+    num_instances: HashMap<String, usize>,
+    pending: Vec<(String, Vec<TypeIndex>, String)>,
+    // generic functions themselves are only referenced through their instances
+    in_generic: bool,
+    errors: Vec<Error>
+}
+
+impl Monomorphiser {
+    fn substitute(&mut self, typ: TypeIndex) -> TypeIndex {
+        self.types.substitute(typ, &self.substitution)
+    }
+
+    // follows bound type variables and aliases, so equal type arguments are the same type index
+    fn canonical(&mut self, typ: TypeIndex) -> TypeIndex {
+        let Some(kind) = self.types.get(typ).map(|typ| typ.kind().clone())
+        else {
+            return typ
+        };
+
+        match kind {
+            TypeKind::Alias(_, Some(inner)) | TypeKind::Var(Some(inner)) => self.canonical(inner),
+            TypeKind::Pointer(inner) => {
+                let inner = self.canonical(inner);
+                self.types.get_or_define(TypeKind::Pointer(inner))
+            }
+            TypeKind::Slice(inner) => {
+                let inner = self.canonical(inner);
+                self.types.get_or_define(TypeKind::Slice(inner))
+            }
+            TypeKind::Array(inner, len) => {
+                let inner = self.canonical(inner);
+                self.types.get_or_define(TypeKind::Array(inner, len))
+            }
+            TypeKind::Record(fields) => {
+                let fields = fields.into_iter()
+                    .map(|field| RecordField::new(field.ident().clone(), self.canonical(field.typ())))
+                    .collect();
+                self.types.get_or_define(TypeKind::Record(fields))
+            }
+            TypeKind::Function(params, return_type) => {
+                let params = params.into_iter().map(|param| self.canonical(param)).collect();
+                let return_type = self.canonical(return_type);
+                self.types.get_or_define(TypeKind::Function(params, return_type))
+            }
+            TypeKind::Instance(ident, args, _) => {
+                let args = args.into_iter().map(|arg| self.canonical(arg)).collect();
+                self.types.instantiate(&ident, args)
+            }
+            _ => typ
+        }
+    }
+
+    // identifier of the instance of the generic function `ident` for `type_args`, queueing new instances
+    fn instance(&mut self, ident: &str, type_args: &[TypeIndex]) -> Option<String> {
+        let type_args = type_args.iter()
+            .map(|arg| {
+                let arg = self.substitute(*arg);
+                self.canonical(arg)
+            })
+            .collect::<Vec<_>>();
+
+        let key = (ident.to_string(), type_args);
+        if let Some(instance) = self.instances.get(&key) {
+            return Some(instance.clone())
+        }
+
+        let num_instances = self.num_instances.entry(ident.to_string()).or_default();
+        if *num_instances >= MAX_INSTANCES {
+            return None
+        }
+
+        let instance = format!("{ident}.{num_instances}");
+        *num_instances += 1;
+        self.pending.push((key.0.clone(), key.1.clone(), instance.clone()));
+        self.instances.insert(key, instance.clone());
+        Some(instance)
+    }
+}
+
+impl ScopeVisitor for Monomorphiser {}
+
+impl Visitor<ast::Program, Error> for Monomorphiser {
+    fn visit(&mut self, _node: &mut ast::Program) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<ast::Section, Error> for Monomorphiser {
+    fn visit(&mut self, _node: &mut ast::Section) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Function, Error> for Monomorphiser {
+    fn visit_before(&mut self, node: &mut Function) -> Result<Action, Error> {
+        self.in_generic = node.is_generic();
+        Ok(Action::Continue)
+    }
+
+    fn visit(&mut self, node: &mut Function) -> Result<Action, Error> {
+        if !self.in_generic && let Some(typ) = *node.return_type() {
+            let typ = self.substitute(typ);
+            node.set_return_type(typ);
+        }
+        self.in_generic = false;
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<ast::Param, Error> for Monomorphiser {
+    fn visit(&mut self, node: &mut ast::Param) -> Result<Action, Error> {
+        if !self.in_generic && let Some(typ) = *node.typ() {
+            let typ = self.substitute(typ);
+            node.set_typ(typ);
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Stmt, Error> for Monomorphiser {
+    fn visit(&mut self, _node: &mut Stmt) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Expr, Error> for Monomorphiser {
+    fn visit(&mut self, node: &mut Expr) -> Result<Action, Error> {
+        if self.in_generic {
+            return Ok(Action::Continue)
+        }
+
+        if let Some(typ) = *node.typ() {
+            let typ = self.substitute(typ);
+            node.set_typ(typ);
+        }
+
+        match node.kind_mut() {
+            ExprKind::Lambda(lambda) => if let Some(typ) = *lambda.return_type() {
+                let typ = self.substitute(typ);
+                lambda.set_return_type(typ);
+            }
+            ExprKind::Instance(ident, type_args) => match self.instance(ident, type_args) {
+                Some(instance) => *node.kind_mut() = ExprKind::Ident(instance),
+                None => {
+                    let err = TypeCheckError::InstantiationLimit(ident.clone());
+                    self.errors.push(err.with_location(node.location().clone()));
+                }
+            }
+            _ => ()
+        }
+        Ok(Action::Continue)
+    }
+}
+
+impl Visitor<Located<Pattern>, Error> for Monomorphiser {
+    fn visit(&mut self, _node: &mut Located<Pattern>) -> Result<Action, Error> {
+        Ok(Action::Continue)
+    }
+}
@@ -1,32 +1,112 @@
-use std::{collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
-use crate::ast::{Decl, types::{TypeIndex, TypeKind}, self};
+use crate::{ast::{Decl, self}, source_file::Location};
+
+// what an identifier got resolved to
+#[derive(Debug)]
+pub enum Binding<'a> {
+    Decl(&'a dyn Decl),
+    Local(Location)
+}
 
 #[derive(Debug)]
 pub struct Scope<'a> {
-    bindings: HashMap<&'a String, &'a dyn Decl>,
-    types: HashMap<&'a String, TypeIndex>,
+    bindings: HashMap<String, Binding<'a>>,
 
-    outer: Option<&'a Scope<'a>>
+    outer: Option<Box<Scope<'a>>>
 }
 
 impl<'a> Scope<'a> {
-    pub fn new(outer: Option<&'a Scope<'a>>) -> Self {
+    pub fn new(outer: Option<Box<Scope<'a>>>) -> Self {
         Self {
             bindings: HashMap::new(),
-            types: HashMap::new(),
             outer
         }
     }
 
-    pub fn toplevel(ast: &'a ast::Program) -> Self {
+    // declarations visible in `section`: its own and the public ones of the sections it requires
+    pub fn toplevel(ast: &'a ast::Program, section: &'a ast::Section) -> Self {
+        let required = section.required().iter()
+            .filter_map(|required| ast.sections().get(&**required))
+            .flat_map(|required| required.declarations().values())
+            .filter(|decl| decl.is_public());
+
         Self {
-            types: ast.types().iter().enumerate().filter_map(|(idx, typ)| match typ.kind() {
-                TypeKind::Alias(id, _) => Some((id, idx as u32)),
-                _ => None 
-            }).collect(),
-            bindings: HashMap::new(),
+            bindings: required.chain(section.declarations().values())
+                .map(|decl| (decl.ident().clone(), Binding::Decl(decl.as_ref())))
+                .collect(),
             outer: None
         }
     }
+
+    // makes a new, empty scope the innermost one
+    pub fn push(&mut self) {
+        let outer = std::mem::replace(self, Scope::new(None));
+        self.outer = Some(Box::new(outer));
+    }
+
+    pub fn pop(&mut self) {
+        let outer = self.outer.take().expect("popped the toplevel scope");
+        *self = *outer;
+    }
+
+    pub fn declare(&mut self, ident: String, binding: Binding<'a>) {
+        self.bindings.insert(ident, binding);
+    }
+
+    pub fn lookup(&self, ident: &str) -> Option<&Binding<'a>> {
+        self.bindings.get(ident).or_else(|| self.outer.as_ref()?.lookup(ident))
+    }
+
+    // number of scopes enclosing this one
+    pub fn depth(&self) -> usize {
+        self.outer.as_ref().map(|outer| outer.depth() + 1).unwrap_or(0)
+    }
+
+    // depth of the scope `ident` is declared in
+    pub fn depth_of(&self, ident: &str) -> Option<usize> {
+        if self.bindings.contains_key(ident) {
+            Some(self.depth())
+        }
+        else {
+            self.outer.as_ref()?.depth_of(ident)
+        }
+    }
+
+    // all identifiers visible from this scope
+    pub fn idents(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        let own = self.bindings.keys();
+        match &self.outer {
+            Some(outer) => Box::new(own.chain(outer.idents())),
+            None => Box::new(own)
+        }
+    }
+}
+
+// returns the candidate closest to `ident`, if it is similar enough to be a likely typo
+pub fn most_similar<'b>(ident: &str, candidates: impl Iterator<Item = &'b String>) -> Option<&'b String> {
+    let max_distance = (ident.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(ident, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        // ties are broken alphabetically, since candidates often come from hash maps
+        .min_by_key(|(distance, candidate)| (*distance, *candidate))
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + (ca != *cb) as usize;
+            row.push(substitution.min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
 }
@@ -0,0 +1,97 @@
+// format strings of `writef`, shared by the typechecker and the interpreter
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum DirectiveKind {
+    Number,  // `%n`, `%d`
+    Integer, // `%i`
+    Hex,     // `%x`
+    Octal,   // `%o`
+    Binary,  // `%b`
+    String,  // `%s`
+    Char,    // `%c`
+    Float    // `%f`
+}
+
+impl DirectiveKind {
+    fn from_char(ch: char) -> Option<Self> {
+        Some(match ch.to_ascii_lowercase() {
+            'n' | 'd' => Self::Number,
+            'i' => Self::Integer,
+            'x' => Self::Hex,
+            'o' => Self::Octal,
+            'b' => Self::Binary,
+            's' => Self::String,
+            'c' => Self::Char,
+            'f' => Self::Float,
+            _ => return None
+        })
+    }
+
+    // `%n` prints numbers in their natural width, so digits after it are printed as they are
+    fn takes_width(&self) -> bool {
+        !matches!(self, Self::Number)
+    }
+
+    // hexadecimal, octal and binary numbers are padded with zeros, everything else with spaces
+    pub(crate) fn pads_with_zeros(&self) -> bool {
+        matches!(self, Self::Hex | Self::Octal | Self::Binary)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Directive {
+    pub(crate) kind: DirectiveKind,
+    // minimum number of characters printed
+    pub(crate) width: usize,
+    // digits after the decimal point of `%f`
+    pub(crate) precision: Option<usize>
+}
+
+pub(crate) enum FormatPart {
+    Text(char),
+    Arg(Directive),
+    // `%` followed by a character that isn't a directive, printed as it is
+    Unknown(char)
+}
+
+// splits a `writef` format string into its parts, with the byte offset and length of each part;
+// escape sequences like `*n` are already decoded by the parser
+pub(crate) fn parse_format(format: &str) -> Vec<(usize, usize, FormatPart)> {
+    let mut parts = vec![];
+    let mut chars = format.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        let part = match ch {
+            '%' => match chars.next() {
+                Some((_, '%')) => FormatPart::Text('%'),
+                Some((_, ch)) => match DirectiveKind::from_char(ch) {
+                    Some(kind) => {
+                        let mut directive = Directive { kind, width: 0, precision: None };
+                        if kind.takes_width() {
+                            directive.width = digits(&mut chars).unwrap_or(0);
+                            if kind == DirectiveKind::Float && chars.next_if(|(_, ch)| *ch == '.').is_some() {
+                                directive.precision = Some(digits(&mut chars).unwrap_or(0));
+                            }
+                        }
+                        FormatPart::Arg(directive)
+                    }
+                    None => FormatPart::Unknown(ch)
+                },
+                None => FormatPart::Text('%')
+            },
+            _ => FormatPart::Text(ch)
+        };
+
+        let end = chars.peek().map(|(offset, _)| *offset).unwrap_or(format.len());
+        parts.push((start, end - start, part));
+    }
+    parts
+}
+
+fn digits(chars: &mut std::iter::Peekable<std::str::CharIndices>) -> Option<usize> {
+    let mut value = None;
+    while let Some((_, digit)) = chars.next_if(|(_, ch)| ch.is_ascii_digit()) {
+        value = Some(value.unwrap_or(0usize).saturating_mul(10).saturating_add(digit as usize - '0' as usize));
+    }
+    value
+}
+
@@ -0,0 +1,285 @@
+// Runs the programs in `tests/programs` with the interpreter and both code generators and compares
+// their output with the expected `.out` file next to each program.
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio}
+};
+
+const BACKENDS: &[&str] = &["c", "x86_64"];
+
+fn compiler() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_bcplpp"));
+    command.stdin(Stdio::null());
+    command
+}
+
+fn test_file(dir: &str, name: &str, extension: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(dir).join(format!("{name}.{extension}"))
+}
+
+// whether `line` is part of a warning printed by the compiler, like `[Warning] a.bpp:1:2: ...` and the
+// highlighted source lines after it
+fn is_diagnostic(line: &str) -> bool {
+    line.starts_with("[Warning] ") || line.starts_with("[Hint] ")
+        || line.split_once('|').is_some_and(|(number, _)| number.trim().chars().all(|ch| ch.is_ascii_digit()) && number.starts_with("  "))
+}
+
+// output of a program without the status lines and warnings of the compiler and terminal escape codes
+fn stdout(output: &Output) -> String {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut plain = String::new();
+    let mut chars = stdout.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            chars.by_ref().find(|ch| ch.is_ascii_alphabetic());
+        }
+        else {
+            plain.push(ch);
+        }
+    }
+
+    plain.lines()
+        .filter(|line| !line.starts_with("Compiling:") && !is_diagnostic(line))
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+
+fn interpret(name: &str, args: &[&str]) -> String {
+    let output = compiler().arg("--run").args(args).arg(test_file("programs", name, "bpp")).output().expect("error running bcplpp");
+    assert!(output.status.success(), "`{name}` failed with `--run`:\n{}", stdout(&output));
+    stdout(&output)
+}
+
+fn compile_and_run(name: &str, backend: &str, args: &[&str]) -> String {
+    let executable = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}_{backend}"));
+    let output = compiler()
+        .args(["--backend", backend, "-o"])
+        .arg(&executable)
+        .args(args)
+        .arg(test_file("programs", name, "bpp"))
+        .output()
+        .expect("error running bcplpp");
+    assert!(output.status.success(), "`{name}` failed to compile with the `{backend}` backend:\n{}", stdout(&output));
+
+    let output = Command::new(&executable).stdin(Stdio::null()).output().expect("error running compiled program");
+    assert!(output.status.success(), "`{name}` compiled with the `{backend}` backend failed");
+    stdout(&output)
+}
+
+// runs `tests/programs/<name>.bpp` with all backends and compares the output with `<name>.out`
+fn check(name: &str) {
+    check_with_args(name, &[])
+}
+
+// like `check`, passing `args` to the compiler
+fn check_with_args(name: &str, args: &[&str]) {
+    let expected = std::fs::read_to_string(test_file("programs", name, "out")).expect("error reading expected output");
+    assert_eq!(interpret(name, args), expected, "output of `{name}` with `--run`");
+    for backend in BACKENDS {
+        assert_eq!(compile_and_run(name, backend, args), expected, "output of `{name}` with the `{backend}` backend");
+    }
+}
+
+// checks that compiling `tests/errors/<name>.bpp` fails with `message`
+fn check_error(name: &str, message: &str) {
+    let output = compiler().arg("--run").arg(test_file("errors", name, "bpp")).output().expect("error running bcplpp");
+    let diagnostics = stdout(&output);
+    assert!(!output.status.success(), "`{name}` compiled without errors");
+    assert!(diagnostics.contains(message), "diagnostics of `{name}` don't contain `{message}`:\n{diagnostics}");
+}
+
+#[test]
+fn list_patterns() {
+    check("list_patterns");
+}
+
+#[test]
+fn records() {
+    check("records");
+}
+
+#[test]
+fn branch_labels() {
+    check("branch_labels");
+}
+
+#[test]
+fn goto() {
+    check("goto");
+    check_error("goto_over_let", "`goto` label `L` skips a `let` declaration.");
+    check_error("goto_into_for", "Cannot `goto` label `L` inside of a nested statement.");
+    check_error("goto_other_function", "Undefined label `L`.");
+}
+
+#[test]
+fn promotion() {
+    check("promotion");
+}
+
+#[test]
+fn wrapping() {
+    check("wrapping");
+}
+
+#[test]
+fn float_comparisons() {
+    check("float_comparisons");
+}
+
+#[test]
+fn resultis_mismatch() {
+    check_error("resultis_mismatch", "Mismatched types: expected `Int32`, found `Bool`.");
+}
+
+#[test]
+fn char_escapes() {
+    check("char_escapes");
+    check_error("unterminated_char", "unterminated character literal");
+}
+
+#[test]
+fn string_escapes() {
+    check("string_escapes");
+    check_error("unknown_string_escape", "Invalid string literal `\"100*%*n\"`.");
+}
+
+#[test]
+fn diagnostics_in_source_order() {
+    let output = compiler().arg("--run").arg(test_file("errors", "undefined_identifiers", "bpp")).output().expect("error running bcplpp");
+    let lines = stdout(&output).lines()
+        .filter_map(|line| line.strip_prefix("[Error] "))
+        .filter_map(|line| line.split(':').nth(1)?.parse::<usize>().ok())
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [3, 5, 7, 10]);
+}
+
+#[test]
+fn list_witnesses() {
+    check_error("missing_long_lists", "Non-exhaustive patterns: `[?, ?, ..]` not covered.");
+}
+
+#[test]
+fn std_natives_resolved() {
+    check_error("std_typo", "Undefined identifier `wirtef`.");
+    check_error("std_typo", "Undefined identifier `cuont`.");
+}
+
+#[test]
+fn basics() {
+    check("basics");
+}
+
+#[test]
+fn scopes() {
+    check("scopes");
+    check_error("undeclared_manifest", "Undefined identifier `C`.");
+}
+
+#[test]
+fn inference() {
+    check("inference");
+}
+
+#[test]
+fn type_errors() {
+    check_error("type_errors", "`add` expects 2 arguments, but 3 were given.");
+    check_error("type_errors", "Condition is of type `Int32` instead of `Bool`.");
+}
+
+#[test]
+fn exhaustiveness() {
+    check_error("missing_cases", "Non-exhaustive patterns: `false` not covered.");
+    check_error("missing_variants", "Non-exhaustive patterns: `Circle(false)`, `Square(?)`, `Point` not covered.");
+}
+
+#[test]
+fn decision_trees() {
+    check("decision_trees");
+}
+
+#[test]
+fn declarations() {
+    check("declarations");
+}
+
+#[test]
+fn assignment() {
+    check("assignment");
+}
+
+#[test]
+fn literals() {
+    check("literals");
+}
+
+#[test]
+fn vectors() {
+    check("vectors");
+    check_error("vector_size", "Vector size -1 is negative.");
+}
+
+#[test]
+fn tables() {
+    check("tables");
+}
+
+#[test]
+fn function_pointers() {
+    check("function_pointers");
+    check_error("function_arity", "`op` expects 2 arguments, but 1 was given.");
+}
+
+#[test]
+fn closures() {
+    check("closures");
+    check_error("captured_assignment", "Cannot assign to `k`, which is captured by a lambda.");
+}
+
+#[test]
+fn generics() {
+    check("generics");
+    check_error("generic_operator", "Operator `+` cannot be applied to a value of type `T`.");
+}
+
+#[test]
+fn loops() {
+    check("loops");
+    check_error("break_outside_loop", "Encountered `break` statement outside of");
+}
+
+#[test]
+fn parse_recovery() {
+    check_error("parse_recovery", "5:17: Unexpected token `;`; Expected `expression`.");
+    check_error("parse_recovery", "23:15: Unexpected token `;`; Expected `)`.");
+}
+
+#[test]
+fn modules() {
+    check("modules");
+}
+
+#[test]
+fn namespaces() {
+    check("namespaces");
+    check_error("ambiguous_ident", "`helper` is declared in both sections `Left` and `Right`.");
+}
+
+#[test]
+fn search_paths() {
+    let lib = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("lib");
+    check_with_args("search_paths", &["-I", lib.to_str().expect("non-UTF-8 path")]);
+    check_error("missing_section", "Section `Geometry` not found");
+}
+
+#[test]
+fn std_library() {
+    check("std_library");
+}
+
+#[test]
+fn writef_formats() {
+    check("writef_formats");
+    check_error("writef_errors", "Format string expects 2 arguments, but 1 was given.");
+    check_error("writef_errors", "Directive `%s` expects a string, found `Int32`.");
+    check_error("writef_errors", "Unknown `writef` directive `%q`.");
+}